@@ -0,0 +1,250 @@
+use crate::*;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+/// The current version of the election archive format
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// A summary of the transactions in an election archive.
+///
+/// The manifest is written ahead of the transactions, so a reader can check the archive is complete and unaltered
+/// before trusting any of it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ArchiveManifest {
+    pub election_id: Identifier,
+
+    /// The time the archive was written, in seconds since the unix epoch
+    pub created: u64,
+
+    /// The total number of transactions in the archive
+    pub transaction_count: u64,
+
+    /// The number of transactions of each type
+    pub counts: BTreeMap<TransactionType, u64>,
+
+    /// SHA-256 hash of every transaction, in the order they appear in the archive
+    #[serde(with = "hex_serde")]
+    pub transactions_hash: [u8; 32],
+}
+
+/// An election archive: every transaction in an election, in id order, and a manifest describing them.
+///
+/// Archives are self-contained, so an election can be published and audited long after the nodes that ran it are
+/// gone. An archive is a sequence of CBOR values: the format version, the manifest, then each signed transaction.
+#[derive(Debug, Clone)]
+pub struct ElectionArchive {
+    pub manifest: ArchiveManifest,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+/// The outcome of verifying an election archive
+#[derive(Debug)]
+pub struct ArchiveVerification {
+    pub manifest: ArchiveManifest,
+
+    /// Transactions that failed validation when the election was replayed, in order
+    pub failures: Vec<BulkValidationFailure>,
+
+    /// The tally of the election, or None if the election is incomplete or could not be tallied
+    pub tally: Option<IndexMap<String, TallyResult>>,
+}
+
+/// Write every transaction in an election from a store to an archive.
+///
+/// The store is read twice, once to build the manifest and once to write the transactions, so the election should not
+/// be modified while it is being archived.
+pub fn write_archive<S: Store, W: Write>(
+    store: &S,
+    election_id: Identifier,
+    mut writer: W,
+) -> Result<ArchiveManifest, Error> {
+    let election_id = Identifier::new(election_id, TransactionType::Election, None);
+    let start = Identifier::start(election_id, TransactionType::FIRST, None);
+    let end = Identifier::end(election_id, TransactionType::LAST, None);
+
+    let mut counts = BTreeMap::new();
+    let mut hasher = Sha256::new();
+    let mut transaction_count = 0;
+    for tx in store.range(start, end) {
+        let tx = tx?;
+        hash_transaction(&mut hasher, &tx);
+        *counts.entry(tx.transaction_type()).or_insert(0) += 1;
+        transaction_count += 1;
+    }
+
+    let manifest = ArchiveManifest {
+        election_id,
+        created: store.current_time()?,
+        transaction_count,
+        counts,
+        transactions_hash: hasher.finalize().into(),
+    };
+
+    serde_cbor::to_writer(&mut writer, &ARCHIVE_VERSION)?;
+    serde_cbor::to_writer(&mut writer, &manifest)?;
+    for tx in store.range(start, end) {
+        serde_cbor::to_writer(&mut writer, &tx?)?;
+    }
+
+    Ok(manifest)
+}
+
+/// Read an election archive, checking that it matches its manifest.
+///
+/// This does not validate the transactions themselves, use `verify_archive` or `replay_archive` to do so.
+pub fn read_archive<R: Read>(reader: R) -> Result<ElectionArchive, Error> {
+    let mut deserializer = serde_cbor::Deserializer::from_reader(reader);
+
+    let version = u32::deserialize(&mut deserializer)?;
+    if version != ARCHIVE_VERSION {
+        return Err(Error::ArchiveVersion(version));
+    }
+    let manifest = ArchiveManifest::deserialize(&mut deserializer)?;
+
+    // Don't trust the manifest's count for allocation until the transactions have been read
+    let mut transactions = Vec::with_capacity(manifest.transaction_count.min(1024) as usize);
+    let mut counts = BTreeMap::new();
+    let mut hasher = Sha256::new();
+    for _ in 0..manifest.transaction_count {
+        let tx = SignedTransaction::deserialize(&mut deserializer)?;
+        let id = tx.id();
+
+        // Transactions must belong to the election, and be in strictly ascending id order
+        let in_order = transactions
+            .last()
+            .map_or(true, |prev: &SignedTransaction| prev.id() < id);
+        if id.election_id != manifest.election_id.election_id || !in_order {
+            return Err(Error::ArchiveTransactionOutOfPlace(id));
+        }
+
+        hash_transaction(&mut hasher, &tx);
+        *counts.entry(tx.transaction_type()).or_insert(0) += 1;
+        transactions.push(tx);
+    }
+    deserializer.end()?;
+
+    let transactions_hash: [u8; 32] = hasher.finalize().into();
+    if counts != manifest.counts || transactions_hash != manifest.transactions_hash {
+        return Err(Error::ArchiveManifestMismatch);
+    }
+
+    Ok(ElectionArchive {
+        manifest,
+        transactions,
+    })
+}
+
+/// Replay an election archive into a store, validating every transaction (see `replay_election`).
+pub fn replay_archive<S, F>(
+    archive: ElectionArchive,
+    store: &mut S,
+    insert: F,
+) -> Vec<BulkValidationFailure>
+where
+    S: Store + Sync,
+    F: FnMut(&mut S, SignedTransaction),
+{
+    replay_election(archive.transactions, store, insert)
+}
+
+/// Replay every transaction of an election into a store, validating every transaction.
+///
+/// The transactions must be in id order, starting with the election transaction. Valid transactions are inserted into
+/// the store with `insert`. Invalid transactions are not inserted, and are reported in order.
+///
+/// An election is usually replayed long after it has ended, so time-dependent checks are made against the
+/// election's own schedule rather than the store's clock: votes are validated as though they were cast when voting
+/// opened, and later transactions as though their timestamps had already passed. Whether each vote actually arrived
+/// within the voting window was checked by the nodes that accepted it.
+pub fn replay_election<S, F>(
+    mut transactions: Vec<SignedTransaction>,
+    store: &mut S,
+    mut insert: F,
+) -> Vec<BulkValidationFailure>
+where
+    S: Store + Sync,
+    F: FnMut(&mut S, SignedTransaction),
+{
+    let voting_start = match transactions.first() {
+        Some(SignedTransaction::Election(election)) => election.voting_start.unwrap_or(0),
+        _ => 0,
+    };
+
+    // Transactions are in id order, so sorting by type puts them in the order they were posted. A replacement vote's id
+    // can sort before the vote it replaces (see `VoteTransaction::build_id`), so votes are also sorted by revision.
+    transactions.sort_by_key(|tx| {
+        let revision = match tx {
+            SignedTransaction::Vote(vote) => vote.revision,
+            _ => 0,
+        };
+        (tx.transaction_type().protocol_order(), revision)
+    });
+    let (during_voting, after_voting): (Vec<_>, Vec<_>) = transactions
+        .into_iter()
+        .partition(|tx| tx.transaction_type().before_voting_end());
+
+    let mut replay = ReplayStore {
+        store,
+        time: voting_start,
+    };
+    let mut failures = validate_bulk(&mut replay, during_voting, |replay, tx| {
+        insert(&mut *replay.store, tx)
+    });
+
+    replay.time = u64::MAX;
+    failures.extend(validate_bulk(&mut replay, after_voting, |replay, tx| {
+        insert(&mut *replay.store, tx)
+    }));
+
+    failures
+}
+
+/// Verify an election archive offline, replaying it into an empty in-memory store and tallying the election.
+pub fn verify_archive(archive: ElectionArchive) -> ArchiveVerification {
+    let manifest = archive.manifest.clone();
+
+    let mut store = MemStore::default();
+    let failures = replay_archive(archive, &mut store, |store, tx| store.set(tx));
+
+    let tally = store
+        .get_election(manifest.election_id)
+        .ok()
+        .and_then(|election| tally_election(&store, &election).ok());
+
+    ArchiveVerification {
+        manifest,
+        failures,
+        tally,
+    }
+}
+
+// Each transaction is hashed with its id and length, so the boundaries between transactions are unambiguous
+fn hash_transaction(hasher: &mut Sha256, tx: &SignedTransaction) {
+    let bytes = tx.as_bytes();
+    hasher.update(&tx.id().to_array());
+    hasher.update(&(bytes.len() as u64).to_be_bytes());
+    hasher.update(&bytes);
+}
+
+// A store with a clock that follows the election being replayed
+struct ReplayStore<'a, S: Store> {
+    store: &'a mut S,
+    time: u64,
+}
+
+impl<'a, S: Store> Store for ReplayStore<'a, S> {
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        self.store.get_transaction(id)
+    }
+
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_> {
+        self.store.range(start, end_inclusive)
+    }
+
+    fn current_time(&self) -> Result<u64, StoreError> {
+        Ok(self.time)
+    }
+}
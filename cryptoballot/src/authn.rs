@@ -1,18 +1,138 @@
 use crate::*;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
 use ed25519_dalek::PublicKey;
 use indexmap::IndexMap;
+use rand::{CryptoRng, Rng};
 use rsa::{RSAPrivateKey, RSAPublicKey};
 use rsa_fdh::blind;
-use sha2::Sha256;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-/// RSA Public Key for blind signing
+/// The blind-signature scheme used by an authenticator
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthKeyType {
+    /// RSA Full-Domain-Hash blind signatures
+    Rsa,
+
+    /// Schnorr blind signatures over ristretto255 (curve25519).
+    ///
+    /// Keys are much smaller and faster to generate than RSA keys, but signing takes an extra
+    /// round-trip, since the voter needs the authenticator's commitment before blinding (see `SchnorrSessions`).
+    Schnorr,
+}
+
+impl Default for AuthKeyType {
+    fn default() -> Self {
+        AuthKeyType::Rsa
+    }
+}
+
+/// Public Key for blind signing
 #[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct AuthPublicKey(#[serde(with = "RSAPublicKeyHex")] RSAPublicKey);
+#[serde(untagged)]
+pub enum AuthPublicKey {
+    Schnorr(#[serde(with = "RistrettoPointHex")] RistrettoPoint),
+    Rsa(#[serde(with = "RSAPublicKeyHex")] RSAPublicKey),
+}
+
+impl AuthPublicKey {
+    /// The blind-signature scheme this key is for
+    pub fn key_type(&self) -> AuthKeyType {
+        match self {
+            AuthPublicKey::Rsa(_) => AuthKeyType::Rsa,
+            AuthPublicKey::Schnorr(_) => AuthKeyType::Schnorr,
+        }
+    }
+}
+
+/// Secret Key for blind signing
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum AuthSecretKey {
+    Rsa(RSAPrivateKey),
+    Schnorr(#[serde(with = "ScalarHex")] Scalar),
+}
+
+/// The authenticator's commitment for a single Schnorr blind-signing session.
+///
+/// This is sent to the voter, who needs it to blind their auth package.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct SchnorrCommitment(#[serde(with = "RistrettoPointHex")] RistrettoPoint);
+
+/// The authenticator's secret nonce for a single Schnorr blind-signing session.
+///
+/// A nonce can only be obtained by closing a session with `SchnorrSessions::close`, and is consumed by signing,
+/// since signing two different blinded auth packages with the same nonce discloses the secret key.
+pub struct SchnorrNonce(Scalar);
+
+/// The open Schnorr blind-signing sessions of one or more authenticators.
+///
+/// Schnorr blind signatures are only secure if a voter cannot run many signing sessions concurrently: with enough
+/// open sessions a voter can combine the authenticator's responses into one more signature than they were given
+/// (the ROS attack). This allows at most one open session per authenticator and voter, so a voter has to finish
+/// a session before starting the next one.
+///
+/// Voters are identified by whatever the authenticator checks their bonefides against (eg a government-id).
+#[derive(Default)]
+pub struct SchnorrSessions {
+    open: HashMap<(Uuid, String), Scalar>,
+}
+
+impl SchnorrSessions {
+    /// Create an empty set of sessions
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-impl AsRef<RSAPublicKey> for AuthPublicKey {
-    fn as_ref(&self) -> &RSAPublicKey {
-        &self.0
+    /// Start a Schnorr blind-signing session for a voter, returning the commitment to send to them.
+    ///
+    /// Returns an error if the voter already has an open session with this authenticator.
+    pub fn open(
+        &mut self,
+        authenticator: &Authenticator,
+        voter: &str,
+    ) -> Result<SchnorrCommitment, Error> {
+        self.open_with_rng(&mut rand::rngs::OsRng {}, authenticator, voter)
+    }
+
+    /// Start a Schnorr blind-signing session for a voter, drawing the secret nonce from the given RNG.
+    ///
+    /// WARNING: The RNG must never repeat a nonce, see `SchnorrNonce`.
+    pub fn open_with_rng<R: Rng + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+        authenticator: &Authenticator,
+        voter: &str,
+    ) -> Result<SchnorrCommitment, Error> {
+        if authenticator.key_type != AuthKeyType::Schnorr {
+            return Err(Error::AuthKeyTypeMismatch);
+        }
+
+        let key = (authenticator.id, voter.to_string());
+        if self.open.contains_key(&key) {
+            return Err(Error::SchnorrSessionOpen(voter.to_string()));
+        }
+
+        let nonce = Scalar::random(rng);
+        self.open.insert(key, nonce);
+
+        Ok(SchnorrCommitment(nonce * RISTRETTO_BASEPOINT_POINT))
+    }
+
+    /// Finish a voter's Schnorr blind-signing session, returning the nonce to pass to `Authenticator::authenticate`.
+    pub fn close(
+        &mut self,
+        authenticator: &Authenticator,
+        voter: &str,
+    ) -> Result<SchnorrNonce, Error> {
+        self.open
+            .remove(&(authenticator.id, voter.to_string()))
+            .map(SchnorrNonce)
+            .ok_or(Error::MissingSchnorrCommitment)
     }
 }
 
@@ -33,14 +153,21 @@ impl AsRef<RSAPublicKey> for AuthPublicKey {
 ///
 /// WARNING: The secret keys used to sign blinded triplets must NOT be used for any other purpose.
 /// Doing so can result in secret key disclosure.
+///
+/// Schnorr authenticators sign in two rounds, tracking each voter's open session with `SchnorrSessions`.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Authenticator {
     pub id: uuid::Uuid,
+
+    /// The blind-signature scheme used by all of this authenticator's keys
+    #[serde(default, skip_serializing_if = "crate::util::is_default")]
+    pub key_type: AuthKeyType,
+
     pub public_keys: IndexMap<String, AuthPublicKey>,
 }
 
 impl Authenticator {
-    /// Create a new Authenticator, generating keys for provided ballot-ids.
+    /// Create a new RSA Authenticator, generating keys for provided ballot-ids.
     ///
     /// For good security, keysize should be at least 2048 bits, and ideally 4096 bits.
     ///
@@ -49,7 +176,7 @@ impl Authenticator {
     pub fn new(
         keysize: usize,
         ballot_ids: &[String],
-    ) -> Result<(Self, IndexMap<String, RSAPrivateKey>), Error> {
+    ) -> Result<(Self, IndexMap<String, AuthSecretKey>), Error> {
         // If we are in release mode, make sure we are at least 2048 bits
         #[cfg(not(debug_assertions))]
         assert!(
@@ -60,41 +187,98 @@ impl Authenticator {
         // Create the keys
         let mut rng = rand::rngs::OsRng {};
         let mut public_keys = IndexMap::<String, AuthPublicKey>::new();
-        let mut secret_keys = IndexMap::<String, RSAPrivateKey>::with_capacity(ballot_ids.len());
+        let mut secret_keys = IndexMap::<String, AuthSecretKey>::with_capacity(ballot_ids.len());
 
         for ballot_id in ballot_ids {
             let secret = RSAPrivateKey::new(&mut rng, keysize)?;
             let public: RSAPublicKey = secret.clone().into();
 
-            public_keys.insert(ballot_id.clone(), AuthPublicKey(public));
-            secret_keys.insert(ballot_id.clone(), secret);
+            public_keys.insert(ballot_id.clone(), AuthPublicKey::Rsa(public));
+            secret_keys.insert(ballot_id.clone(), AuthSecretKey::Rsa(secret));
         }
 
         let authenticator = Authenticator {
             id: Uuid::new_v4(),
+            key_type: AuthKeyType::Rsa,
             public_keys: public_keys,
         };
 
         Ok((authenticator, secret_keys))
     }
 
+    /// Create a new Schnorr Authenticator, generating keys for provided ballot-ids.
+    ///
+    /// WARNING: The secret keys generated here must NOT be used for any other purpose.
+    /// Doing so can result in secret key disclosure.
+    pub fn new_schnorr(ballot_ids: &[String]) -> (Self, IndexMap<String, AuthSecretKey>) {
+        Self::new_schnorr_with_rng(&mut rand::rngs::OsRng {}, ballot_ids)
+    }
+
+    /// Create a new Schnorr Authenticator, generating keys for provided ballot-ids from the given RNG.
+    ///
+    /// The authenticator's id is also drawn from the RNG, so a seeded RNG produces the same authenticator every time.
+    pub fn new_schnorr_with_rng<R: Rng + CryptoRng>(
+        rng: &mut R,
+        ballot_ids: &[String],
+    ) -> (Self, IndexMap<String, AuthSecretKey>) {
+        let mut public_keys = IndexMap::<String, AuthPublicKey>::new();
+        let mut secret_keys = IndexMap::<String, AuthSecretKey>::with_capacity(ballot_ids.len());
+
+        for ballot_id in ballot_ids {
+            let secret = Scalar::random(rng);
+            let public = secret * RISTRETTO_BASEPOINT_POINT;
+
+            public_keys.insert(ballot_id.clone(), AuthPublicKey::Schnorr(public));
+            secret_keys.insert(ballot_id.clone(), AuthSecretKey::Schnorr(secret));
+        }
+
+        // A version 4 (random) UUID
+        let mut id: [u8; 16] = rng.gen();
+        id[6] = (id[6] & 0x0f) | 0x40;
+        id[8] = (id[8] & 0x3f) | 0x80;
+
+        let authenticator = Authenticator {
+            id: Uuid::from_bytes(id),
+            key_type: AuthKeyType::Schnorr,
+            public_keys: public_keys,
+        };
+
+        (authenticator, secret_keys)
+    }
+
     /// Sign the blinded (`election-id`, `ballot-id`, `voter-public-key`) auth-package triplet.
     ///
     /// This should only be called after verifying the voter's bonefides (eg government-id, security-code, password etc)
     /// and that they are authorized to vote the requested election and ballot.
+    ///
+    /// Schnorr authenticators must pass the nonce from closing the session whose commitment the voter blinded against
+    /// (see `SchnorrSessions`).
     pub fn authenticate(
         &self,
-        secret: &RSAPrivateKey,
+        secret: &AuthSecretKey,
         blinded_auth_package: &[u8],
-    ) -> Authentication {
-        let mut rng = rand::thread_rng();
-        let blind_signature = blind::sign(&mut rng, &secret, blinded_auth_package)
-            .expect("Error generating blind signature");
+        nonce: Option<SchnorrNonce>,
+    ) -> Result<Authentication, Error> {
+        let blind_signature = match (secret, self.key_type) {
+            (AuthSecretKey::Rsa(secret), AuthKeyType::Rsa) => {
+                let mut rng = rand::thread_rng();
+                blind::sign(&mut rng, &secret, blinded_auth_package)
+                    .map_err(|_| Error::BlindSignatureError)?
+            }
+            (AuthSecretKey::Schnorr(secret), AuthKeyType::Schnorr) => {
+                let nonce = nonce.ok_or(Error::MissingSchnorrCommitment)?;
+                let challenge = scalar_from_bytes(blinded_auth_package)?;
+
+                let signature = nonce.0 + challenge * secret;
+                signature.to_bytes().to_vec()
+            }
+            _ => return Err(Error::AuthKeyTypeMismatch),
+        };
 
-        Authentication {
+        Ok(Authentication {
             authenticator: self.id,
             signature: blind_signature,
-        }
+        })
     }
 
     /// Verify the authenticator signature
@@ -115,11 +299,32 @@ impl Authenticator {
             .get(ballot_id)
             .ok_or(ValidationError::BallotDoesNotExist)?;
 
-        let digest = package.digest(&public_key.0);
-
-        // Verify the signature
-        blind::verify(&public_key.0, &digest, &signature)
-            .map_err(|_| ValidationError::AuthSignatureVerificationFailed)
+        match public_key {
+            AuthPublicKey::Rsa(public_key) => {
+                let digest = package.digest(public_key);
+
+                // Verify the signature
+                blind::verify(public_key, &digest, &signature)
+                    .map_err(|_| ValidationError::AuthSignatureVerificationFailed)
+            }
+            AuthPublicKey::Schnorr(public_key) => {
+                if signature.len() != 64 {
+                    return Err(ValidationError::AuthSignatureVerificationFailed);
+                }
+                let commitment = ristretto_point_from_bytes(&signature[..32])
+                    .map_err(|_| ValidationError::AuthSignatureVerificationFailed)?;
+                let response = scalar_from_bytes(&signature[32..])
+                    .map_err(|_| ValidationError::AuthSignatureVerificationFailed)?;
+
+                // Verify that s⋅G = R + c⋅X
+                let challenge = package.schnorr_challenge(&commitment, public_key);
+                if response * RISTRETTO_BASEPOINT_POINT != commitment + challenge * public_key {
+                    return Err(ValidationError::AuthSignatureVerificationFailed);
+                }
+
+                Ok(())
+            }
+        }
     }
 }
 
@@ -146,15 +351,52 @@ impl AuthPackage {
     }
 
     /// Blind the authentication package, readiying it to be send to the authenticator
-    pub fn blind(&self, signer_pub_key: &RSAPublicKey) -> (Vec<u8>, Vec<u8>) {
-        let mut csprng = rand::rngs::OsRng {};
-
-        let digest = self.digest(signer_pub_key);
-
-        // Get the blinded digest and the secret unblinder
-        let (blinded_digest, unblinder) = blind::blind(&mut csprng, signer_pub_key, &digest);
+    ///
+    /// Returns the blinded auth package and the secret unblinder. Schnorr authenticators must first provide
+    /// a commitment for this signing session (see `Authenticator::commit`).
+    pub fn blind(
+        &self,
+        signer_pub_key: &AuthPublicKey,
+        commitment: Option<&SchnorrCommitment>,
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        self.blind_with_rng(&mut rand::rngs::OsRng {}, signer_pub_key, commitment)
+    }
 
-        (blinded_digest, unblinder)
+    /// Blind the authentication package, drawing the blinding factors from the given RNG
+    pub fn blind_with_rng<R: Rng + CryptoRng>(
+        &self,
+        csprng: &mut R,
+        signer_pub_key: &AuthPublicKey,
+        commitment: Option<&SchnorrCommitment>,
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        match signer_pub_key {
+            AuthPublicKey::Rsa(signer_pub_key) => {
+                let digest = self.digest(signer_pub_key);
+
+                // Get the blinded digest and the secret unblinder
+                let (blinded_digest, unblinder) = blind::blind(csprng, signer_pub_key, &digest);
+
+                Ok((blinded_digest, unblinder))
+            }
+            AuthPublicKey::Schnorr(signer_pub_key) => {
+                let commitment = commitment.ok_or(Error::MissingSchnorrCommitment)?;
+
+                // Blind the commitment: R' = R + α⋅G + β⋅X
+                let alpha = Scalar::random(csprng);
+                let beta = Scalar::random(csprng);
+                let blinded_commitment =
+                    commitment.0 + alpha * RISTRETTO_BASEPOINT_POINT + beta * signer_pub_key;
+
+                // The authenticator signs the challenge c = c' + β, without learning c' or R'
+                let challenge = self.schnorr_challenge(&blinded_commitment, signer_pub_key);
+                let blinded_challenge = challenge + beta;
+
+                let mut unblinder = alpha.to_bytes().to_vec();
+                unblinder.extend_from_slice(blinded_commitment.compress().as_bytes());
+
+                Ok((blinded_challenge.to_bytes().to_vec(), unblinder))
+            }
+        }
     }
 
     fn pack(&self) -> Vec<u8> {
@@ -170,6 +412,20 @@ impl AuthPackage {
 
         digest
     }
+
+    fn schnorr_challenge(
+        &self,
+        commitment: &RistrettoPoint,
+        signer_pub_key: &RistrettoPoint,
+    ) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(b"cryptoballot schnorr blind signature");
+        hasher.update(commitment.compress().as_bytes());
+        hasher.update(signer_pub_key.compress().as_bytes());
+        hasher.update(&self.pack());
+
+        Scalar::from_hash(hasher)
+    }
 }
 
 /// An Authentication is returned by an authenticator, clearing the voter to vote.
@@ -185,13 +441,36 @@ pub struct Authentication {
 
 impl Authentication {
     /// Unblind the signature, reading it for use in a Vote transaction.
-    pub fn unblind(self, signer_pub_key: &RSAPublicKey, unblinder: Vec<u8>) -> Self {
-        // Unblind the signature
-        let unblinded = blind::unblind(signer_pub_key, &self.signature, &unblinder);
-        Authentication {
+    pub fn unblind(
+        self,
+        signer_pub_key: &AuthPublicKey,
+        unblinder: Vec<u8>,
+    ) -> Result<Self, Error> {
+        let unblinded = match signer_pub_key {
+            AuthPublicKey::Rsa(signer_pub_key) => {
+                // Unblind the signature
+                blind::unblind(signer_pub_key, &self.signature, &unblinder)
+            }
+            AuthPublicKey::Schnorr(_) => {
+                if unblinder.len() != 64 {
+                    return Err(Error::InvalidScalar);
+                }
+                let alpha = scalar_from_bytes(&unblinder[..32])?;
+                let blinded_commitment = &unblinder[32..];
+
+                // Unblind the signature: s' = s + α, giving the signature (R', s')
+                let response = scalar_from_bytes(&self.signature)? + alpha;
+
+                let mut signature = blinded_commitment.to_vec();
+                signature.extend_from_slice(response.as_bytes());
+                signature
+            }
+        };
+
+        Ok(Authentication {
             authenticator: self.authenticator,
             signature: unblinded,
-        }
+        })
     }
 }
 
@@ -216,17 +495,76 @@ mod tests {
         let auth_package = AuthPackage::new(election_id, ballot_id.to_string(), voter_public);
 
         // Blind the auth package
-        let public_key = authenticator.public_keys.get(ballot_id).unwrap().as_ref();
-        let (blinded, unblinder) = auth_package.blind(&public_key);
+        let public_key = authenticator.public_keys.get(ballot_id).unwrap();
+        let (blinded, unblinder) = auth_package.blind(&public_key, None).unwrap();
 
         // Get it signed by the authenticator and unblind it
         let auth_secret = auth_secrets.get(ballot_id).unwrap();
-        let auth = authenticator.authenticate(&auth_secret, &blinded);
-        let auth = auth.unblind(public_key, unblinder);
+        let auth = authenticator
+            .authenticate(&auth_secret, &blinded, None)
+            .unwrap();
+        let auth = auth.unblind(public_key, unblinder).unwrap();
 
         // Check that it's still valid even after unblinding
         authenticator
             .verify(election_id, ballot_id, &voter_public, &auth.signature)
             .unwrap();
     }
+
+    #[test]
+    fn test_schnorr_blind_signing() {
+        let mut rng = rand::thread_rng();
+        let election_id = ElectionTransaction::build_id(rng.gen());
+        let ballot_id = "TEST";
+        let (_voter_secret, voter_public) = generate_keypair();
+        let (_other_secret, other_public) = generate_keypair();
+
+        let (authenticator, auth_secrets) =
+            Authenticator::new_schnorr(&vec![ballot_id.to_string()]);
+        assert_eq!(authenticator.key_type, AuthKeyType::Schnorr);
+
+        // The authenticator commits to a nonce for this voter's signing session, and won't open another one for
+        // them until it's finished
+        let mut sessions = SchnorrSessions::new();
+        let voter = "voter-1";
+        let commitment = sessions.open(&authenticator, voter).unwrap();
+        assert!(matches!(
+            sessions.open(&authenticator, voter),
+            Err(Error::SchnorrSessionOpen(_))
+        ));
+        let _other_commitment = sessions.open(&authenticator, "voter-2").unwrap();
+
+        // Blind the auth package against the commitment
+        let auth_package = AuthPackage::new(election_id, ballot_id.to_string(), voter_public);
+        let public_key = authenticator.public_keys.get(ballot_id).unwrap();
+        assert!(auth_package.blind(&public_key, None).is_err());
+        let (blinded, unblinder) = auth_package.blind(&public_key, Some(&commitment)).unwrap();
+
+        // Get it signed by the authenticator and unblind it
+        let auth_secret = auth_secrets.get(ballot_id).unwrap();
+        let nonce = sessions.close(&authenticator, voter).unwrap();
+        let auth = authenticator
+            .authenticate(&auth_secret, &blinded, Some(nonce))
+            .unwrap();
+        let auth = auth.unblind(public_key, unblinder).unwrap();
+
+        // The nonce is gone once the session is closed, and the voter can start a new session
+        assert!(sessions.close(&authenticator, voter).is_err());
+        sessions.open(&authenticator, voter).unwrap();
+
+        // Check that it's valid after unblinding, and only for the voter's key
+        authenticator
+            .verify(election_id, ballot_id, &voter_public, &auth.signature)
+            .unwrap();
+        assert!(authenticator
+            .verify(election_id, ballot_id, &other_public, &auth.signature)
+            .is_err());
+
+        // Keys round-trip through serialization
+        let serialized = serde_json::to_string(&authenticator).unwrap();
+        let deserialized: Authenticator = serde_json::from_str(&serialized).unwrap();
+        deserialized
+            .verify(election_id, ballot_id, &voter_public, &auth.signature)
+            .unwrap();
+    }
 }
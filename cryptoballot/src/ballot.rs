@@ -1,3 +1,5 @@
+use crate::Quota;
+use crate::SpoiledBallotError;
 use indexmap::IndexMap;
 use prost::Message;
 
@@ -14,6 +16,9 @@ pub struct Ballot {
     pub properties: IndexMap<String, serde_json::Value>,
 }
 
+/// The highest `max_score` a contest may have, bounding the size of its well-formedness proofs
+pub const MAX_SCORE: u32 = 100;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Contest {
     pub id: String,
@@ -24,6 +29,23 @@ pub struct Contest {
     pub write_in: bool,
     pub candidates: Vec<Candidate>,
 
+    /// Tally this contest homomorphically, without decrypting individual votes.
+    ///
+    /// Each vote encrypts a counter for every candidate using exponential-ElGamal. Trustees decrypt only the sum of
+    /// all counters, so individual votes are never decrypted and the contest is not mixed.
+    /// Only Plurality, Approval and Score contests without write-ins can be tallied homomorphically.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "crate::util::is_default")]
+    pub homomorphic: bool,
+
+    /// The highest score that can be given to a candidate in a Score contest. Required for homomorphic Score contests.
+    ///
+    /// Each selection's well-formedness proof has a branch for every candidate and score (a counter's range proof has a
+    /// branch for every score), so proofs grow with `max_score`, which may be at most `MAX_SCORE`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_score: Option<u32>,
+
     /// Application specific properties.
     ///
     /// Hashmaps are not allowed because their unstable ordering leads to non-determinism.
@@ -32,6 +54,167 @@ pub struct Contest {
     pub properties: IndexMap<String, serde_json::Value>,
 }
 
+impl Contest {
+    /// Check that the selections made on a single ballot follow the rules of this contest.
+    ///
+    /// The following is checked:
+    ///  - There is at least one selection
+    ///  - Candidates exist in this contest, unless they are a write-in and write-ins are allowed
+    ///  - No candidate is selected more than once
+    ///  - Plurality contests have no more selections than `num_winners`
+    ///  - Score contests give no candidate a score above `max_score`, if it is set
+    ///  - Ranked contests have ranks starting at zero with no gaps. InstantRunoff and SingleTransferable do not allow ties.
+    pub fn check_selections(&self, selections: &[Selection]) -> Result<(), SpoiledBallotError> {
+        if selections.is_empty() {
+            return Err(SpoiledBallotError::NoSelections);
+        }
+
+        for (i, selection) in selections.iter().enumerate() {
+            if selection.write_in {
+                if !self.write_in {
+                    return Err(SpoiledBallotError::WriteInNotAllowed);
+                }
+            } else if !self
+                .candidates
+                .iter()
+                .any(|candidate| candidate.id == selection.selection)
+            {
+                return Err(SpoiledBallotError::CandidateNotFound);
+            }
+
+            if selections[..i]
+                .iter()
+                .any(|previous| previous.selection == selection.selection)
+            {
+                return Err(SpoiledBallotError::DuplicateCandidate);
+            }
+        }
+
+        match self.contest_type {
+            ContestType::Plurality => {
+                if selections.len() > self.num_winners as usize {
+                    return Err(SpoiledBallotError::TooManySelections);
+                }
+            }
+            ContestType::Score => {
+                if let Some(max_score) = self.max_score {
+                    if selections
+                        .iter()
+                        .any(|selection| selection.score > max_score)
+                    {
+                        return Err(SpoiledBallotError::ScoreOutOfRange);
+                    }
+                }
+            }
+            ContestType::Approval => {}
+            ContestType::Condorcet
+            | ContestType::Borda
+            | ContestType::BordaClassic
+            | ContestType::BordaDowdall
+            | ContestType::BordaModifiedClassic
+            | ContestType::SchulzeWinning
+            | ContestType::SchulzeRatio
+            | ContestType::SchulzeMargin => check_ranks(selections, true)?,
+            ContestType::InstantRunoff { .. } | ContestType::SingleTransferable { .. } => {
+                check_ranks(selections, false)?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// All valid selections for this contest, or None if they can't be enumerated.
+    ///
+    /// For Plurality and Approval contests the score must be zero, for Score contests it must be at most `max_score`,
+    /// and for ranked contests it must be a rank less than the number of candidates. Contests that allow write-ins, and
+    /// Score contests without a `max_score`, have no bound on their valid selections, so votes for them carry no
+    /// well-formedness proofs (see `encrypt_vote_with_proofs`) and are only checked when decrypted.
+    ///
+    /// There are `(max_score + 1) * candidates` valid selections in a Score contest, so its proofs are much larger than
+    /// those of other contests.
+    ///
+    /// Homomorphic contests have no valid selections, since their votes are counters rather than selections.
+    pub fn valid_selections(&self) -> Option<Vec<Selection>> {
+        if self.write_in || self.homomorphic {
+            return None;
+        }
+
+        let num_scores = match self.contest_type {
+            ContestType::Score => self.max_score?.checked_add(1)?,
+            ContestType::Plurality | ContestType::Approval => 1,
+            ContestType::Condorcet
+            | ContestType::Borda
+            | ContestType::BordaClassic
+            | ContestType::BordaDowdall
+            | ContestType::BordaModifiedClassic
+            | ContestType::SchulzeWinning
+            | ContestType::SchulzeRatio
+            | ContestType::SchulzeMargin
+            | ContestType::InstantRunoff { .. }
+            | ContestType::SingleTransferable { .. } => self.candidates.len() as u32,
+        };
+
+        let mut selections = Vec::with_capacity(self.candidates.len() * num_scores as usize);
+        for candidate in &self.candidates {
+            for score in 0..num_scores {
+                selections.push(Selection {
+                    write_in: false,
+                    score,
+                    selection: candidate.id.clone(),
+                });
+            }
+        }
+
+        Some(selections)
+    }
+
+    /// Returns true if `TallyResult::tally` can elect `num_winners` candidates in this contest.
+    ///
+    /// Every contest type can be tallied, but a tally must elect at least one candidate, and instant-runoff always elects
+    /// exactly one.
+    pub fn tally_supported(&self) -> bool {
+        match self.contest_type {
+            _ if self.num_winners == 0 => false,
+            ContestType::InstantRunoff { .. } => self.num_winners == 1,
+            _ => true,
+        }
+    }
+
+    /// The highest value that a single vote's counter for a candidate can take in a homomorphic contest.
+    ///
+    /// Returns None if the contest can't be tallied homomorphically.
+    pub fn max_counter(&self) -> Option<u32> {
+        if self.write_in || !self.contest_type.homomorphic_supported() {
+            return None;
+        }
+
+        match self.contest_type {
+            ContestType::Score => self.max_score,
+            _ => Some(1),
+        }
+    }
+}
+
+// Ranks must start at zero and have no gaps
+fn check_ranks(selections: &[Selection], allow_ties: bool) -> Result<(), SpoiledBallotError> {
+    let mut ranks: Vec<u32> = selections.iter().map(|s| s.score).collect();
+    ranks.sort_unstable();
+
+    let mut previous: Option<u32> = None;
+    for rank in ranks {
+        let valid = match previous {
+            None => rank == 0,
+            Some(previous) => rank == previous + 1 || (allow_ties && rank == previous),
+        };
+        if !valid {
+            return Err(SpoiledBallotError::InvalidRanking);
+        }
+        previous = Some(rank);
+    }
+
+    Ok(())
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Candidate {
     pub id: String,
@@ -152,18 +335,60 @@ pub enum ContestType {
     SchulzeWinning,
 
     /// The Schulze method is an voting system that selects a single winner using votes that express preferences.
-    /// In SchulzeRatio, the strength of a link is measured by the difference between its support and opposition.
+    /// In SchulzeRatio, the strength of a link is measured by the ratio of its support and opposition.
     ///
     /// For SchulzeRatio tally, `Selection.score` is interpreted as the candidate rank, where the best ranked candidate has a rank of zero.
     /// Candidates that have the same rank are considered to be of equal preference.
     SchulzeRatio,
 
     /// The Schulze method is an voting system that selects a single winner using votes that express preferences.
-    /// In SchulzeMargin, the strength of a link is measured by the ratio of its support and opposition.
+    /// In SchulzeMargin, the strength of a link is measured by the difference between its support and opposition.
     ///
     /// For SchulzeMargin tally, `Selection.score` is interpreted as the candidate rank, where the best ranked candidate has a rank of zero.
     /// Candidates that have the same rank are considered to be of equal preference.
     SchulzeMargin,
+
+    /// Instant-runoff voting (IRV) is a single-winner ranked voting system. Ballots are first counted by their first preference.
+    /// If no candidate reaches the quota, the candidate with the fewest votes is eliminated and their ballots are transferred
+    /// to the next continuing preference. This repeats until a candidate reaches the quota or only one candidate remains.
+    ///
+    /// Instant-runoff always elects a single candidate; use `SingleTransferable` for contests with more than one winner.
+    ///
+    /// For InstantRunoff tally, `Selection.score` is interpreted as the candidate rank, where the best ranked candidate has a rank of zero.
+    InstantRunoff {
+        #[serde(default)]
+        quota: Quota,
+    },
+
+    /// The single transferable vote (STV) is a multi-winner ranked voting system that achieves proportional representation.
+    /// Candidates that reach the quota are elected and their surplus is transferred at a fractional value to the next continuing
+    /// preference on their ballots. When no candidate reaches the quota, the candidate with the fewest votes is eliminated
+    /// and their ballots are transferred at full value.
+    ///
+    /// For SingleTransferable tally, `Selection.score` is interpreted as the candidate rank, where the best ranked candidate has a rank of zero.
+    SingleTransferable {
+        #[serde(default)]
+        quota: Quota,
+    },
+}
+
+impl ContestType {
+    /// Returns true if contests of this type can be tallied homomorphically.
+    pub fn homomorphic_supported(&self) -> bool {
+        match self {
+            ContestType::Plurality | ContestType::Approval | ContestType::Score => true,
+            ContestType::Condorcet
+            | ContestType::Borda
+            | ContestType::BordaClassic
+            | ContestType::BordaDowdall
+            | ContestType::BordaModifiedClassic
+            | ContestType::SchulzeWinning
+            | ContestType::SchulzeRatio
+            | ContestType::SchulzeMargin
+            | ContestType::InstantRunoff { .. }
+            | ContestType::SingleTransferable { .. } => false,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Message, PartialEq, Eq)]
@@ -174,9 +399,9 @@ pub struct Selection {
     pub write_in: bool,
 
     /// Score has different meanings depending on the tally type:
-    /// STV, Condorcet, Borda and Schulze: `score` means candidate rank, where a zero is the best rank that can be assigned to a candidate.
+    /// SingleTransferable, InstantRunoff, Condorcet, Borda and Schulze: `score` means candidate rank, where a zero is the best rank that can be assigned to a candidate.
     /// Score: `score` is the points assinged to this candidate. Zero is the worst score that can be asssigned to a candidate.
-    /// Plurality, Approval, and InstantRunoff: `score` is meaningless and has no effect.
+    /// Plurality and Approval: `score` is meaningless and has no effect.
     #[prost(uint32)]
     #[serde(default)]
     pub score: u32,
@@ -0,0 +1,161 @@
+use crate::*;
+use ed25519_dalek::PublicKey;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// The plaintext selections for a single contest
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlaintextVote {
+    pub contest_index: u32,
+    pub selections: Vec<Selection>,
+}
+
+/// An encrypted ballot awaiting the voter's decision to either cast it or challenge it.
+///
+/// Benaloh's cast-or-challenge lets a voter check that their device encrypted their vote honestly:
+///   1. The device encrypts the vote and shows the voter a commitment to the encrypted ballot.
+///   2. The voter either casts the ballot, or challenges it.
+///   3. A challenged ballot reveals its plaintext and encryption randomness, so anyone can re-encrypt it
+///      using `verify_benaloh_challenge` and confirm that it matches the commitment.
+///
+/// Because the device must commit to the ballot before knowing whether it will be challenged, a dishonest
+/// device is caught with high probability over repeated challenges. A challenged ballot must never be cast,
+/// since its contents are no longer secret. Instead the device encrypts a fresh ballot under a fresh anonymous key,
+/// and the voter decides again.
+pub struct BenalohBallot {
+    plaintext: Vec<PlaintextVote>,
+    encrypted_votes: Vec<EncryptedVote>,
+    anonymous_key: PublicKey,
+    seed: [u8; 32],
+}
+
+impl BenalohBallot {
+    /// Encrypt a vote with the public key provided by the encryption_key transaction (EncryptionKeyTransaction.encryption_key)
+    ///
+    /// All encryption randomness is derived from a single random seed, which is revealed if the ballot is challenged.
+    /// Well-formedness proofs are bound to the voter's anonymous key, which must be the key used in the eventual VoteTransaction.
+    pub fn encrypt<R: CryptoRng + RngCore>(
+        encryption_key: &cryptid::elgamal::PublicKey,
+        election: &ElectionTransaction,
+        anonymous_key: &PublicKey,
+        plaintext: Vec<PlaintextVote>,
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        let mut seed = [0; 32];
+        rng.fill_bytes(&mut seed);
+
+        let encrypted_votes =
+            encrypt_with_seed(encryption_key, election, anonymous_key, &plaintext, seed)?;
+
+        Ok(BenalohBallot {
+            plaintext,
+            encrypted_votes,
+            anonymous_key: *anonymous_key,
+            seed,
+        })
+    }
+
+    /// The commitment to the encrypted ballot, to be shown to the voter before they decide to cast or challenge it
+    pub fn commitment(&self) -> [u8; 32] {
+        benaloh_commitment(&self.encrypted_votes)
+    }
+
+    /// Cast the ballot, discarding the encryption randomness and returning the encrypted votes for the VoteTransaction
+    pub fn cast(self) -> Vec<EncryptedVote> {
+        self.encrypted_votes
+    }
+
+    /// Challenge the ballot, revealing the plaintext and encryption randomness
+    ///
+    /// The ballot is consumed, and must not be cast.
+    pub fn challenge(self) -> BenalohChallenge {
+        BenalohChallenge {
+            commitment: benaloh_commitment(&self.encrypted_votes),
+            encrypted_votes: self.encrypted_votes,
+            plaintext: self.plaintext,
+            anonymous_key: self.anonymous_key,
+            seed: self.seed,
+        }
+    }
+}
+
+/// A challenged ballot, revealing its plaintext and encryption randomness.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BenalohChallenge {
+    #[serde(with = "hex_serde")]
+    pub commitment: [u8; 32],
+    pub encrypted_votes: Vec<EncryptedVote>,
+    pub plaintext: Vec<PlaintextVote>,
+    #[serde(with = "EdPublicKeyHex")]
+    pub anonymous_key: PublicKey,
+    #[serde(with = "hex_serde")]
+    pub seed: [u8; 32],
+}
+
+/// Verify a challenged ballot by re-encrypting the revealed plaintext with the revealed randomness.
+///
+/// This confirms that the encrypted ballot matches the commitment shown to the voter, and that it is an
+/// honest encryption of the revealed plaintext. The voter must separately confirm that the plaintext is what they intended to vote.
+pub fn verify_benaloh_challenge(
+    encryption_key: &cryptid::elgamal::PublicKey,
+    election: &ElectionTransaction,
+    challenge: &BenalohChallenge,
+) -> Result<(), ValidationError> {
+    if benaloh_commitment(&challenge.encrypted_votes) != challenge.commitment {
+        return Err(ValidationError::BenalohCommitmentMismatch);
+    }
+
+    let reencrypted = encrypt_with_seed(
+        encryption_key,
+        election,
+        &challenge.anonymous_key,
+        &challenge.plaintext,
+        challenge.seed,
+    )
+    .map_err(|_| ValidationError::BenalohChallengeFailed)?;
+
+    if benaloh_commitment(&reencrypted) != challenge.commitment {
+        return Err(ValidationError::BenalohChallengeFailed);
+    }
+
+    Ok(())
+}
+
+fn encrypt_with_seed(
+    encryption_key: &cryptid::elgamal::PublicKey,
+    election: &ElectionTransaction,
+    anonymous_key: &PublicKey,
+    plaintext: &[PlaintextVote],
+    seed: [u8; 32],
+) -> Result<Vec<EncryptedVote>, Error> {
+    let mut rng = ChaCha20Rng::from_seed(seed);
+
+    let mut encrypted_votes = Vec::with_capacity(plaintext.len());
+    for vote in plaintext {
+        let contest = election
+            .get_contest(vote.contest_index)
+            .ok_or(Error::CannotFindContet(vote.contest_index))?;
+
+        encrypted_votes.push(encrypt_vote_with_proofs(
+            encryption_key,
+            election.id,
+            anonymous_key,
+            contest,
+            vote.selections.clone(),
+            &mut rng,
+        )?);
+    }
+
+    Ok(encrypted_votes)
+}
+
+pub(crate) fn benaloh_commitment(encrypted_votes: &[EncryptedVote]) -> [u8; 32] {
+    let packed =
+        serde_cbor::to_vec(encrypted_votes).expect("cryptoballot: error packing encrypted votes");
+
+    let mut commitment = [0; 32];
+    commitment.copy_from_slice(&Sha256::digest(&packed));
+    commitment
+}
@@ -0,0 +1,159 @@
+use crate::*;
+use rayon::prelude::*;
+use std::collections::HashSet;
+
+// Number of signatures verified together in a single ed25519 batch
+const SIGNATURE_BATCH_SIZE: usize = 1024;
+
+// Maximum number of independent transactions held in memory and validated together
+const MAX_INDEPENDENT_RUN: usize = 16384;
+
+/// A transaction that failed bulk validation
+#[derive(Debug)]
+pub struct BulkValidationFailure {
+    pub id: Identifier,
+    pub error: ValidationError,
+}
+
+/// Verify the signatures of many transactions at once.
+///
+/// Signatures are verified in parallel using ed25519 batch verification. If a batch fails, its signatures are
+/// verified one at a time so that the result for each transaction is reported. The results are in the same order
+/// as the transactions.
+pub fn verify_signatures(txs: &[SignedTransaction]) -> Vec<Result<(), ValidationError>> {
+    txs.par_chunks(SIGNATURE_BATCH_SIZE)
+        .flat_map_iter(|chunk| {
+            let parts: Vec<_> = chunk.iter().filter_map(|tx| tx.signature_parts()).collect();
+
+            let mut public_keys = Vec::with_capacity(parts.len());
+            let mut messages = Vec::with_capacity(parts.len());
+            let mut signatures = Vec::with_capacity(parts.len());
+            for (public, message, sig) in parts.iter() {
+                public_keys.push(*public);
+                messages.push(message.as_slice());
+                signatures.push(*sig);
+            }
+
+            if ed25519_dalek::verify_batch(&messages, &signatures, &public_keys).is_ok() {
+                chunk.iter().map(|_| Ok(())).collect::<Vec<_>>()
+            } else {
+                chunk.iter().map(|tx| tx.verify_signature()).collect()
+            }
+        })
+        .collect()
+}
+
+/// Validate transactions that don't depend on each other, in parallel.
+///
+/// Every transaction is validated against the same store, so none of them may depend on another transaction in
+/// `txs` (for example, the votes in an election, or its decryptions). The results are in the same order as the
+/// transactions.
+pub fn validate_independent<S: Store + Sync>(
+    store: &S,
+    txs: &[SignedTransaction],
+) -> Vec<Result<(), ValidationError>> {
+    let signatures = verify_signatures(txs);
+
+    txs.par_iter()
+        .zip(signatures)
+        .map(|(tx, signature)| signature.and_then(|_| tx.validate_tx(store)))
+        .collect()
+}
+
+/// Validate an ordered list of transactions, such as every transaction in an election.
+///
+/// Transactions must be in the order they were posted, which is not always id order: see `replay_election` for
+/// ordering the transactions of an election read from a store or archive.
+///
+/// This produces the same results as validating each transaction in turn with `SignedTransaction::validate` and
+/// inserting it into the store, but runs of independent votes, partial-decryptions and decryptions are validated
+/// in parallel, with their signatures verified in batches. Valid transactions are inserted into the store with
+/// `insert`. Invalid transactions are not inserted, and are reported in order.
+pub fn validate_bulk<S, I, F>(store: &mut S, txs: I, mut insert: F) -> Vec<BulkValidationFailure>
+where
+    S: Store + Sync,
+    I: IntoIterator<Item = SignedTransaction>,
+    F: FnMut(&mut S, SignedTransaction),
+{
+    let mut failures = Vec::new();
+    let mut run: Vec<SignedTransaction> = Vec::new();
+    let mut run_ids = HashSet::new();
+    let mut run_prefixes = HashSet::new();
+
+    for tx in txs {
+        let prefix = collision_prefix(&tx);
+        let joins_run = match run.first() {
+            Some(first) => {
+                is_independent(first)
+                    && is_independent(&tx)
+                    && first.transaction_type() == tx.transaction_type()
+                    && !run_ids.contains(&tx.id())
+                    && prefix.map_or(true, |prefix| !run_prefixes.contains(&prefix))
+                    && run.len() < MAX_INDEPENDENT_RUN
+            }
+            None => true,
+        };
+
+        if !joins_run {
+            validate_run(store, &mut run, &mut insert, &mut failures);
+            run_ids.clear();
+            run_prefixes.clear();
+        }
+
+        run_ids.insert(tx.id());
+        if let Some(prefix) = prefix {
+            run_prefixes.insert(prefix);
+        }
+        run.push(tx);
+    }
+    validate_run(store, &mut run, &mut insert, &mut failures);
+
+    failures
+}
+
+// Validate a run of independent transactions, inserting the valid ones into the store
+fn validate_run<S, F>(
+    store: &mut S,
+    run: &mut Vec<SignedTransaction>,
+    insert: &mut F,
+    failures: &mut Vec<BulkValidationFailure>,
+) where
+    S: Store + Sync,
+    F: FnMut(&mut S, SignedTransaction),
+{
+    let results = match run.len() {
+        0 => return,
+        1 => vec![run[0].validate(&*store)],
+        _ => validate_independent(&*store, run),
+    };
+
+    for (tx, result) in run.drain(..).zip(results) {
+        match result {
+            Ok(()) => insert(store, tx),
+            Err(error) => failures.push(BulkValidationFailure { id: tx.id(), error }),
+        }
+    }
+}
+
+// Votes whose anonymous keys share their first 10 bytes collide (see `VoteTransaction::validate_tx`), so only the
+// first of them may be accepted. Such votes depend on each other and must not be validated in the same run.
+fn collision_prefix(tx: &SignedTransaction) -> Option<([u8; 15], [u8; 10])> {
+    match tx {
+        SignedTransaction::Vote(vote) => {
+            let mut prefix = [0; 10];
+            prefix.copy_from_slice(&vote.anonymous_key.as_bytes()[0..10]);
+            Some((vote.election.election_id, prefix))
+        }
+        _ => None,
+    }
+}
+
+// Whether a transaction can be validated alongside others of the same type.
+// A replacement vote depends on the vote it replaces, so it is always validated on its own.
+fn is_independent(tx: &SignedTransaction) -> bool {
+    match tx {
+        SignedTransaction::Vote(vote) => vote.revision == 0,
+        SignedTransaction::PartialDecryption(_) | SignedTransaction::Decryption(_) => true,
+        _ => false,
+    }
+}
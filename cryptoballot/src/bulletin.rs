@@ -0,0 +1,571 @@
+use crate::*;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+// Domain separation between leaves and interior nodes, as in RFC 6962
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// A SHA-256 hash in a bulletin board's merkle tree
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MerkleHash(#[serde(with = "hex_serde")] pub [u8; 32]);
+
+/// An append-only bulletin board over a transaction store.
+///
+/// Every transaction posted to the board is committed, in the order it was accepted, to a merkle tree for its
+/// election (built as in RFC 6962). The root of the tree commits to the election's entire history, so once a board
+/// operator has published a root, any transaction they have accepted can be proven to be part of that history with
+/// an `InclusionProof`, and the operator cannot drop, reorder or rewrite transactions without changing the root.
+/// A `ConsistencyProof` shows that a later root only appends to an earlier one.
+///
+/// The board is itself a `Store`, so transactions are validated against everything already posted. Its leaves are
+/// persisted in the underlying `BoardStore`, so a board can be re-opened over a store that was posted to before.
+pub struct BulletinBoard<S: BoardStore> {
+    store: S,
+    elections: HashMap<[u8; 15], ElectionLog>,
+}
+
+// The leaves of a single election's merkle tree, in the order they were posted
+#[derive(Default)]
+struct ElectionLog {
+    leaves: Vec<MerkleHash>,
+    index: HashMap<Identifier, usize>,
+}
+
+/// A store that persists a bulletin board's merkle leaves alongside its transactions
+///
+/// Transactions written to the store directly, rather than posted to a board, are not part of any board's history.
+pub trait BoardStore: Store {
+    /// Every leaf posted to the board, with the id of its transaction, in the order they were posted
+    fn board_leaves(&self) -> Result<Vec<(Identifier, MerkleHash)>, StoreError>;
+
+    /// Store a posted transaction and append its leaf to the board
+    ///
+    /// The transaction and its leaf must be written atomically, so the board never commits to a missing transaction.
+    fn append_leaf(&mut self, tx: SignedTransaction, leaf: MerkleHash) -> Result<(), StoreError>;
+}
+
+/// The root of an election's merkle tree after `tree_size` transactions were posted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BoardRoot {
+    pub election_id: Identifier,
+    pub tree_size: u64,
+    pub root: MerkleHash,
+}
+
+/// A proof that a transaction is included in an election's merkle tree
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InclusionProof {
+    pub tx_id: Identifier,
+    pub leaf_index: u64,
+    pub tree_size: u64,
+    pub path: Vec<MerkleHash>,
+}
+
+/// A proof that an election's merkle tree at one size is a prefix of the tree at a later size
+///
+/// Anyone holding two published roots can check that the board only appended transactions between them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsistencyProof {
+    pub election_id: Identifier,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub path: Vec<MerkleHash>,
+}
+
+impl<S: BoardStore> BulletinBoard<S> {
+    /// Open a bulletin board over a store, rebuilding the merkle tree of every election already posted to it
+    pub fn new(store: S) -> Result<Self, StoreError> {
+        let mut elections: HashMap<[u8; 15], ElectionLog> = HashMap::new();
+        for (id, leaf) in store.board_leaves()? {
+            let log = elections.entry(id.election_id).or_default();
+            log.index.insert(id, log.leaves.len());
+            log.leaves.push(leaf);
+        }
+
+        Ok(BulletinBoard { store, elections })
+    }
+
+    /// Get the underlying store
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Validate a transaction, store it, and commit it to its election's merkle tree.
+    ///
+    /// Returns the index of the transaction in the tree. Transactions can never be replaced once posted.
+    pub fn post(&mut self, tx: SignedTransaction) -> Result<u64, ValidationError> {
+        let id = tx.id();
+        let log = self.elections.entry(id.election_id).or_default();
+        if log.index.contains_key(&id) {
+            return Err(ValidationError::AlreadyPosted(id));
+        }
+
+        tx.validate(&self.store)?;
+
+        let leaf = leaf_hash(&tx);
+        self.store.append_leaf(tx, leaf)?;
+
+        let leaf_index = log.leaves.len();
+        log.leaves.push(leaf);
+        log.index.insert(id, leaf_index);
+
+        Ok(leaf_index as u64)
+    }
+
+    /// The current root of an election's merkle tree
+    pub fn root(&self, election_id: Identifier) -> BoardRoot {
+        let leaves = self
+            .elections
+            .get(&election_id.election_id)
+            .map_or(&[][..], |log| &log.leaves[..]);
+
+        BoardRoot {
+            election_id: Identifier::new(election_id, TransactionType::Election, None),
+            tree_size: leaves.len() as u64,
+            root: merkle_root(leaves),
+        }
+    }
+
+    /// Prove that a transaction is included in the current root of its election's merkle tree
+    ///
+    /// Returns None if the transaction has not been posted to the board.
+    pub fn prove(&self, tx_id: Identifier) -> Option<InclusionProof> {
+        let log = self.elections.get(&tx_id.election_id)?;
+        let leaf_index = *log.index.get(&tx_id)?;
+
+        Some(InclusionProof {
+            tx_id,
+            leaf_index: leaf_index as u64,
+            tree_size: log.leaves.len() as u64,
+            path: merkle_path(leaf_index, &log.leaves),
+        })
+    }
+
+    /// Prove that the root of an election's merkle tree at `old_size` is consistent with its root at `new_size`
+    ///
+    /// Returns None if `old_size` is larger than `new_size`, or more transactions than have been posted are requested.
+    pub fn prove_consistency(
+        &self,
+        election_id: Identifier,
+        old_size: u64,
+        new_size: u64,
+    ) -> Option<ConsistencyProof> {
+        let leaves = self
+            .elections
+            .get(&election_id.election_id)
+            .map_or(&[][..], |log| &log.leaves[..]);
+        if old_size > new_size || new_size > leaves.len() as u64 {
+            return None;
+        }
+
+        let path = match old_size {
+            0 => vec![],
+            _ => merkle_subproof(old_size as usize, &leaves[..new_size as usize], true),
+        };
+
+        Some(ConsistencyProof {
+            election_id: Identifier::new(election_id, TransactionType::Election, None),
+            old_size,
+            new_size,
+            path,
+        })
+    }
+}
+
+impl<S: BoardStore> Store for BulletinBoard<S> {
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        self.store.get_transaction(id)
+    }
+
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_> {
+        self.store.range(start, end_inclusive)
+    }
+
+    fn current_time(&self) -> Result<u64, StoreError> {
+        self.store.current_time()
+    }
+}
+
+impl BoardStore for MemStore {
+    fn board_leaves(&self) -> Result<Vec<(Identifier, MerkleHash)>, StoreError> {
+        Ok(self.board.clone())
+    }
+
+    fn append_leaf(&mut self, tx: SignedTransaction, leaf: MerkleHash) -> Result<(), StoreError> {
+        self.board.push((tx.id(), leaf));
+        self.set(tx);
+        Ok(())
+    }
+}
+
+impl InclusionProof {
+    /// Verify that a transaction is included in a published root
+    ///
+    /// The verification follows RFC 9162 (section 2.1.3.2).
+    pub fn verify(&self, tx: &SignedTransaction, root: &BoardRoot) -> Result<(), ValidationError> {
+        let tx_id = tx.id();
+        if self.tx_id != tx_id
+            || root.election_id.election_id != tx_id.election_id
+            || self.tree_size != root.tree_size
+            || self.leaf_index >= self.tree_size
+        {
+            return Err(ValidationError::InclusionProofFailed(tx_id));
+        }
+
+        let mut f_n = self.leaf_index;
+        let mut s_n = self.tree_size - 1;
+        let mut hash = leaf_hash(tx);
+        for sibling in &self.path {
+            if s_n == 0 {
+                return Err(ValidationError::InclusionProofFailed(tx_id));
+            }
+            if f_n & 1 == 1 || f_n == s_n {
+                hash = node_hash(sibling, &hash);
+                while f_n & 1 == 0 && f_n != 0 {
+                    f_n >>= 1;
+                    s_n >>= 1;
+                }
+            } else {
+                hash = node_hash(&hash, sibling);
+            }
+            f_n >>= 1;
+            s_n >>= 1;
+        }
+
+        if s_n != 0 || hash != root.root {
+            return Err(ValidationError::InclusionProofFailed(tx_id));
+        }
+
+        Ok(())
+    }
+}
+
+impl ConsistencyProof {
+    /// Verify that a published root is consistent with an earlier one
+    ///
+    /// The verification follows RFC 9162 (section 2.1.4.2).
+    pub fn verify(
+        &self,
+        old_root: &BoardRoot,
+        new_root: &BoardRoot,
+    ) -> Result<(), ValidationError> {
+        if old_root.election_id.election_id != self.election_id.election_id
+            || new_root.election_id.election_id != self.election_id.election_id
+            || old_root.tree_size != self.old_size
+            || new_root.tree_size != self.new_size
+            || !consistency_path_is_valid(
+                self.old_size,
+                self.new_size,
+                &old_root.root,
+                &new_root.root,
+                &self.path,
+            )
+        {
+            return Err(ValidationError::ConsistencyProofFailed(self.election_id));
+        }
+
+        Ok(())
+    }
+}
+
+// The leaf hash commits to both the identifier and the full signed transaction
+fn leaf_hash(tx: &SignedTransaction) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(&tx.id().to_array());
+    hasher.update(&tx.as_bytes());
+
+    MerkleHash(hasher.finalize().into())
+}
+
+fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut hasher = Sha256::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(&left.0);
+    hasher.update(&right.0);
+
+    MerkleHash(hasher.finalize().into())
+}
+
+// The largest power of two smaller than n (n must be at least 2)
+fn split_point(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+// MTH from RFC 6962 (section 2.1)
+fn merkle_root(leaves: &[MerkleHash]) -> MerkleHash {
+    match leaves.len() {
+        0 => MerkleHash(Sha256::digest(&[]).into()),
+        1 => leaves[0],
+        n => {
+            let k = split_point(n);
+            node_hash(&merkle_root(&leaves[..k]), &merkle_root(&leaves[k..]))
+        }
+    }
+}
+
+// PATH from RFC 6962 (section 2.1.1)
+fn merkle_path(m: usize, leaves: &[MerkleHash]) -> Vec<MerkleHash> {
+    let n = leaves.len();
+    if n <= 1 {
+        return vec![];
+    }
+
+    let k = split_point(n);
+    if m < k {
+        let mut path = merkle_path(m, &leaves[..k]);
+        path.push(merkle_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = merkle_path(m - k, &leaves[k..]);
+        path.push(merkle_root(&leaves[..k]));
+        path
+    }
+}
+
+// Verify a consistency path as in RFC 9162 (section 2.1.4.2)
+fn consistency_path_is_valid(
+    old_size: u64,
+    new_size: u64,
+    old_root: &MerkleHash,
+    new_root: &MerkleHash,
+    path: &[MerkleHash],
+) -> bool {
+    // Every tree extends the empty tree, and a tree is only consistent with itself
+    if old_size == 0 {
+        return path.is_empty();
+    }
+    if old_size >= new_size {
+        return old_size == new_size && path.is_empty() && old_root == new_root;
+    }
+
+    // When the old tree is a complete subtree its root is left out of the path
+    let mut path = path.iter();
+    let first = if old_size.is_power_of_two() {
+        *old_root
+    } else {
+        match path.next() {
+            Some(first) => *first,
+            None => return false,
+        }
+    };
+
+    let mut f_n = old_size - 1;
+    let mut s_n = new_size - 1;
+    while f_n & 1 == 1 {
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+
+    let mut f_r = first;
+    let mut s_r = first;
+    for c in path {
+        if s_n == 0 {
+            return false;
+        }
+        if f_n & 1 == 1 || f_n == s_n {
+            f_r = node_hash(c, &f_r);
+            s_r = node_hash(c, &s_r);
+            while f_n & 1 == 0 && f_n != 0 {
+                f_n >>= 1;
+                s_n >>= 1;
+            }
+        } else {
+            s_r = node_hash(&s_r, c);
+        }
+        f_n >>= 1;
+        s_n >>= 1;
+    }
+
+    s_n == 0 && f_r == *old_root && s_r == *new_root
+}
+
+// SUBPROOF from RFC 6962 (section 2.1.2)
+fn merkle_subproof(m: usize, leaves: &[MerkleHash], complete: bool) -> Vec<MerkleHash> {
+    let n = leaves.len();
+    if m == n {
+        return match complete {
+            true => vec![],
+            false => vec![merkle_root(leaves)],
+        };
+    }
+
+    let k = split_point(n);
+    if m <= k {
+        let mut path = merkle_subproof(m, &leaves[..k], complete);
+        path.push(merkle_root(&leaves[k..]));
+        path
+    } else {
+        let mut path = merkle_subproof(m - k, &leaves[k..], false);
+        path.push(merkle_root(&leaves[..k]));
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+    use rand::RngCore;
+
+    #[test]
+    fn inclusion_proofs() {
+        let (authority_secret, authority_public) = generate_keypair();
+        let election = ElectionTransaction::new(authority_public);
+        let election = Signed::sign(&authority_secret, election).unwrap();
+        let election_id = election.id;
+
+        // The election is posted, other transactions are appended to the store directly so they needn't be valid
+        let mut board = BulletinBoard::new(MemStore::default()).unwrap();
+        board.post(election.clone().into()).unwrap();
+        assert!(matches!(
+            board.post(election.clone().into()),
+            Err(ValidationError::AlreadyPosted(_))
+        ));
+
+        let mut txs: Vec<SignedTransaction> = vec![election.into()];
+        for i in 0..6u8 {
+            let mut voting_end = VotingEndTransaction::new(election_id, authority_public, 0);
+            voting_end.id.unique_info[0] = i + 1;
+            let voting_end: SignedTransaction =
+                Signed::sign(&authority_secret, voting_end).unwrap().into();
+            let mut store = board.store;
+            store
+                .append_leaf(voting_end.clone(), leaf_hash(&voting_end))
+                .unwrap();
+            txs.push(voting_end);
+
+            // The board's trees are rebuilt from the leaves in its store
+            board = BulletinBoard::new(store).unwrap();
+
+            // Every transaction is provably included in every tree size
+            let root = board.root(election_id);
+            assert_eq!(root.tree_size, txs.len() as u64);
+            for tx in &txs {
+                let proof = board.prove(tx.id()).unwrap();
+                proof.verify(tx, &root).unwrap();
+            }
+        }
+
+        // A proof doesn't verify against a different root, or for a different transaction
+        let root = board.root(election_id);
+        let proof = board.prove(txs[2].id()).unwrap();
+        assert!(proof.verify(&txs[3], &root).is_err());
+
+        let mut bad_root = root.clone();
+        bad_root.root.0[0] ^= 1;
+        assert!(proof.verify(&txs[2], &bad_root).is_err());
+
+        let mut bad_proof = proof.clone();
+        bad_proof.path[0].0[0] ^= 1;
+        assert!(bad_proof.verify(&txs[2], &root).is_err());
+
+        // Other elections have their own, empty, tree
+        let other_election = ElectionTransaction::new(authority_public);
+        assert_eq!(board.root(other_election.id).tree_size, 0);
+        assert!(board.prove(other_election.id).is_none());
+    }
+
+    #[test]
+    fn consistency_proofs() {
+        let contest = Contest {
+            id: "CONTEST".to_string(),
+            index: 0,
+            contest_type: ContestType::Plurality,
+            write_in: false,
+            num_winners: 1,
+            candidates: ["Alice", "Bob"]
+                .iter()
+                .map(|name| Candidate {
+                    id: name.to_string(),
+                    properties: IndexMap::new(),
+                })
+                .collect(),
+            homomorphic: false,
+            max_score: None,
+            properties: IndexMap::new(),
+        };
+        let spec = ElectionSpec {
+            num_trustees: 2,
+            trustees_threshold: 2,
+            num_authenticators: 1,
+            authenticators_threshold: 1,
+            ballots: vec![Ballot {
+                id: "BALLOT".to_string(),
+                contests: vec![0],
+                properties: IndexMap::new(),
+            }],
+            contests: vec![contest],
+            mix_config: None,
+            num_voters: 3,
+            bad_shares: vec![],
+            reshare: None,
+        };
+
+        // Run a whole election through the board, keeping its root after every post
+        let mut store = MemStore::default();
+        store.set_time(1_600_000_000);
+        let mut board = BulletinBoard::new(store).unwrap();
+        let mut roots = Vec::new();
+        let result = simulate_election(
+            [9; 32],
+            &spec,
+            &mut board,
+            |board, tx| {
+                let election_id = tx.id();
+                board.post(tx).unwrap();
+                roots.push(board.root(election_id));
+            },
+            |_rng: &mut dyn RngCore, voter: usize, contest: &Contest| {
+                vec![Selection {
+                    write_in: false,
+                    score: 0,
+                    selection: contest.candidates[voter % 2].id.clone(),
+                }]
+            },
+        )
+        .unwrap();
+        let election_id = result.election.id;
+        assert_eq!(roots.len(), result.transactions.len());
+
+        // Every root is consistent with every later root
+        let empty = BulletinBoard::new(MemStore::default())
+            .unwrap()
+            .root(election_id);
+        roots.insert(0, empty);
+        for (i, old_root) in roots.iter().enumerate() {
+            for new_root in &roots[i..] {
+                let proof = board
+                    .prove_consistency(election_id, old_root.tree_size, new_root.tree_size)
+                    .unwrap();
+                proof.verify(old_root, new_root).unwrap();
+            }
+        }
+
+        // A root isn't consistent with an earlier one, or with one that commits to a different history
+        let (old_root, new_root) = (&roots[5], &roots[roots.len() - 1]);
+        let proof = board
+            .prove_consistency(election_id, old_root.tree_size, new_root.tree_size)
+            .unwrap();
+        assert!(proof.verify(new_root, old_root).is_err());
+        assert!(board
+            .prove_consistency(election_id, new_root.tree_size, old_root.tree_size)
+            .is_none());
+        assert!(board
+            .prove_consistency(election_id, 0, new_root.tree_size + 1)
+            .is_none());
+
+        let mut rewritten_root = old_root.clone();
+        rewritten_root.root.0[0] ^= 1;
+        assert!(proof.verify(&rewritten_root, new_root).is_err());
+
+        let mut bad_proof = proof.clone();
+        bad_proof.path[0].0[0] ^= 1;
+        assert!(bad_proof.verify(old_root, new_root).is_err());
+    }
+}
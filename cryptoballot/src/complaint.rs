@@ -0,0 +1,421 @@
+use crate::reshare::evaluate_commitment;
+use crate::*;
+use cryptid::curve::CurveElem;
+use cryptid::threshold::KeygenCommitment;
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{PublicKey, SecretKey};
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use std::collections::HashSet;
+use x25519_dalek as x25519;
+
+/// Transaction 15: KeyGenComplaint
+///
+/// A trustee's public accusation that another trustee sent it an invalid share during key generation.
+///
+/// The complaint reveals the x25519 shared secret between the two trustees, with a proof that it is correct, so anyone
+/// can decrypt the share and check it against the accused trustee's commitment. A complaint is only valid if the share
+/// really is invalid. The accused trustee is then disqualified: the remaining trustees generate the election key
+/// without them, and they take no part in mixing or decryption.
+///
+/// Complaints can be posted until the EncryptionKey transaction, so a trustee that is slow to check its shares can
+/// still complain after others have posted their public keys. Those trustees then post new public keys without the
+/// accused trustee (see `KeyGenPublicKeyTransaction::disqualified`). Trustees should check their shares with
+/// `Trustee::invalid_shares` before posting their public key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct KeyGenComplaintTransaction {
+    pub id: Identifier,
+    pub election: Identifier,
+    pub trustee_index: u8,
+    #[serde(with = "EdPublicKeyHex")]
+    pub trustee_public_key: PublicKey,
+
+    /// The trustee that sent the invalid share
+    pub accused_index: u8,
+
+    /// The x25519 shared secret between the two trustees, used to encrypt the share
+    pub shared_secret: SharedSecretProof,
+}
+
+/// An x25519 shared secret, with a proof that it was computed with the trustee's x25519 secret key.
+///
+/// x25519 only works with Montgomery u-coordinates, so the proof is made with the equivalent Edwards points.
+/// `public_point` is the trustee's x25519 public key divided by the cofactor, and `shared_point` is the shared secret.
+/// The Chaum-Pedersen proof shows that `log_B(public_point) == log_P(shared_point)`, where `P` is the other trustee's
+/// x25519 public key multiplied by the cofactor.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SharedSecretProof {
+    #[serde(with = "EdwardsPointHex")]
+    pub public_point: EdwardsPoint,
+    #[serde(with = "EdwardsPointHex")]
+    pub shared_point: EdwardsPoint,
+    #[serde(with = "ScalarHex")]
+    pub challenge: Scalar,
+    #[serde(with = "ScalarHex")]
+    pub response: Scalar,
+}
+
+impl KeyGenComplaintTransaction {
+    /// Create a new KeyGenComplaintTransaction against the trustee that sent an invalid share
+    pub fn new(
+        election_id: Identifier,
+        trustee_index: u8,
+        trustee_public_key: PublicKey,
+        accused_index: u8,
+        shared_secret: SharedSecretProof,
+    ) -> Self {
+        KeyGenComplaintTransaction {
+            id: Self::build_id(election_id, trustee_index, accused_index),
+            election: election_id,
+            trustee_index,
+            trustee_public_key,
+            accused_index,
+            shared_secret,
+        }
+    }
+
+    pub fn build_id(election_id: Identifier, trustee_index: u8, accused_index: u8) -> Identifier {
+        let mut unique_info = [0; 16];
+        unique_info[0] = trustee_index;
+        unique_info[1] = accused_index;
+        Identifier::new(
+            election_id,
+            TransactionType::KeyGenComplaint,
+            Some(unique_info),
+        )
+    }
+}
+
+impl CryptoBallotTransaction for KeyGenComplaintTransaction {
+    #[inline(always)]
+    fn id(&self) -> Identifier {
+        self.id
+    }
+
+    #[inline(always)]
+    fn public(&self) -> Option<PublicKey> {
+        Some(self.trustee_public_key)
+    }
+
+    #[inline(always)]
+    fn election_id(&self) -> Identifier {
+        self.election
+    }
+
+    #[inline(always)]
+    fn tx_type() -> TransactionType {
+        TransactionType::KeyGenComplaint
+    }
+
+    /// Validate the transaction
+    ///
+    /// The validation does the following:
+    ///  - Validates that this transaction has been signed by a valid trustee, complaining about another trustee
+    ///  - Validates that the election key has not been posted yet
+    ///  - Validates the shared secret against both trustees' x25519 public keys
+    ///  - Validates that the accused trustee's share, decrypted with the shared secret, does not match their commitment
+    fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
+        let election = store.get_election(self.election)?;
+
+        if Self::build_id(self.election, self.trustee_index, self.accused_index) != self.id {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
+        // Validate that both trustees exist
+        match election.get_trustee(self.trustee_index) {
+            Some(trustee) if trustee.public_key == self.trustee_public_key => {}
+            _ => return Err(ValidationError::TrusteeDoesNotExist(self.trustee_index)),
+        }
+        if election.get_trustee(self.accused_index).is_none() {
+            return Err(ValidationError::TrusteeDoesNotExist(self.accused_index));
+        }
+        if self.accused_index == self.trustee_index {
+            return Err(ValidationError::KeyGenComplaintAgainstSelf);
+        }
+
+        // Complaints are settled before the election key is fixed
+        if store
+            .get_transaction(EncryptionKeyTransaction::build_id(self.election))?
+            .is_some()
+        {
+            return Err(ValidationError::KeyGenComplaintTooLate);
+        }
+
+        let commitment_id =
+            KeyGenCommitmentTransaction::build_id(self.election, self.trustee_index);
+        let commitment: KeyGenCommitmentTransaction = store
+            .get_transaction(commitment_id)?
+            .ok_or(ValidationError::TrusteeMissing(self.trustee_index))?
+            .into();
+
+        let accused_commitment_id =
+            KeyGenCommitmentTransaction::build_id(self.election, self.accused_index);
+        let accused_commitment: KeyGenCommitmentTransaction = store
+            .get_transaction(accused_commitment_id)?
+            .ok_or(ValidationError::TrusteeMissing(self.accused_index))?
+            .into();
+
+        let accused_share_id = KeyGenShareTransaction::build_id(self.election, self.accused_index);
+        let accused_shares: KeyGenShareTransaction = store
+            .get_transaction(accused_share_id)?
+            .ok_or(ValidationError::TrusteeShareMissing(self.accused_index))?
+            .into();
+        let share = accused_shares
+            .shares
+            .get(&self.trustee_index)
+            .ok_or(ValidationError::TrusteeShareMissing(self.accused_index))?;
+
+        let shared_secret = self
+            .shared_secret
+            .verify(
+                self.election,
+                &commitment.x25519_public_key,
+                &accused_commitment.x25519_public_key,
+            )
+            .ok_or(ValidationError::InvalidSharedSecretProof)?;
+
+        // A share that can't be decrypted is just as invalid as one that doesn't match its commitment
+        let encryption_key = share_encryption_key(self.election, &shared_secret);
+        let valid = match share.decrypt(encryption_key) {
+            Ok(share) => share_is_valid(
+                self.trustee_index,
+                election.trustees_threshold,
+                &accused_commitment.commitment,
+                &share,
+            ),
+            Err(_) => false,
+        };
+        if valid {
+            return Err(ValidationError::KeyGenShareValid(self.accused_index));
+        }
+
+        Ok(())
+    }
+}
+
+impl SharedSecretProof {
+    /// Verify the proof against both trustees' x25519 public keys, returning the x25519 shared secret
+    pub fn verify(
+        &self,
+        election_id: Identifier,
+        public_key: &x25519::PublicKey,
+        other_public_key: &x25519::PublicKey,
+    ) -> Option<[u8; 32]> {
+        if !self.public_point.is_torsion_free() || !self.shared_point.is_torsion_free() {
+            return None;
+        }
+        if self
+            .public_point
+            .mul_by_cofactor()
+            .to_montgomery()
+            .to_bytes()
+            != public_key.to_bytes()
+        {
+            return None;
+        }
+        let other_point = cofactor_point(other_public_key)?;
+
+        // Recompute the commitments: a = z⋅B - e⋅public_point and b = z⋅P - e⋅shared_point
+        let commitments = (
+            &self.response * &ED25519_BASEPOINT_POINT - &self.challenge * &self.public_point,
+            &self.response * &other_point - &self.challenge * &self.shared_point,
+        );
+
+        let challenge = shared_secret_challenge(
+            election_id,
+            &self.public_point,
+            &other_point,
+            &self.shared_point,
+            &commitments,
+        );
+        if challenge != self.challenge {
+            return None;
+        }
+
+        Some(self.shared_point.to_montgomery().to_bytes())
+    }
+}
+
+impl Trustee {
+    /// Check the shares this trustee received during key generation against their senders' commitments.
+    ///
+    /// Returns the trustees that sent an invalid share. This trustee should post a KeyGenComplaint against each of them
+    /// (see `Trustee::shared_secret_proof`) instead of its public key.
+    pub fn invalid_shares(
+        &self,
+        sk: &SecretKey,
+        x25519_public_keys: &[(u8, x25519::PublicKey)],
+        commitments: &[(u8, KeygenCommitment)],
+        shares: &[(u8, EncryptedShare)], // From, Share
+        election_id: Identifier,
+    ) -> Vec<u8> {
+        let mut invalid = Vec::new();
+        for (sender_index, share) in shares {
+            let commitment = commitments.iter().find(|(index, _)| index == sender_index);
+            let public_key = x25519_public_keys
+                .iter()
+                .find(|(index, _)| index == sender_index);
+
+            let valid = match (commitment, public_key) {
+                (Some((_, commitment)), Some((_, public_key))) => {
+                    let shared_secret = self.shared_secret(sk, election_id, public_key);
+                    match share.decrypt(shared_secret) {
+                        Ok(share) => share_is_valid(self.index, self.threshold, commitment, &share),
+                        Err(_) => false,
+                    }
+                }
+                _ => false,
+            };
+
+            if !valid {
+                invalid.push(*sender_index);
+            }
+        }
+
+        invalid
+    }
+
+    /// Reveal this trustee's x25519 shared secret with another trustee, for a KeyGenComplaint transaction.
+    ///
+    /// Revealing the shared secret exposes only the shares sent between these two trustees.
+    pub fn shared_secret_proof<R: CryptoRng + RngCore>(
+        &self,
+        rng: &mut R,
+        sk: &SecretKey,
+        election_id: Identifier,
+        other_public_key: &x25519::PublicKey,
+    ) -> Result<SharedSecretProof, Error> {
+        // x25519 clamps its secret key to a multiple of the cofactor, so divide it back out
+        let mut secret = self.x25519_secret_bytes(sk, election_id);
+        secret[0] &= 248;
+        secret[31] &= 127;
+        secret[31] |= 64;
+        let secret = Scalar::from_bytes_mod_order(secret) * Scalar::from(8u64).invert();
+
+        let other_point = cofactor_point(other_public_key).ok_or(Error::InvalidX25519PublicKey)?;
+        let public_point = &secret * &ED25519_BASEPOINT_POINT;
+        let shared_point = &secret * &other_point;
+
+        let nonce = Scalar::random(rng);
+        let commitments = (&nonce * &ED25519_BASEPOINT_POINT, &nonce * &other_point);
+        let challenge = shared_secret_challenge(
+            election_id,
+            &public_point,
+            &other_point,
+            &shared_point,
+            &commitments,
+        );
+
+        Ok(SharedSecretProof {
+            public_point,
+            shared_point,
+            challenge,
+            response: nonce + challenge * secret,
+        })
+    }
+}
+
+/// The trustees disqualified by a KeyGenComplaint transaction
+pub fn disqualified_trustees<S: Store>(
+    store: &S,
+    election_id: Identifier,
+) -> Result<HashSet<u8>, ValidationError> {
+    store
+        .get_multiple(election_id, TransactionType::KeyGenComplaint)
+        .map(|tx| -> Result<_, ValidationError> {
+            let tx: KeyGenComplaintTransaction = tx?.into();
+            Ok(tx.accused_index)
+        })
+        .collect()
+}
+
+/// The election's trustees that have not been disqualified during key generation.
+///
+/// Only the qualified trustees' commitments and shares are used to generate the election key.
+pub fn qualified_trustees<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+) -> Result<Vec<Trustee>, ValidationError> {
+    let disqualified = disqualified_trustees(store, election.id)?;
+
+    Ok(election
+        .get_full_trustees()
+        .into_iter()
+        .filter(|trustee| !disqualified.contains(&trustee.index))
+        .collect())
+}
+
+/// The public keys of the qualified trustees, generated without every disqualified trustee.
+///
+/// Public keys posted before a later complaint are left out: they list fewer disqualified trustees.
+pub fn qualified_public_keys<S: Store>(
+    store: &S,
+    election_id: Identifier,
+) -> Result<Vec<KeyGenPublicKeyTransaction>, ValidationError> {
+    let num_disqualified = disqualified_trustees(store, election_id)?.len();
+
+    let mut public_keys = Vec::new();
+    for tx in store.get_multiple(election_id, TransactionType::KeyGenPublicKey) {
+        let tx: KeyGenPublicKeyTransaction = tx?.into();
+        // A public key can only list disqualified trustees, so listing as many means listing all of them
+        if tx.disqualified.len() == num_disqualified {
+            public_keys.push(tx);
+        }
+    }
+    Ok(public_keys)
+}
+
+/// A trustee's public key from `qualified_public_keys`
+pub fn qualified_public_key<S: Store>(
+    store: &S,
+    election_id: Identifier,
+    trustee_index: u8,
+) -> Result<KeyGenPublicKeyTransaction, ValidationError> {
+    let num_disqualified = disqualified_trustees(store, election_id)?.len();
+    let id =
+        KeyGenPublicKeyTransaction::build_id(election_id, trustee_index, num_disqualified as u8);
+    Ok(store.get_keygen_public_key(id)?.tx)
+}
+
+// Check a key generation share against its sender's commitment to a polynomial of degree `threshold - 1`: the share is
+// the polynomial's value at the recipient's index, so `share⋅G` must be the commitment evaluated there
+pub(crate) fn share_is_valid(
+    recipient_index: u8,
+    threshold: u8,
+    commitment: &KeygenCommitment,
+    share: &cryptid::Scalar,
+) -> bool {
+    commitment.elems().len() == threshold as usize
+        && CurveElem::generator().scaled(share)
+            == evaluate_commitment(commitment.elems(), recipient_index)
+}
+
+// An x25519 public key as an Edwards point, multiplied by the cofactor so it is in the prime-order subgroup
+pub(crate) fn cofactor_point(public_key: &x25519::PublicKey) -> Option<EdwardsPoint> {
+    let point = MontgomeryPoint(public_key.to_bytes()).to_edwards(0)?;
+
+    Some(point.mul_by_cofactor())
+}
+
+// Fiat-Shamir challenge for a shared secret proof
+fn shared_secret_challenge(
+    election_id: Identifier,
+    public_point: &EdwardsPoint,
+    other_point: &EdwardsPoint,
+    shared_point: &EdwardsPoint,
+    commitments: &(EdwardsPoint, EdwardsPoint),
+) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(b"cryptoballot shared secret proof");
+    hasher.update(&election_id.to_bytes());
+    hasher.update(public_point.compress().as_bytes());
+    hasher.update(other_point.compress().as_bytes());
+    hasher.update(shared_point.compress().as_bytes());
+    hasher.update(commitments.0.compress().as_bytes());
+    hasher.update(commitments.1.compress().as_bytes());
+
+    Scalar::from_hash(hasher)
+}
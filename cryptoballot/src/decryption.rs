@@ -13,7 +13,8 @@ pub struct PartialDecryptionTransaction {
     pub id: Identifier,
     pub election_id: Identifier,
 
-    /// The upstream transaction ID, either the vote transaction ID or the mix transaction ID
+    /// The upstream transaction ID, either the vote transaction ID or the mix transaction ID.
+    /// For homomorphic contests, this is the VotingEnd transaction ID and the sum of all votes is decrypted.
     pub upstream_id: Identifier,
 
     /// If this is from a mix, the index of the ciphertext in the `mixed_ciphertexts` field, or `0` if from a vote transaction
@@ -28,6 +29,11 @@ pub struct PartialDecryptionTransaction {
     pub trustee_public_key: PublicKey,
 
     pub partial_decryption: Vec<DecryptShare>,
+
+    /// Partial decryptions by a trustee that joined the election in a reshare, used instead of `partial_decryption`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reshared_decryption: Vec<ResharedDecryptShare>,
 }
 
 impl PartialDecryptionTransaction {
@@ -56,9 +62,33 @@ impl PartialDecryptionTransaction {
             contest_index,
             trustee_public_key,
             partial_decryption,
+            reshared_decryption: Vec::new(),
         }
     }
 
+    /// Create a new PartialDecryptionTransaction for a trustee that joined the election in a reshare
+    pub fn new_reshared(
+        election_id: Identifier,
+        upstream_id: Identifier,
+        upstream_index: u16,
+        trustee_index: u8,
+        contest_index: u32,
+        trustee_public_key: PublicKey,
+        reshared_decryption: Vec<ResharedDecryptShare>,
+    ) -> Self {
+        let mut tx = Self::new(
+            election_id,
+            upstream_id,
+            upstream_index,
+            trustee_index,
+            contest_index,
+            trustee_public_key,
+            Vec::new(),
+        );
+        tx.reshared_decryption = reshared_decryption;
+        tx
+    }
+
     // Has an ID format of <election-id><type><upstream-tx-type><voter-anonymous-key/mix-unique-info><trustee-index>
     pub fn build_id(
         election_id: Identifier,
@@ -103,9 +133,10 @@ impl CryptoBallotTransaction for PartialDecryptionTransaction {
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         let election = store.get_election(self.election_id)?;
 
-        // Make sure the trustee is correct
+        // Make sure the trustee is one of the trustees currently holding the key
+        let trustees = current_trustees(store, election.inner())?;
         let mut trustee = None;
-        for election_trustee in election.get_full_trustees() {
+        for election_trustee in trustees.trustees.iter() {
             if election_trustee.index == self.trustee_index
                 && election_trustee.public_key == self.trustee_public_key
             {
@@ -130,40 +161,67 @@ impl CryptoBallotTransaction for PartialDecryptionTransaction {
 
         // Make sure voting end exists
         let voting_end_id = Identifier::new(self.election_id, TransactionType::VotingEnd, None);
-        if store.get_transaction(voting_end_id).is_none() {
+        if store.get_transaction(voting_end_id)?.is_none() {
             return Err(ValidationError::MisingVotingEndTransaction);
         }
 
-        // Get the ciphertext either from the vote or the mix
-        let encrypted_vote: Vec<Ciphertext> = encrypted_vote_from_upstream_tx(
+        // Get the ciphertext either from the vote or the mix, or the sum of all votes
+        let encrypted_vote: Vec<Ciphertext> = upstream_ciphertexts(
             store,
+            election.inner(),
             self.upstream_id,
             self.upstream_index,
             self.contest_index,
-            &election.mix_config,
         )?;
 
+        // Trustees that joined in a reshare prove their partial decryptions against their verification key
+        if trustees.generation > 0 {
+            let public_key_id = ResharePublicKeyTransaction::build_id(
+                self.election_id,
+                trustees.generation,
+                self.trustee_index,
+            );
+            let public_key: ResharePublicKeyTransaction = store
+                .get_transaction(public_key_id)?
+                .ok_or(ValidationError::TrusteeMissing(self.trustee_index))?
+                .into();
+
+            if !self.partial_decryption.is_empty()
+                || encrypted_vote.len() != self.reshared_decryption.len()
+            {
+                return Err(ValidationError::PartialDecryptionProofFailed);
+            }
+            for (partial, ciphertext) in self.reshared_decryption.iter().zip(&encrypted_vote) {
+                if !partial.verify(&public_key.verification_key, ciphertext) {
+                    return Err(ValidationError::PartialDecryptionProofFailed);
+                }
+            }
+
+            return Ok(());
+        }
+
         // Get the public key transaction for this trustee
-        let pkey_tx_id = KeyGenPublicKeyTransaction::build_id(self.election_id, self.trustee_index);
-        let public_key = store.get_keygen_public_key(pkey_tx_id)?;
+        let public_key = qualified_public_key(store, self.election_id, self.trustee_index)?;
 
         // Validate that the public_key transaction matches
-        if self.trustee_index != public_key.inner().trustee_index
-            || self.trustee_public_key != public_key.inner().trustee_public_key
+        if self.trustee_index != public_key.trustee_index
+            || self.trustee_public_key != public_key.trustee_public_key
         {
             return Err(ValidationError::TrusteePublicKeyMismatch(
                 self.trustee_index,
             ));
         }
 
-        if encrypted_vote.len() != self.partial_decryption.len() {
+        if encrypted_vote.len() != self.partial_decryption.len()
+            || !self.reshared_decryption.is_empty()
+        {
             // TODO: Use a dedicated errror
             return Err(ValidationError::PartialDecryptionProofFailed);
         }
 
         // Verify the partial decryption proof
         for (i, partial) in self.partial_decryption.iter().enumerate() {
-            if !partial.verify(&public_key.inner().public_key_proof, &encrypted_vote[i]) {
+            if !partial.verify(&public_key.public_key_proof, &encrypted_vote[i]) {
                 return Err(ValidationError::PartialDecryptionProofFailed);
             }
         }
@@ -182,7 +240,8 @@ pub struct DecryptionTransaction {
     pub id: Identifier,
     pub election_id: Identifier,
 
-    /// The Vote or the Mix transaction, depending on if we are using a mixnet
+    /// The Vote or the Mix transaction, depending on if we are using a mixnet.
+    /// For homomorphic contests, the VotingEnd transaction.
     pub upstream_id: Identifier,
 
     /// If we are using a mixnet, the index in the reencrypted field, or `0` if upstream is a vote transaction
@@ -194,7 +253,8 @@ pub struct DecryptionTransaction {
     /// The trustees (as defined by index) who's PartialDecryption transactions were used to produce this full decryption
     pub trustees: Vec<u8>,
 
-    /// The decrypted vote
+    /// The decrypted vote.
+    /// For homomorphic contests, one selection per candidate whose score is the candidate's total.
     pub decrypted_vote: Vec<Selection>,
 }
 
@@ -270,31 +330,20 @@ impl CryptoBallotTransaction for DecryptionTransaction {
         }
 
         let election = store.get_election(self.election_id)?;
-
-        // Get the ciphertext either from the vote or the mix
-        let encrypted_vote: Vec<Ciphertext> = encrypted_vote_from_upstream_tx(
-            store,
-            self.upstream_id,
-            self.upstream_index,
-            self.contest_index,
-            &election.mix_config,
-        )?;
+        let contest = election
+            .get_contest(self.contest_index)
+            .ok_or(ValidationError::InvalidUpstreamContestIndex)?;
 
         // Get all pubkeys mapped by trustee ID
-        let pubkeys: Vec<KeyGenPublicKeyTransaction> = store
-            .get_multiple(self.election_id, TransactionType::KeyGenPublicKey)
-            .into_iter()
-            .map(|tx| tx.into())
-            .map(|tx: Signed<KeyGenPublicKeyTransaction>| tx.tx)
-            .collect();
-
-        // Get all partial decryptions mapped by trustee ID
+        let pubkeys = qualified_public_keys(store, self.election_id)?;
+
+        // Get all partial decryptions mapped by trustee ID, from the trustees currently holding the key
+        let trustees = current_trustees(store, election.inner())?;
         let mut partials = Vec::with_capacity(self.trustees.len());
         for trustee_index in self.trustees.iter() {
             // TODO: This could be more efficient with a range
-            let trustee = election
-                .inner()
-                .get_trustee(*trustee_index)
+            let trustee = trustees
+                .get(*trustee_index)
                 .ok_or(ValidationError::TrusteeDoesNotExist(*trustee_index))?;
             let partial_id = PartialDecryptionTransaction::build_id(
                 self.election_id,
@@ -309,7 +358,7 @@ impl CryptoBallotTransaction for DecryptionTransaction {
         }
 
         // Make sure we have enough shares
-        let required_shares = election.trustees_threshold as usize;
+        let required_shares = trustees.threshold as usize;
         if partials.len() < required_shares {
             return Err(ValidationError::NotEnoughShares(
                 required_shares,
@@ -317,20 +366,62 @@ impl CryptoBallotTransaction for DecryptionTransaction {
             ));
         }
 
-        // Decrypt the vote
-        let decrypted_vote = decrypt_vote(
-            &encrypted_vote,
-            election.inner().trustees_threshold,
-            &election.inner().trustees,
-            &pubkeys,
-            &partials,
-        )?;
+        // Decrypt the vote, or the totals for a homomorphic contest
+        let decrypted_vote = if contest.homomorphic {
+            // Individual votes in a homomorphic contest must never be decrypted
+            if self.upstream_id
+                != Identifier::new(self.election_id, TransactionType::VotingEnd, None)
+            {
+                return Err(ValidationError::InvalidUpstreamID);
+            }
+            if self.upstream_index != 0 {
+                return Err(ValidationError::InvalidUpstreamIndex);
+            }
+
+            let (totals, num_votes) = homomorphic_aggregate(store, self.election_id, contest)?;
+
+            if trustees.generation > 0 {
+                decrypt_totals_reshared(contest, &totals, num_votes, &partials)?
+            } else {
+                decrypt_totals(
+                    contest,
+                    &totals,
+                    num_votes,
+                    election.inner().trustees_threshold,
+                    &election.inner().trustees,
+                    &pubkeys,
+                    &partials,
+                )?
+            }
+        } else {
+            // Get the ciphertext either from the vote or the mix
+            let encrypted_vote: Vec<Ciphertext> = encrypted_vote_from_upstream_tx(
+                store,
+                self.upstream_id,
+                self.upstream_index,
+                self.contest_index,
+                &election.mix_config,
+            )?;
+
+            if trustees.generation > 0 {
+                decrypt_vote_reshared(&encrypted_vote, &partials)?
+            } else {
+                decrypt_vote(
+                    &encrypted_vote,
+                    election.inner().trustees_threshold,
+                    &election.inner().trustees,
+                    &pubkeys,
+                    &partials,
+                )?
+            }
+        };
 
         if decrypted_vote != self.decrypted_vote {
             return Err(ValidationError::VoteDecryptionMismatch);
         }
 
-        // TODO: Check that the selections match the ballot-style settings
+        // Selections that do not follow the contest rules are not rejected here. The voter
+        // cast them, so they are decrypted as-is and recorded as spoiled ballots by the tally.
 
         Ok(())
     }
@@ -385,6 +476,43 @@ pub fn decrypt_vote(
     Ok(results)
 }
 
+/// Get the ciphertexts to be decrypted for some upstream transaction ID.
+///
+/// For homomorphic contests the upstream must be the VotingEnd transaction, and the ciphertexts are the sum of all votes
+/// (see `homomorphic_aggregate`). Otherwise it is a vote or mix transaction (see `encrypted_vote_from_upstream_tx`).
+pub fn upstream_ciphertexts<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+    upstream_id: Identifier,
+    upstream_index: u16,
+    contest_index: u32,
+) -> Result<Vec<Ciphertext>, ValidationError> {
+    let contest = election
+        .get_contest(contest_index)
+        .ok_or(ValidationError::InvalidUpstreamContestIndex)?;
+
+    if !contest.homomorphic {
+        return encrypted_vote_from_upstream_tx(
+            store,
+            upstream_id,
+            upstream_index,
+            contest_index,
+            &election.mix_config,
+        );
+    }
+
+    // Individual votes in a homomorphic contest must never be decrypted
+    if upstream_id != Identifier::new(election.id, TransactionType::VotingEnd, None) {
+        return Err(ValidationError::InvalidUpstreamID);
+    }
+    if upstream_index != 0 {
+        return Err(ValidationError::InvalidUpstreamIndex);
+    }
+
+    let (totals, _num_votes) = homomorphic_aggregate(store, election.id, contest)?;
+    Ok(totals)
+}
+
 /// A convenience function for getting an encrypted-vote from some upstream transaction ID.
 /// The upstream transaction should either be a mixnet or a vote transaction.
 pub fn encrypted_vote_from_upstream_tx<S: Store>(
@@ -406,6 +534,11 @@ pub fn encrypted_vote_from_upstream_tx<S: Store>(
 
             let vote = store.get_vote(upstream_id)?.tx;
 
+            // Only the latest revision of a voter's vote is decrypted
+            if !vote_is_latest(store, &vote)? {
+                return Err(ValidationError::VoteReplaced);
+            }
+
             for encrypted_vote in vote.encrypted_votes {
                 if encrypted_vote.contest_index == contest_index {
                     return Ok(encrypted_vote.selections);
@@ -471,6 +604,13 @@ fn build_unique_info(
         // <contest-index><upstream-type><voter-public-key><trustee-index>
         //      4 bytes       1 byte         10 bytes          1 byte
     }
+    if upstream_id.transaction_type == TransactionType::VotingEnd {
+        unique_info[15] = trustee_index; // 1 byte
+
+        // Result:
+        // <contest-index><upstream-type><null-bytes><trustee-index>
+        //      4 bytes       1 byte       10 bytes      1 byte
+    }
 
     unique_info
 }
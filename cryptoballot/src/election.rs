@@ -2,6 +2,7 @@ use crate::*;
 use ed25519_dalek::PublicKey;
 use indexmap::IndexMap;
 use rand::Rng;
+use std::collections::HashSet;
 use uuid::Uuid;
 
 /// Transaction 1: Election
@@ -41,6 +42,18 @@ pub struct ElectionTransaction {
     /// List of contests in this election
     pub contests: Vec<Contest>,
 
+    /// The time voting opens, in seconds since the unix epoch. None implies voting opens once the encryption key is published.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voting_start: Option<u64>,
+
+    /// The time voting closes, in seconds since the unix epoch. None implies voting stays open until the authority posts a VotingEnd transaction.
+    ///
+    /// Once this time has passed, any trustee may post the VotingEnd transaction on the authority's behalf.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voting_end: Option<u64>,
+
     /// Application specific properties.
     ///
     /// Hashmaps are not allowed because their unstable ordering leads to non-determinism.
@@ -65,6 +78,8 @@ impl ElectionTransaction {
             mix_config: None,
             ballots: vec![],
             contests: vec![],
+            voting_start: None,
+            voting_end: None,
             properties: IndexMap::new(),
         }
     }
@@ -107,6 +122,16 @@ impl ElectionTransaction {
         None
     }
 
+    /// Get a contest with the given index
+    pub fn get_contest(&self, contest_index: u32) -> Option<&Contest> {
+        for contest in &self.contests {
+            if contest.index == contest_index {
+                return Some(contest);
+            }
+        }
+        None
+    }
+
     /// Get all trustees with all info
     pub fn get_full_trustees(&self) -> Vec<Trustee> {
         let mut trustees = Vec::with_capacity(self.trustees.len());
@@ -148,33 +173,149 @@ impl CryptoBallotTransaction for ElectionTransaction {
         }
 
         // Make sure trustees settings are sane
-        if self.trustees_threshold as usize > self.trustees.len() {
-            return Err(ValidationError::InvalidTrusteeThreshold);
-        }
+        validate_trustees(&self.trustees, self.trustees_threshold)?;
 
         // Make sure authenticator settings are sane
-        if self.authenticators_threshold > self.authenticators.len() as u8 {
+        // A zero threshold would let votes through without any authentication at all
+        if self.authenticators_threshold > self.authenticators.len() as u8
+            || (self.authenticators_threshold == 0 && !self.authenticators.is_empty())
+        {
             return Err(ValidationError::InvalidAuthThreshold);
         }
 
+        // Make sure the mixnet settings are sane
+        if let Some(mix_config) = &self.mix_config {
+            if mix_config.timeout_secs == 0 {
+                return Err(ValidationError::MixConfigZeroTimeout);
+            }
+            if mix_config.batch_size == Some(0) {
+                return Err(ValidationError::MixConfigZeroBatchSize);
+            }
+        }
+
+        // Make sure the voting window is sane
+        if let (Some(voting_start), Some(voting_end)) = (self.voting_start, self.voting_end) {
+            if voting_start >= voting_end {
+                return Err(ValidationError::InvalidVotingWindow);
+            }
+        }
+
+        // Make sure contests are well formed
+        let mut contest_ids = HashSet::with_capacity(self.contests.len());
+        let mut contest_indexes = HashSet::with_capacity(self.contests.len());
+        for contest in &self.contests {
+            if !contest_ids.insert(contest.id.as_str()) {
+                return Err(ValidationError::DuplicateContestId(contest.id.clone()));
+            }
+            if !contest_indexes.insert(contest.index) {
+                return Err(ValidationError::DuplicateContestIndex(contest.index));
+            }
+            if !contest.tally_supported() {
+                return Err(ValidationError::UnsupportedContestType(contest.index));
+            }
+            if contest.homomorphic && contest.max_counter().is_none() {
+                return Err(ValidationError::UnsupportedHomomorphicContest(
+                    contest.index,
+                ));
+            }
+            if contest.max_score.unwrap_or_default() > MAX_SCORE {
+                return Err(ValidationError::MaxScoreTooHigh(contest.index));
+            }
+
+            let mut candidate_ids = HashSet::with_capacity(contest.candidates.len());
+            for candidate in &contest.candidates {
+                if !candidate_ids.insert(candidate.id.as_str()) {
+                    return Err(ValidationError::DuplicateCandidateId(
+                        contest.index,
+                        candidate.id.clone(),
+                    ));
+                }
+            }
+        }
+
+        // Make sure ballots and contests are consistent
+        let mut ballot_ids = HashSet::with_capacity(self.ballots.len());
+        let mut contests_on_ballots = HashSet::with_capacity(self.contests.len());
+        for ballot in &self.ballots {
+            if !ballot_ids.insert(ballot.id.as_str()) {
+                return Err(ValidationError::DuplicateBallotId(ballot.id.clone()));
+            }
+            for contest_index in &ballot.contests {
+                if !contest_indexes.contains(contest_index) {
+                    return Err(ValidationError::BallotContestDoesNotExist(
+                        ballot.id.clone(),
+                        *contest_index,
+                    ));
+                }
+                contests_on_ballots.insert(*contest_index);
+            }
+        }
+        for contest in &self.contests {
+            if !contests_on_ballots.contains(&contest.index) {
+                return Err(ValidationError::ContestNotOnBallot(contest.index));
+            }
+        }
+
+        // Make sure every authenticator has a key for every ballot, only for known ballots, and of the right key type
+        for authenticator in &self.authenticators {
+            for ballot in &self.ballots {
+                if !authenticator.public_keys.contains_key(&ballot.id) {
+                    return Err(ValidationError::AuthenticatorMissingBallotKey(
+                        authenticator.id,
+                        ballot.id.clone(),
+                    ));
+                }
+            }
+            for (ballot_id, public_key) in &authenticator.public_keys {
+                if !ballot_ids.contains(ballot_id.as_str()) {
+                    return Err(ValidationError::AuthenticatorUnknownBallot(
+                        authenticator.id,
+                        ballot_id.clone(),
+                    ));
+                }
+                if public_key.key_type() != authenticator.key_type {
+                    return Err(ValidationError::AuthenticatorKeyTypeMismatch(
+                        authenticator.id,
+                        ballot_id.clone(),
+                    ));
+                }
+            }
+        }
+
         // TODO: Make sure the encryption public-key is well-formed
-        // TODO: check parsing of public key
-        // TODO: check that we have at least 1 trustee
-        // TODO: Hard Maximum of 255 trustees (index needs to fit in a non-zero u8)
-        // TODO: Sanity check ballot-ids in authenticators
-        // TODO: MixConfig validation: non-zero on all three params
         // TODO: Check that properties do not contain hashmaps (due to unstable ordering) (including in ballots, contests, and candidates)
-        // TODO: Check that ballots and contests are consistent and well formed
-        //       All contests must exist in at least one ballot
-        //       All ballot contests must exist
-        //       All contest and ballots have a unique ID
-        //       All contests have a unique index
-        //       All candidates have a unique index within their contest
 
         Ok(())
     }
 }
 
+// Make sure trustee settings are sane, for an election or a reshare
+pub(crate) fn validate_trustees(
+    trustees: &[Trustee],
+    threshold: u8,
+) -> Result<(), ValidationError> {
+    if trustees.is_empty() {
+        return Err(ValidationError::NoTrustees);
+    }
+    if trustees.len() > 255 {
+        return Err(ValidationError::TooManyTrustees);
+    }
+    if threshold == 0 || threshold as usize > trustees.len() {
+        return Err(ValidationError::InvalidTrusteeThreshold);
+    }
+    let mut trustee_indexes = HashSet::with_capacity(trustees.len());
+    for trustee in trustees {
+        if trustee.index == 0 {
+            return Err(ValidationError::TrusteeIndexZero);
+        }
+        if !trustee_indexes.insert(trustee.index) {
+            return Err(ValidationError::DuplicateTrusteeIndex(trustee.index));
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -204,6 +345,8 @@ mod tests {
             num_winners: 1,
             write_in: true,
             candidates: vec![],
+            homomorphic: false,
+            max_score: None,
             properties: IndexMap::new(),
         };
 
@@ -211,7 +354,7 @@ mod tests {
         let (authenticator, authn_secrets) =
             Authenticator::new(256, &vec!["TEST".to_string()]).unwrap();
         let _authn_secret = authn_secrets.get(&ballot.id).unwrap();
-        let _authn_public = authenticator.public_keys.get(&ballot.id).unwrap().as_ref();
+        let _authn_public = authenticator.public_keys.get(&ballot.id).unwrap();
 
         // Create 1 trustee
         let (trustee, _trustee_secret) = Trustee::new(1, 1, 1);
@@ -259,4 +402,245 @@ mod tests {
         assert!(election.get_trustee(0).is_none());
         assert!(election.get_trustee(2).is_none());
     }
+
+    #[test]
+    fn election_structural_validation() {
+        let store = MemStore::default();
+        let (_authority_secret, authority_public) = generate_keypair();
+
+        let contest = |id: &str, index: u32| Contest {
+            id: id.to_string(),
+            index,
+            contest_type: ContestType::Plurality,
+            num_winners: 1,
+            write_in: true,
+            candidates: vec![],
+            homomorphic: false,
+            max_score: None,
+            properties: IndexMap::new(),
+        };
+
+        let (authenticator, _authn_secrets) =
+            Authenticator::new(256, &vec!["BALLOT1".to_string()]).unwrap();
+        let (trustee_1, _) = Trustee::new(1, 2, 2);
+        let (trustee_2, _) = Trustee::new(2, 2, 2);
+
+        let mut valid = ElectionTransaction::new(authority_public);
+        valid.ballots = vec![Ballot {
+            id: "BALLOT1".to_string(),
+            contests: vec![0, 1],
+            properties: IndexMap::new(),
+        }];
+        valid.contests = vec![contest("CONTEST1", 0), contest("CONTEST2", 1)];
+        valid.authenticators = vec![authenticator.clone()];
+        valid.trustees = vec![trustee_1, trustee_2];
+        valid.trustees_threshold = 2;
+        valid.validate_tx(&store).unwrap();
+
+        // Trustees
+        let mut election = valid.clone();
+        election.trustees_threshold = 0;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::InvalidTrusteeThreshold)
+        ));
+
+        let mut election = valid.clone();
+        election.trustees[1].index = 0;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::TrusteeIndexZero)
+        ));
+
+        let mut election = valid.clone();
+        election.trustees[1].index = 1;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::DuplicateTrusteeIndex(1))
+        ));
+
+        // Authenticators
+        let mut election = valid.clone();
+        election.authenticators_threshold = 0;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::InvalidAuthThreshold)
+        ));
+
+        let mut election = valid.clone();
+        election.authenticators_threshold = 2;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::InvalidAuthThreshold)
+        ));
+
+        // Mix config
+        let mut election = valid.clone();
+        election.mix_config = Some(MixConfig {
+            timeout_secs: 0,
+            batch_size: None,
+        });
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::MixConfigZeroTimeout)
+        ));
+
+        let mut election = valid.clone();
+        election.mix_config = Some(MixConfig {
+            timeout_secs: 600,
+            batch_size: Some(0),
+        });
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::MixConfigZeroBatchSize)
+        ));
+
+        // Voting window
+        let mut election = valid.clone();
+        election.voting_start = Some(1_600_000_000);
+        election.voting_end = Some(1_600_000_000);
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::InvalidVotingWindow)
+        ));
+
+        let mut election = valid.clone();
+        election.voting_end = Some(1_600_000_000);
+        election.validate_tx(&store).unwrap();
+
+        // Contests
+        let mut election = valid.clone();
+        election.contests[1].id = "CONTEST1".to_string();
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::DuplicateContestId(_))
+        ));
+
+        let mut election = valid.clone();
+        election.contests[1].index = 0;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::DuplicateContestIndex(0))
+        ));
+
+        let mut election = valid.clone();
+        election.contests[0].candidates = vec![
+            Candidate {
+                id: "Alice".to_string(),
+                properties: IndexMap::new(),
+            },
+            Candidate {
+                id: "Alice".to_string(),
+                properties: IndexMap::new(),
+            },
+        ];
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::DuplicateCandidateId(0, _))
+        ));
+
+        // Instant-runoff elects a single candidate, and every contest elects at least one
+        let mut election = valid.clone();
+        election.contests[0].num_winners = 0;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::UnsupportedContestType(0))
+        ));
+        election.contests[0].contest_type = ContestType::InstantRunoff {
+            quota: Quota::default(),
+        };
+        election.contests[0].num_winners = 2;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::UnsupportedContestType(0))
+        ));
+        election.contests[0].num_winners = 1;
+        election.validate_tx(&store).unwrap();
+
+        // Max scores are capped, since proofs grow with them
+        let mut election = valid.clone();
+        election.contests[0].contest_type = ContestType::Score;
+        election.validate_tx(&store).unwrap();
+        election.contests[0].max_score = Some(MAX_SCORE);
+        election.validate_tx(&store).unwrap();
+        election.contests[0].max_score = Some(MAX_SCORE + 1);
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::MaxScoreTooHigh(0))
+        ));
+
+        // Homomorphic contests can't have write-ins, and Score contests need a max score
+        let mut election = valid.clone();
+        election.contests[0].homomorphic = true;
+        election.contests[0].write_in = true;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::UnsupportedHomomorphicContest(0))
+        ));
+        election.contests[0].write_in = false;
+        election.validate_tx(&store).unwrap();
+        election.contests[0].contest_type = ContestType::Score;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::UnsupportedHomomorphicContest(0))
+        ));
+        election.contests[0].max_score = Some(5);
+        election.validate_tx(&store).unwrap();
+        election.contests[0].contest_type = ContestType::Borda;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::UnsupportedHomomorphicContest(0))
+        ));
+
+        // Ballots
+        let mut election = valid.clone();
+        election.ballots[0].contests = vec![0, 1, 2];
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::BallotContestDoesNotExist(_, 2))
+        ));
+
+        let mut election = valid.clone();
+        election.ballots[0].contests = vec![0];
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::ContestNotOnBallot(1))
+        ));
+
+        let mut election = valid.clone();
+        election.ballots.push(election.ballots[0].clone());
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::DuplicateBallotId(_))
+        ));
+
+        // Authenticators
+        let mut election = valid.clone();
+        election.ballots.push(Ballot {
+            id: "BALLOT2".to_string(),
+            contests: vec![0],
+            properties: IndexMap::new(),
+        });
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::AuthenticatorMissingBallotKey(_, _))
+        ));
+
+        let mut election = valid.clone();
+        let key = authenticator.public_keys["BALLOT1"].clone();
+        election.authenticators[0]
+            .public_keys
+            .insert("BALLOT3".to_string(), key);
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::AuthenticatorUnknownBallot(_, _))
+        ));
+
+        let mut election = valid;
+        election.authenticators[0].key_type = AuthKeyType::Schnorr;
+        assert!(matches!(
+            election.validate_tx(&store),
+            Err(ValidationError::AuthenticatorKeyTypeMismatch(_, _))
+        ));
+    }
 }
@@ -23,6 +23,9 @@ pub enum Error {
     #[error("cryptoballot: invalid identifier - wrong length")]
     IdentifierBadLen,
 
+    #[error("cryptoballot: invalid identifier - unknown transaction type {0}")]
+    IdentifierBadType(u8),
+
     #[error("cryptoballot: CBOR error deserializing transaction: {0}")]
     CBORDeserialization(#[from] serde_cbor::Error),
 
@@ -44,20 +47,62 @@ pub enum Error {
     #[error("cryptoballot: invalid x25519 public key")]
     InvalidX25519PublicKey,
 
+    #[error("cryptoballot: invalid ristretto point")]
+    InvalidRistrettoPoint,
+
+    #[error("cryptoballot: invalid scalar")]
+    InvalidScalar,
+
+    #[error("cryptoballot: invalid edwards point")]
+    InvalidEdwardsPoint,
+
+    #[error("cryptoballot: authentication key type mismatch")]
+    AuthKeyTypeMismatch,
+
+    #[error("cryptoballot: schnorr blind signing requires a signer commitment and nonce")]
+    MissingSchnorrCommitment,
+
+    #[error("cryptoballot: voter {0} already has an open schnorr signing session")]
+    SchnorrSessionOpen(String),
+
+    #[error("cryptoballot: error generating blind signature")]
+    BlindSignatureError,
+
     #[error("{0}")]
     ValidationError(#[from] ValidationError),
 
     #[error("{0}")]
     TransactionNotFound(#[from] TransactionNotFound),
 
+    #[error("{0}")]
+    Store(StoreError),
+
     #[error("cryptoballot: unexpected transaction type")]
     UnexpectedTransactionType,
 
     #[error("cryptoballot: cannot find contest {0}")]
     CannotFindContet(u32),
 
+    #[error("cryptoballot: selection {0} is not a valid selection for contest {1}")]
+    InvalidSelection(String, u32),
+
+    #[error("cryptoballot: contest {0} cannot be tallied homomorphically")]
+    HomomorphicNotSupported(u32),
+
+    #[error("{0}")]
+    SpoiledBallot(#[from] SpoiledBallotError),
+
     #[error("cryptoballot: could not encode vote selection: {0}")]
     VoteEncodingError(#[from] prost::EncodeError),
+
+    #[error("cryptoballot: unsupported election archive version {0}")]
+    ArchiveVersion(u32),
+
+    #[error("cryptoballot: election archive does not match its manifest")]
+    ArchiveManifestMismatch,
+
+    #[error("cryptoballot: transaction {0} is out of place in the election archive")]
+    ArchiveTransactionOutOfPlace(Identifier),
 }
 
 /// Transaction Validation errors
@@ -81,6 +126,54 @@ pub enum ValidationError {
     #[error("cryptoballot validation: threshold is invalid for number of authenticators")]
     InvalidAuthThreshold,
 
+    #[error("cryptoballot validation: contest {0} cannot be tallied for its number of winners")]
+    UnsupportedContestType(u32),
+
+    #[error("cryptoballot validation: election must have at least one trustee")]
+    NoTrustees,
+
+    #[error("cryptoballot validation: election cannot have more than 255 trustees")]
+    TooManyTrustees,
+
+    #[error("cryptoballot validation: trustee index must be non-zero")]
+    TrusteeIndexZero,
+
+    #[error("cryptoballot validation: duplicate trustee index {0}")]
+    DuplicateTrusteeIndex(u8),
+
+    #[error("cryptoballot validation: duplicate ballot id {0}")]
+    DuplicateBallotId(String),
+
+    #[error("cryptoballot validation: duplicate contest id {0}")]
+    DuplicateContestId(String),
+
+    #[error("cryptoballot validation: duplicate contest index {0}")]
+    DuplicateContestIndex(u32),
+
+    #[error("cryptoballot validation: ballot {0} contains contest {1} which does not exist")]
+    BallotContestDoesNotExist(String, u32),
+
+    #[error("cryptoballot validation: contest {0} is not on any ballot")]
+    ContestNotOnBallot(u32),
+
+    #[error("cryptoballot validation: contest {0} has duplicate candidate id {1}")]
+    DuplicateCandidateId(u32, String),
+
+    #[error("cryptoballot validation: mix config timeout_secs must be non-zero")]
+    MixConfigZeroTimeout,
+
+    #[error("cryptoballot validation: mix config batch_size must be non-zero")]
+    MixConfigZeroBatchSize,
+
+    #[error("cryptoballot validation: authenticator {0} is missing a key for ballot {1}")]
+    AuthenticatorMissingBallotKey(uuid::Uuid, String),
+
+    #[error("cryptoballot validation: authenticator {0} has a key for unknown ballot {1}")]
+    AuthenticatorUnknownBallot(uuid::Uuid, String),
+
+    #[error("cryptoballot validation: authenticator {0} has a key for ballot {1} that does not match its key_type")]
+    AuthenticatorKeyTypeMismatch(uuid::Uuid, String),
+
     #[error("cryptoballot validation: invalid public key")]
     InvalidPublicKey,
 
@@ -116,6 +209,12 @@ pub enum ValidationError {
     #[error("cryptoballot validation: authentication failed")]
     AuthFailed,
 
+    #[error("cryptoballot validation: more than one authentication from authenticator {0}")]
+    DuplicateAuthentication(uuid::Uuid),
+
+    #[error("cryptoballot validation: not enough authentications: need {0}, found {1}")]
+    NotEnoughAuthentications(usize, usize),
+
     #[error("cryptoballot: encryption_key transaction not does yet exist")]
     EncryptionKeyTransactionDoesNotExist,
 
@@ -137,6 +236,9 @@ pub enum ValidationError {
     #[error("cryptoballot: transaction not found: {0}")]
     TransactionNotFound(#[from] TransactionNotFound),
 
+    #[error("{0}")]
+    Store(StoreError),
+
     #[error("cryptoballot: Missing voting_end transaction")]
     MisingVotingEndTransaction,
 
@@ -155,6 +257,15 @@ pub enum ValidationError {
     #[error("cryptoballot: voting has ended")]
     VotingHasEnded,
 
+    #[error("cryptoballot: voting has not started")]
+    VotingNotStarted,
+
+    #[error("cryptoballot validation: voting_start must be before voting_end")]
+    InvalidVotingWindow,
+
+    #[error("cryptoballot validation: voting_end has not been reached, only the election authority may end voting")]
+    VotingEndBeforeDeadline,
+
     #[error("cryptoballot: shuffle verification failed")]
     ShuffleVerificationFailed,
 
@@ -176,9 +287,24 @@ pub enum ValidationError {
     #[error("cryptoballot: wrong number of votes in mix")]
     MixWrongNumberOfVotes,
 
+    #[error("cryptoballot: mix batch {0} does not exist")]
+    MixBatchDoesNotExist(u32),
+
     #[error("cryptoballot: not all votes accounted for in mix")]
     MixVotesNotAccountedFor,
 
+    #[error("cryptoballot: mix {0} has already been produced by trustee {1}")]
+    MixSlotTaken(u8, u8),
+
+    #[error("cryptoballot: trustee {0} has already mixed earlier in this mix chain")]
+    TrusteeAlreadyMixed(u8),
+
+    #[error("cryptoballot: mix timestamp is earlier than the previous mix or voting_end")]
+    MixTimestampBeforePrevious,
+
+    #[error("cryptoballot: timestamp {0} is in the future")]
+    TimestampInFuture(u64),
+
     #[error("cryptoballot: invalid upstream transaction ID")]
     InvalidUpstreamID,
 
@@ -194,11 +320,160 @@ pub enum ValidationError {
     #[error("cryptoballot: voted in wrong contest")]
     VotedInWrongContest,
 
+    #[error("cryptoballot validation: voted more than once in contest {0}")]
+    DuplicateContestVote(u32),
+
     #[error("cryptoballot: vote anonymous_key collides with existing vote")]
     VoteAnonymousKeyCollision,
 
+    #[error("cryptoballot validation: replacement vote has no previous vote to replace")]
+    ReplacedVoteDoesNotExist,
+
+    #[error("cryptoballot validation: replacement vote revision {0} does not follow the latest revision")]
+    VoteRevisionOutOfOrder(u16),
+
+    #[error("cryptoballot validation: replacement vote must be for the same ballot as the vote it replaces")]
+    ReplacementVoteWrongBallot,
+
+    #[error("cryptoballot validation: vote has been replaced and cannot be decrypted")]
+    VoteReplaced,
+
     #[error("cryptoballot: could not decode vote selection: {0}")]
     VoteDecodingError(#[from] prost::DecodeError),
+
+    #[error("cryptoballot: challenged ballot does not match its commitment")]
+    BenalohCommitmentMismatch,
+
+    #[error("cryptoballot: challenged ballot is not an encryption of the revealed plaintext")]
+    BenalohChallengeFailed,
+
+    #[error("cryptoballot validation: vote for contest {0} has more selections than candidates")]
+    TooManySelections(u32),
+
+    #[error("cryptoballot validation: vote for contest {0} is missing well-formedness proofs")]
+    SelectionProofMissing(u32),
+
+    #[error("cryptoballot validation: vote for contest {0} has an invalid well-formedness proof")]
+    SelectionProofFailed(u32),
+
+    #[error("cryptoballot validation: contest {0} has a max_score above MAX_SCORE")]
+    MaxScoreTooHigh(u32),
+
+    #[error("cryptoballot validation: contest {0} cannot be tallied homomorphically")]
+    UnsupportedHomomorphicContest(u32),
+
+    #[error("cryptoballot validation: vote for homomorphic contest {0} must have one counter per candidate")]
+    WrongNumberOfCounters(u32),
+
+    #[error("cryptoballot validation: contest {0} is tallied homomorphically and cannot be mixed")]
+    HomomorphicContestMixed(u32),
+
+    #[error("cryptoballot: could not recover the totals for homomorphic contest {0}")]
+    HomomorphicTotalOutOfRange(u32),
+
+    #[error("cryptoballot: contest {0} is missing decryptions: expected {1}, found {2}")]
+    TallyMissingDecryptions(u32, usize, usize),
+
+    #[error("cryptoballot: tally has the wrong number of contests")]
+    TallyWrongNumberOfContests,
+
+    #[error("cryptoballot: tally mismatch for contest {0}")]
+    TallyMismatch(u32),
+
+    #[error(
+        "cryptoballot validation: transaction {0} has already been posted to the bulletin board"
+    )]
+    AlreadyPosted(Identifier),
+
+    #[error("cryptoballot: inclusion proof for transaction {0} failed to verify")]
+    InclusionProofFailed(Identifier),
+
+    #[error("cryptoballot: consistency proof for election {0} failed to verify")]
+    ConsistencyProofFailed(Identifier),
+
+    #[error("cryptoballot: receipt does not match vote {0}")]
+    ReceiptMismatch(Identifier),
+
+    #[error("cryptoballot: vote {0} has not been counted for contest {1}")]
+    ReceiptVoteNotCounted(Identifier, u32),
+
+    #[error("cryptoballot validation: reshare {0} does not exist")]
+    ReshareDoesNotExist(u8),
+
+    #[error("cryptoballot validation: reshare {0} is not the latest reshare")]
+    ReshareOutOfOrder(u8),
+
+    #[error("cryptoballot validation: trustees cannot be reshared once mixing or decryption has started")]
+    ReshareAfterDecryptionStarted,
+
+    #[error("cryptoballot validation: reshare dealers must be distinct current trustees, as many as the current threshold")]
+    InvalidReshareDealers,
+
+    #[error("cryptoballot validation: reshare must have one x25519 public key per new trustee")]
+    ReshareX25519KeysMismatch,
+
+    #[error("cryptoballot validation: trustee {0} is not a dealer in this reshare")]
+    TrusteeNotDealer(u8),
+
+    #[error("cryptoballot validation: reshare commitment from trustee {0} has the wrong number of coefficients")]
+    ReshareCommitmentInvalid(u8),
+
+    #[error("cryptoballot validation: missing reshare_share transaction from trustee {0}")]
+    MissingReshareShare(u8),
+
+    #[error("cryptoballot validation: reshared key does not match the election's encryption key")]
+    ReshareChangesEncryptionKey,
+
+    #[error(
+        "cryptoballot validation: reshare commitment from trustee {0} does not match its key share"
+    )]
+    ReshareDealerKeyMismatch(u8),
+
+    #[error("cryptoballot validation: verification key for trustee {0} does not match the reshare commitments")]
+    VerificationKeyMismatch(u8),
+
+    #[error("cryptoballot: reshare share from trustee {0} does not match its commitment")]
+    ReshareShareInvalid(u8),
+
+    #[error("cryptoballot validation: invalid keygen commitment from trustee {0}")]
+    InvalidKeyGenCommitment(u8),
+
+    #[error("cryptoballot: keygen share from trustee {0} does not match its commitment")]
+    InvalidKeyGenShare(u8),
+
+    #[error("cryptoballot: key generation is missing commitments or shares")]
+    KeyGenIncomplete,
+
+    #[error("cryptoballot validation: a trustee cannot complain about its own share")]
+    KeyGenComplaintAgainstSelf,
+
+    #[error(
+        "cryptoballot validation: complaints must be made before the encryption_key transaction"
+    )]
+    KeyGenComplaintTooLate,
+
+    #[error("cryptoballot validation: invalid shared secret proof")]
+    InvalidSharedSecretProof,
+
+    #[error("cryptoballot validation: complaint rejected, the share from trustee {0} is valid")]
+    KeyGenShareValid(u8),
+
+    #[error("cryptoballot validation: trustee {0} has been disqualified")]
+    TrusteeDisqualified(u8),
+
+    #[error("cryptoballot validation: public key from trustee {0} lists trustees that have not been disqualified")]
+    InvalidDisqualifiedTrustees(u8),
+
+    #[error("cryptoballot validation: not enough qualified trustees: need {0}, found {1}")]
+    NotEnoughQualifiedTrustees(usize, usize),
+
+    #[error(
+        "cryptoballot validation: x25519 public key from trustee {0} is not a valid curve point"
+    )]
+    InvalidX25519PublicKey(u8),
+
+    #[error("cryptoballot validation: public key from trustee {0} does not match the keygen commitments")]
+    KeyGenPublicKeyMismatch(u8),
 }
 
 /// SpoiledBallotError represent the various ways a ballot can be spoiled
@@ -222,9 +497,31 @@ pub enum SpoiledBallotError {
     #[error("cryptoballot: spoiled ballot: sum of candidate scores over limit")]
     ScoreOverLimit,
 
+    #[error("cryptoballot: spoiled ballot: candidate score above the contest's maximum score")]
+    ScoreOutOfRange,
+
     #[error("cryptoballot: spoiled ballot: write-in not allowed")]
     WriteInNotAllowed,
 
     #[error("cryptoballot: spoiled ballot: candidate ID not found in this contest")]
     CandidateNotFound,
 }
+
+// Missing transactions keep their own variant, so callers can tell them apart from backend failures
+impl From<StoreError> for Error {
+    fn from(err: StoreError) -> Self {
+        match err {
+            StoreError::NotFound(err) => Error::TransactionNotFound(err),
+            err => Error::Store(err),
+        }
+    }
+}
+
+impl From<StoreError> for ValidationError {
+    fn from(err: StoreError) -> Self {
+        match err {
+            StoreError::NotFound(err) => ValidationError::TransactionNotFound(err),
+            err => ValidationError::Store(err),
+        }
+    }
+}
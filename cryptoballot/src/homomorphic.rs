@@ -0,0 +1,335 @@
+use crate::reshare::{identity, lagrange_coefficient};
+use crate::wellformed::{
+    encrypt_point, prove_membership, to_cryptid, verify_membership, ProofContext,
+};
+use crate::*;
+use cryptid::curve::CurveElem;
+use cryptid::elgamal::{Ciphertext, PublicKey as EncryptionPublicKey};
+use cryptid::threshold::DecryptShare;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::PublicKey;
+use rand::{CryptoRng, RngCore};
+use std::collections::HashMap;
+
+/// Encrypt a vote for a homomorphic contest as exponential-ElGamal counters.
+///
+/// The vote has one ciphertext for every candidate, in the order the candidates are listed in the contest. Each ciphertext
+/// encrypts `m⋅G`, where `m` is the candidate's score for Score contests, or `1` if the candidate was selected and `0` otherwise.
+///
+/// Every counter carries a proof that it is between zero and `Contest::max_counter`. Plurality votes also carry a `limit_proof`
+/// that the sum of the counters is at most `num_winners`.
+pub fn encrypt_homomorphic_vote<R: CryptoRng + RngCore>(
+    encryption_key: &EncryptionPublicKey,
+    election_id: Identifier,
+    anonymous_key: &PublicKey,
+    contest: &Contest,
+    vote: Vec<Selection>,
+    rng: &mut R,
+) -> Result<EncryptedVote, Error> {
+    let max_counter = contest
+        .max_counter()
+        .ok_or(Error::HomomorphicNotSupported(contest.index))?;
+
+    contest.check_selections(&vote)?;
+
+    // Work out the counter for each candidate
+    let mut counters = vec![0; contest.candidates.len()];
+    for selection in &vote {
+        let candidate = contest
+            .candidates
+            .iter()
+            .position(|candidate| candidate.id == selection.selection)
+            .ok_or_else(|| Error::InvalidSelection(selection.selection.clone(), contest.index))?;
+
+        let counter = match contest.contest_type {
+            ContestType::Score => selection.score,
+            _ => 1,
+        };
+        if counter > max_counter {
+            return Err(Error::InvalidSelection(
+                selection.selection.clone(),
+                contest.index,
+            ));
+        }
+        counters[candidate] = counter;
+    }
+
+    let context = ProofContext {
+        election_id,
+        anonymous_key: *anonymous_key,
+        contest_index: contest.index,
+    };
+    let messages = counter_messages(max_counter);
+
+    let mut selections = Vec::with_capacity(counters.len());
+    let mut proofs = Vec::with_capacity(counters.len());
+    let mut randomness_sum = Scalar::zero();
+    for (position, counter) in counters.iter().enumerate() {
+        let randomness = Scalar::random(rng);
+        let ciphertext = encrypt_point(encryption_key, &messages[*counter as usize], &randomness);
+        let proof = prove_membership(
+            encryption_key,
+            &context,
+            position,
+            &ciphertext,
+            &randomness,
+            &messages,
+            *counter as usize,
+            rng,
+        )?;
+
+        randomness_sum += randomness;
+        selections.push(ciphertext);
+        proofs.push(proof);
+    }
+
+    // Plurality voters may select at most num_winners candidates
+    let limit_proof = match contest.contest_type {
+        ContestType::Plurality => {
+            let total: u32 = counters.iter().sum();
+            let sum = sum_ciphertexts(&selections);
+            let limit_messages = counter_messages(contest.num_winners);
+            Some(prove_membership(
+                encryption_key,
+                &context,
+                counters.len(),
+                &sum,
+                &randomness_sum,
+                &limit_messages,
+                total as usize,
+                rng,
+            )?)
+        }
+        _ => None,
+    };
+
+    Ok(EncryptedVote {
+        contest_index: contest.index,
+        selections,
+        proofs,
+        limit_proof,
+    })
+}
+
+/// Verify that an encrypted vote for a homomorphic contest contains a well-formed counter for every candidate.
+pub fn verify_homomorphic_vote(
+    encryption_key: &EncryptionPublicKey,
+    election_id: Identifier,
+    anonymous_key: &PublicKey,
+    contest: &Contest,
+    encrypted_vote: &EncryptedVote,
+) -> Result<(), ValidationError> {
+    let contest_index = contest.index;
+    let max_counter =
+        contest
+            .max_counter()
+            .ok_or(ValidationError::UnsupportedHomomorphicContest(
+                contest_index,
+            ))?;
+
+    if encrypted_vote.selections.len() != contest.candidates.len() {
+        return Err(ValidationError::WrongNumberOfCounters(contest_index));
+    }
+    if encrypted_vote.proofs.len() != encrypted_vote.selections.len() {
+        return Err(ValidationError::SelectionProofMissing(contest_index));
+    }
+
+    let context = ProofContext {
+        election_id,
+        anonymous_key: *anonymous_key,
+        contest_index,
+    };
+    let messages = counter_messages(max_counter);
+
+    for (position, (ciphertext, proof)) in encrypted_vote
+        .selections
+        .iter()
+        .zip(encrypted_vote.proofs.iter())
+        .enumerate()
+    {
+        if !verify_membership(
+            encryption_key,
+            &context,
+            position,
+            ciphertext,
+            &messages,
+            proof,
+        ) {
+            return Err(ValidationError::SelectionProofFailed(contest_index));
+        }
+    }
+
+    if let ContestType::Plurality = contest.contest_type {
+        let limit_proof = encrypted_vote
+            .limit_proof
+            .as_ref()
+            .ok_or(ValidationError::SelectionProofMissing(contest_index))?;
+        let sum = sum_ciphertexts(&encrypted_vote.selections);
+        let limit_messages = counter_messages(contest.num_winners);
+
+        if !verify_membership(
+            encryption_key,
+            &context,
+            encrypted_vote.selections.len(),
+            &sum,
+            &limit_messages,
+            limit_proof,
+        ) {
+            return Err(ValidationError::SelectionProofFailed(contest_index));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sum the counters of every vote cast in a homomorphic contest, ignoring votes that have been replaced.
+///
+/// Returns one ciphertext per candidate, encrypting that candidate's total, and the number of votes that were summed.
+/// Trustees partially decrypt these sums instead of individual votes.
+pub fn homomorphic_aggregate<S: Store>(
+    store: &S,
+    election_id: Identifier,
+    contest: &Contest,
+) -> Result<(Vec<Ciphertext>, usize), StoreError> {
+    let mut totals = vec![zero_ciphertext(); contest.candidates.len()];
+    let mut num_votes = 0;
+
+    for vote in latest_votes(store, election_id) {
+        for encrypted_vote in vote?.encrypted_votes {
+            if encrypted_vote.contest_index == contest.index {
+                for (total, counter) in totals.iter_mut().zip(encrypted_vote.selections.iter()) {
+                    *total = add_ciphertexts(total, counter);
+                }
+                num_votes += 1;
+            }
+        }
+    }
+
+    Ok((totals, num_votes))
+}
+
+/// Decrypt the totals of a homomorphic contest from the given partial decryptions.
+///
+/// Returns one selection per candidate, whose score is the candidate's total.
+///
+/// Each trustee's share is checked once, and the shares of the first `trustees_threshold` trustees are combined to
+/// recover `total⋅G`. The total is then found by stepping through every value up to the largest possible total.
+pub fn decrypt_totals(
+    contest: &Contest,
+    ciphertexts: &[Ciphertext],
+    num_votes: usize,
+    trustees_threshold: u8,
+    trustees: &[Trustee],
+    pubkeys: &[KeyGenPublicKeyTransaction],
+    partials: &[PartialDecryptionTransaction],
+) -> Result<Vec<Selection>, ValidationError> {
+    let max_counter =
+        contest
+            .max_counter()
+            .ok_or(ValidationError::UnsupportedHomomorphicContest(
+                contest.index,
+            ))?;
+    let max_total = num_votes as u64 * max_counter as u64;
+
+    // Map pubkeys by trustee index
+    let pubkeys: HashMap<u8, &KeyGenPublicKeyTransaction> =
+        pubkeys.iter().map(|tx| (tx.trustee_index, tx)).collect();
+
+    // Map partials by trustee index
+    let partials: HashMap<u8, &PartialDecryptionTransaction> =
+        partials.iter().map(|tx| (tx.trustee_index, tx)).collect();
+
+    // Use the first quorum of trustees that have posted a partial decryption
+    let quorum: Vec<(&PartialDecryptionTransaction, &KeyGenPublicKeyTransaction)> = trustees
+        .iter()
+        .filter_map(|trustee| {
+            Some((
+                *partials.get(&trustee.index)?,
+                *pubkeys.get(&trustee.index)?,
+            ))
+        })
+        .take(trustees_threshold as usize)
+        .collect();
+    if quorum.len() < trustees_threshold as usize {
+        return Err(ValidationError::NotEnoughShares(
+            trustees_threshold as usize,
+            quorum.len(),
+        ));
+    }
+    let indexes: Vec<u8> = quorum
+        .iter()
+        .map(|(partial, _)| partial.trustee_index)
+        .collect();
+
+    let mut results = Vec::with_capacity(ciphertexts.len());
+    for (i, (ciphertext, candidate)) in ciphertexts.iter().zip(&contest.candidates).enumerate() {
+        // The message is c2 minus the interpolated shares, which is total⋅G
+        let mut message = ciphertext.c2;
+        for (partial, pubkey) in &quorum {
+            let share = partial
+                .partial_decryption
+                .get(i)
+                .ok_or(ValidationError::PartialDecryptionProofFailed)?;
+            if !share.verify(&pubkey.public_key_proof, ciphertext) {
+                return Err(ValidationError::PartialDecryptionProofFailed);
+            }
+
+            let coefficient = lagrange_coefficient(partial.trustee_index, &indexes);
+            message = message - share.share().scaled(&to_cryptid(&coefficient));
+        }
+
+        let total = find_total(message, max_total)
+            .ok_or(ValidationError::HomomorphicTotalOutOfRange(contest.index))?;
+        results.push(Selection {
+            write_in: false,
+            score: total as u32,
+            selection: candidate.id.clone(),
+        });
+    }
+
+    Ok(results)
+}
+
+/// Find the total that a decrypted homomorphic message `total⋅G` encodes, trying every total from zero to `max_total`.
+pub(crate) fn find_total(message: CurveElem, max_total: u64) -> Option<u64> {
+    let mut guess = identity();
+    for total in 0..=max_total {
+        if guess == message {
+            return Some(total);
+        }
+        guess = guess + CurveElem::generator();
+    }
+
+    None
+}
+
+// The messages m⋅G for every counter value m from zero to max
+fn counter_messages(max: u32) -> Vec<CurveElem> {
+    (0..=max as u64)
+        .map(|m| CurveElem::generator().scaled(&to_cryptid(&Scalar::from(m))))
+        .collect()
+}
+
+// Ciphertexts are added component-wise, adding their plaintexts
+fn add_ciphertexts(a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+    Ciphertext {
+        c1: a.c1 + b.c1,
+        c2: a.c2 + b.c2,
+    }
+}
+
+fn sum_ciphertexts(ciphertexts: &[Ciphertext]) -> Ciphertext {
+    ciphertexts
+        .iter()
+        .fold(zero_ciphertext(), |sum, ciphertext| {
+            add_ciphertexts(&sum, ciphertext)
+        })
+}
+
+// An encryption of zero with zero randomness: both components are the identity
+fn zero_ciphertext() -> Ciphertext {
+    Ciphertext {
+        c1: identity(),
+        c2: identity(),
+    }
+}
@@ -1,5 +1,9 @@
+use crate::complaint::cofactor_point;
+use crate::reshare::{evaluate_commitment, identity};
 use crate::*;
-use cryptid::threshold::KeygenCommitment;
+use cryptid::curve::CurveElem;
+use cryptid::threshold::{KeygenCommitment, PubkeyProof};
+use curve25519_dalek::traits::IsIdentity;
 use ed25519_dalek::PublicKey;
 use indexmap::IndexMap;
 use x25519_dalek as x25519;
@@ -41,6 +45,14 @@ pub struct KeyGenPublicKeyTransaction {
     pub trustee_public_key: PublicKey,
     pub public_key: cryptid::elgamal::PublicKey,
     pub public_key_proof: cryptid::threshold::PubkeyProof,
+
+    /// Trustees disqualified by complaints when this public key was generated, in ascending order.
+    ///
+    /// A complaint can disqualify a trustee after others have posted their public keys, each trustee
+    /// then posts a new public key without it. Only public keys listing every disqualified trustee
+    /// make up the election key (see `qualified_public_keys`).
+    #[serde(default, skip_serializing_if = "crate::util::is_default")]
+    pub disqualified: Vec<u8>,
 }
 
 /// Transaction 5: EncryptionKey
@@ -108,6 +120,8 @@ impl CryptoBallotTransaction for KeyGenCommitmentTransaction {
     ///
     /// The validation does the following:
     ///  - Validates that this transaction has been signed by a valid trustee
+    ///  - Validates that the x25519 public key is a curve point outside the small subgroup
+    ///  - Validates that the commitment has one group element per coefficient for the election's threshold
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         let election = store.get_election(self.election)?;
 
@@ -123,7 +137,15 @@ impl CryptoBallotTransaction for KeyGenCommitmentTransaction {
             return Err(ValidationError::TrusteeDoesNotExist(self.trustee_index));
         }
 
-        // TODO: Validate the commitment?
+        // A key that isn't a point, or is in the small subgroup, gives shared secrets an attacker can guess
+        match cofactor_point(&self.x25519_public_key) {
+            Some(point) if !point.is_identity() => {}
+            _ => return Err(ValidationError::InvalidX25519PublicKey(self.trustee_index)),
+        }
+
+        if !commitment_is_valid(election.trustees_threshold, &self.commitment) {
+            return Err(ValidationError::InvalidKeyGenCommitment(self.trustee_index));
+        }
 
         Ok(())
     }
@@ -218,20 +240,28 @@ impl KeyGenPublicKeyTransaction {
         trustee_public_key: PublicKey,
         public_key: cryptid::elgamal::PublicKey,
         public_key_proof: cryptid::threshold::PubkeyProof,
+        disqualified: Vec<u8>,
     ) -> Self {
         KeyGenPublicKeyTransaction {
-            id: Self::build_id(election_id, trustee_index),
+            id: Self::build_id(election_id, trustee_index, disqualified.len() as u8),
             election: election_id,
             trustee_index,
             trustee_public_key,
             public_key,
             public_key_proof,
+            disqualified,
         }
     }
 
-    pub fn build_id(election_id: Identifier, trustee_index: u8) -> Identifier {
+    /// A trustee posts at most one public key for each number of disqualified trustees
+    pub fn build_id(
+        election_id: Identifier,
+        trustee_index: u8,
+        num_disqualified: u8,
+    ) -> Identifier {
         let mut unique_info = [0; 16];
         unique_info[0] = trustee_index;
+        unique_info[1] = num_disqualified;
         Identifier::new(
             election_id,
             TransactionType::KeyGenPublicKey,
@@ -265,9 +295,21 @@ impl CryptoBallotTransaction for KeyGenPublicKeyTransaction {
     ///
     /// The validation does the following:
     ///  - Validates that this transaction has been signed by a valid trustee
+    ///  - Validates that the trustee is not among the disqualified trustees it lists
+    ///  - Validates that every trustee it lists has been disqualified by a complaint
+    ///  - Validates the public key and public key proof against the commitments of the trustees it does not list
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         let election = store.get_election(self.election)?;
 
+        if Self::build_id(
+            self.election,
+            self.trustee_index,
+            self.disqualified.len() as u8,
+        ) != self.id
+        {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
         // Validate that this trustee exists
         let mut trustee_exists = false;
         for trustee in &election.trustees {
@@ -280,6 +322,49 @@ impl CryptoBallotTransaction for KeyGenPublicKeyTransaction {
             return Err(ValidationError::TrusteeDoesNotExist(self.trustee_index));
         }
 
+        if self.disqualified.contains(&self.trustee_index) {
+            return Err(ValidationError::TrusteeDisqualified(self.trustee_index));
+        }
+
+        // A public key listing fewer trustees than have since been disqualified is still valid, it is
+        // just not used for the election key. Checking against the complaints in the store rather than
+        // requiring the full list keeps keys posted before a late complaint valid on archive replay.
+        let disqualified = disqualified_trustees(store, self.election)?;
+        if self.disqualified.windows(2).any(|pair| pair[0] >= pair[1])
+            || self
+                .disqualified
+                .iter()
+                .any(|index| !disqualified.contains(index))
+        {
+            return Err(ValidationError::InvalidDisqualifiedTrustees(
+                self.trustee_index,
+            ));
+        }
+
+        // Every trustee the public key was generated from must have posted their commitment
+        let mut commitments = Vec::with_capacity(election.trustees.len());
+        for trustee in election
+            .trustees
+            .iter()
+            .filter(|trustee| !self.disqualified.contains(&trustee.index))
+        {
+            let commitment_id = KeyGenCommitmentTransaction::build_id(self.election, trustee.index);
+            let commitment: KeyGenCommitmentTransaction = store
+                .get_transaction(commitment_id)?
+                .ok_or(ValidationError::TrusteeMissing(trustee.index))?
+                .into();
+            commitments.push(commitment.commitment);
+        }
+
+        if !public_key_is_consistent(
+            self.trustee_index,
+            &self.public_key,
+            &self.public_key_proof,
+            &commitments,
+        ) {
+            return Err(ValidationError::KeyGenPublicKeyMismatch(self.trustee_index));
+        }
+
         Ok(())
     }
 }
@@ -328,7 +413,9 @@ impl CryptoBallotTransaction for EncryptionKeyTransaction {
     /// Validate the transaction
     ///
     /// The validation does the following:
-    ///  - Validates that this transaction has been signed by a valid trustee
+    ///  - Validates that this transaction has been signed by the election authority
+    ///  - Validates that enough trustees are still qualified to meet the threshold
+    ///  - Validates that every qualified trustee has posted the same public key, generated without the disqualified trustees
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         let election = store.get_election(self.election)?;
 
@@ -337,43 +424,56 @@ impl CryptoBallotTransaction for EncryptionKeyTransaction {
             return Err(ValidationError::AuthorityPublicKeyMismatch);
         }
 
-        // Get all keygen_public_key transactions
-        let pk_txs = store.get_multiple(self.election, TransactionType::KeyGenPublicKey);
-        let pk_txs: Vec<Signed<KeyGenPublicKeyTransaction>> =
-            pk_txs.into_iter().map(|tx| tx.into()).collect();
-
-        // Validate that the number of public key transactions match
-        if pk_txs.len() != election.trustees.len() {
-            return Err(ValidationError::WrongNumberOfPublicKeyTransactions);
+        // Trustees disqualified during key generation have no part in the key
+        let trustees = qualified_trustees(store, &election)?;
+        if trustees.len() < election.trustees_threshold as usize {
+            return Err(ValidationError::NotEnoughQualifiedTrustees(
+                election.trustees_threshold as usize,
+                trustees.len(),
+            ));
         }
 
-        // Validate that all trustees have a transaction
-        for trustee in &election.trustees {
-            let mut has_tx = false;
-            for tx in &pk_txs {
-                if tx.inner().trustee_index == trustee.index
-                    && tx.inner().trustee_public_key == trustee.public_key
-                {
-                    has_tx = true;
-                    break;
-                }
-            }
-            if !has_tx {
-                return Err(ValidationError::MissingKeyGenPublicKeyTransaction(
+        // Public keys posted before a later complaint are superseded, so only the current ones count
+        let pk_txs = qualified_public_keys(store, self.election)?;
+
+        // Validate that all trustees have a transaction, and that all the encryption keys match
+        for trustee in &trustees {
+            let tx = pk_txs
+                .iter()
+                .find(|tx| {
+                    tx.trustee_index == trustee.index && tx.trustee_public_key == trustee.public_key
+                })
+                .ok_or(ValidationError::MissingKeyGenPublicKeyTransaction(
                     trustee.index,
-                ));
-            }
-        }
-
-        // Validate that all the encryption keys match
-        for tx in &pk_txs {
-            if tx.inner().public_key != self.encryption_key {
-                return Err(ValidationError::MismatchedEncryptionKey(
-                    tx.inner().trustee_index,
-                ));
+                ))?;
+            if tx.public_key != self.encryption_key {
+                return Err(ValidationError::MismatchedEncryptionKey(tx.trustee_index));
             }
         }
 
         Ok(())
     }
 }
+
+// A commitment is to the coefficients of a polynomial of degree `threshold - 1`, none of which may be zero
+fn commitment_is_valid(threshold: u8, commitment: &KeygenCommitment) -> bool {
+    commitment.elems().len() == threshold as usize
+        && commitment.elems().iter().all(|elem| *elem != identity())
+}
+
+// The election key is the sum of the qualified trustees' constant terms, and each trustee's verification key is the sum
+// of their polynomials at the trustee's index
+fn public_key_is_consistent(
+    index: u8,
+    public_key: &cryptid::elgamal::PublicKey,
+    public_key_proof: &PubkeyProof,
+    commitments: &[KeygenCommitment],
+) -> bool {
+    let sum = |x: u8| -> CurveElem {
+        commitments.iter().fold(identity(), |sum, commitment| {
+            sum + evaluate_commitment(commitment.elems(), x)
+        })
+    };
+
+    public_key.y == sum(0) && *public_key_proof.h_i() == sum(index)
+}
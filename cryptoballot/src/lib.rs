@@ -15,11 +15,18 @@
 //!  - **Transaction 8: Mix Transaction** - Shuffled and mixed vote for a single contest, created by a trustee.
 //!  - **Transaction 9: PartialDecryption Transaction** - A partially decrypted vote from a trustee.
 //!  - **Transaction 10: Decryption Transaction** - A fully decrypted vote .
+//!  - **Transaction 11: Tally Transaction** - The verifiable results of the election, produced after all votes are decrypted.
+//!  - **Transaction 12: TrusteeReshare Transaction** - Replaces the election's trustees without changing its encryption key, created by the election authority.
+//!  - **Transaction 13: ReshareShare Transaction** - A current trustee's share of the election key, reshared to the new trustees.
+//!  - **Transaction 14: ResharePublicKey Transaction** - A new trustee's verification key, posted once it has received its shares.
+//!  - **Transaction 15: KeyGenComplaint Transaction** - A trustee's proof that another trustee sent it an invalid share during key generation, disqualifying them.
 //!  - **Election Authority** - Creates an Election Transaction.
 //!  - **Trustee** - A group of trustees collectively create the encryption-key, decrypt votes, and run the mixnet. Generally ⅔ of trustees are required to be honest for the CryptoBallot protocol to function.
 //!  - **Authenticator** - Certifies that a voter can vote an election and ballot.
 //!  - **Contest** - A single question that voters are voting on.
 //!  - **Ballot** - A set of contests, usually restricted to a geographic area. A single contest can exist across multiple ballots.
+//!  - **Bulletin Board** - The append-only public record of an election's transactions, committed to a merkle tree so that anyone can prove a transaction is part of it.
+//!  - **Receipt** - Given to a voter when their vote is built. Its tracking code lets the voter check that their vote was recorded unchanged and counted.
 
 #![feature(is_sorted)]
 
@@ -34,36 +41,62 @@ pub extern crate rsa;
 pub extern crate uuid;
 pub extern crate x25519_dalek;
 
+mod archive;
 mod authn;
 mod ballot;
+mod benaloh;
+mod bulk;
+mod bulletin;
+mod complaint;
 mod decryption;
 mod election;
 mod error;
+mod homomorphic;
 mod keygen;
 mod mix;
+mod receipt;
+mod reshare;
 mod serde_hex;
+mod simulation;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 mod store;
+mod stv;
 mod tally;
 mod transaction;
 mod trustee;
 mod util;
 mod vote;
 mod voting_end;
+mod wellformed;
 
+pub use archive::*;
 pub use authn::*;
 pub use ballot::*;
+pub use benaloh::*;
+pub use bulk::*;
+pub use bulletin::*;
+pub use complaint::*;
 pub use decryption::*;
 pub use election::*;
 pub use error::*;
+pub use homomorphic::*;
 pub use keygen::*;
 pub use mix::*;
+pub use receipt::*;
+pub use reshare::*;
+pub use simulation::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite::*;
 pub use store::*;
+pub use stv::*;
 pub use tally::*;
 pub use transaction::*;
 pub use trustee::*;
 pub use util::*;
 pub use vote::*;
 pub use voting_end::*;
+pub use wellformed::*;
 
 pub(crate) use serde_hex::*;
 
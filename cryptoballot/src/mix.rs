@@ -13,6 +13,93 @@ pub struct MixConfig {
     pub batch_size: Option<u16>,
 }
 
+impl MixConfig {
+    /// Partition the votes for a contest into mix batches.
+    ///
+    /// The latest revision of each voter's vote that contains the contest is taken in ascending vote-id order and split
+    /// into consecutive batches of at most `batch_size` votes. Without a `batch_size`, all votes are in a single batch.
+    pub fn batch_votes<S: Store>(
+        &self,
+        store: &S,
+        election_id: Identifier,
+        contest_index: u32,
+    ) -> Result<Vec<Vec<(Identifier, Vec<Ciphertext>)>>, StoreError> {
+        let contest_votes = contest_votes(store, election_id, contest_index)
+            .collect::<Result<Vec<_>, StoreError>>()?;
+
+        let batch_size = match self.batch_size {
+            Some(batch_size) if batch_size > 0 => batch_size as usize,
+            _ => contest_votes.len().max(1),
+        };
+
+        let mut batches = Vec::with_capacity(contest_votes.len() / batch_size + 1);
+        let mut contest_votes = contest_votes.into_iter().peekable();
+        while contest_votes.peek().is_some() {
+            batches.push(contest_votes.by_ref().take(batch_size).collect());
+        }
+
+        Ok(batches)
+    }
+
+    /// A single mix batch of the votes for a contest, as partitioned by `batch_votes`, or None if there is no such batch.
+    ///
+    /// Votes are read from the store lazily, stopping at the end of the batch, and only the batch's ciphertexts are kept.
+    pub fn vote_batch<S: Store>(
+        &self,
+        store: &S,
+        election_id: Identifier,
+        contest_index: u32,
+        batch: u32,
+    ) -> Result<Option<Vec<(Identifier, Vec<Ciphertext>)>>, StoreError> {
+        let (start, end) = match self.batch_size {
+            Some(batch_size) if batch_size > 0 => {
+                let start = (batch as usize).saturating_mul(batch_size as usize);
+                (start, start.saturating_add(batch_size as usize))
+            }
+            _ if batch == 0 => (0, usize::MAX),
+            _ => return Ok(None),
+        };
+
+        let mut votes = Vec::new();
+        for (position, vote) in contest_votes(store, election_id, contest_index)
+            .enumerate()
+            .take(end)
+        {
+            let vote = vote?;
+            if position >= start {
+                votes.push(vote);
+            }
+        }
+
+        Ok(if votes.is_empty() { None } else { Some(votes) })
+    }
+
+    /// The trustees that may fill a mix slot, in order of precedence.
+    ///
+    /// Mix `n` is scheduled for the `n`th trustee (by trustee index). If a trustee fails to produce its mix
+    /// within `timeout_secs`, the slot passes to the next trustee in line, wrapping around to the first trustee.
+    /// Trustees that have already mixed earlier in the chain are skipped, since a trustee may only mix once.
+    pub fn slot_order(&self, trustees: &[Trustee], mix_index: u8, already_mixed: &[u8]) -> Vec<u8> {
+        let mut indexes: Vec<u8> = trustees.iter().map(|trustee| trustee.index).collect();
+        indexes.sort();
+
+        if !indexes.is_empty() {
+            let scheduled = mix_index as usize % indexes.len();
+            indexes.rotate_left(scheduled);
+        }
+        indexes.retain(|index| !already_mixed.contains(index));
+
+        indexes
+    }
+
+    /// The time at which the trustee at the given position in the slot order may produce the mix.
+    ///
+    /// `reference_time` is the timestamp of the previous mix, or of the VotingEnd transaction for the first mix.
+    pub fn slot_opens_at(&self, reference_time: u64, position: usize) -> u64 {
+        reference_time.saturating_add(self.timeout_secs.saturating_mul(position as u64))
+    }
+}
+
 /// Transaction 8: Mix
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MixTransaction {
@@ -36,6 +123,13 @@ pub struct MixTransaction {
     /// failed to produce a mix within the alloted timeout.
     pub mix_index: u8,
 
+    /// The time the mix was produced, in seconds since the unix epoch
+    /// The next mix slot opens to other trustees `timeout_secs` after this time.
+    /// Zero for transactions that predate timestamps.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "crate::util::is_default")]
+    pub timestamp: u64,
+
     /// The contest that this mix is for
     pub contest_index: u32,
 
@@ -60,6 +154,7 @@ impl MixTransaction {
         prev_mix_id: Option<Identifier>,
         trustee: &Trustee,
         mix_index: u8,
+        timestamp: u64,
         contest_index: u32,
         batch: u32,
         vote_ids: Vec<Identifier>,
@@ -79,6 +174,7 @@ impl MixTransaction {
             trustee_index: trustee.index,
             trustee_public_key: trustee.public_key,
             mix_index,
+            timestamp,
             contest_index,
             batch,
             vote_ids,
@@ -109,6 +205,41 @@ impl MixTransaction {
 
         Identifier::new(election_id, TransactionType::Mix, Some(unique_info))
     }
+
+    /// Get the mix that fills a mix slot, if any trustee has produced it
+    pub fn get_mix_in_slot<S: Store>(
+        store: &S,
+        election_id: Identifier,
+        contest_index: u32,
+        batch: u32,
+        mix_index: u8,
+    ) -> Result<Option<MixTransaction>, StoreError> {
+        // The first 9 bytes of the unique-info identify the slot, the 10th is the trustee
+        let mask = Self::build_id(election_id, contest_index, batch, mix_index, 0).unique_info;
+        let start = Identifier::start(election_id, TransactionType::Mix, Some(&mask[0..9]));
+        let end = Identifier::end(election_id, TransactionType::Mix, Some(&mask[0..9]));
+
+        match store.range(start, end).next() {
+            Some(tx) => Ok(Some(tx?.into())),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the trustee indexes of all mixes earlier in the chain than this one
+    pub fn previous_mixers<S: Store>(&self, store: &S) -> Result<Vec<u8>, ValidationError> {
+        let mut mixers = Vec::with_capacity(self.mix_index as usize);
+        let mut prev_mix_id = self.prev_mix_id;
+        while let Some(id) = prev_mix_id {
+            let prev_mix = store
+                .get_mix(id)
+                .map_err(|_| ValidationError::MissingPrevMixTransaction)?
+                .tx;
+            mixers.push(prev_mix.trustee_index);
+            prev_mix_id = prev_mix.prev_mix_id;
+        }
+
+        Ok(mixers)
+    }
 }
 
 impl CryptoBallotTransaction for MixTransaction {
@@ -150,13 +281,22 @@ impl CryptoBallotTransaction for MixTransaction {
         let election = store.get_election(self.election_id)?.tx;
 
         // If there's no mixnet config, then we can't post mixnet transactions
-        if election.mix_config.is_none() {
-            return Err(ValidationError::NoMixnetConfig);
+        let mix_config = match &election.mix_config {
+            Some(mix_config) => mix_config,
+            None => return Err(ValidationError::NoMixnetConfig),
+        };
+
+        // Homomorphic contests are never mixed, their votes are summed instead
+        if let Some(contest) = election.get_contest(self.contest_index) {
+            if contest.homomorphic {
+                return Err(ValidationError::HomomorphicContestMixed(self.contest_index));
+            }
         }
 
-        // Validate that this trustee exists
+        // Validate that this trustee exists, and is one of the trustees currently holding the key
+        let trustees = current_trustees(store, &election)?;
         let mut trustee_exists = false;
-        for trustee in &election.trustees {
+        for trustee in &trustees.trustees {
             if trustee.index == self.trustee_index && trustee.public_key == self.trustee_public_key
             {
                 trustee_exists = true;
@@ -166,19 +306,35 @@ impl CryptoBallotTransaction for MixTransaction {
             return Err(ValidationError::TrusteeDoesNotExist(self.trustee_index));
         }
 
-        // TODO: Deal with timeouts and mix index orderings
-        if self.mix_index != self.trustee_index - 1 {
+        // There are exactly trustees_threshold mixes in every chain
+        if self.mix_index >= trustees.threshold {
             return Err(ValidationError::OutOfOrderMix);
         }
 
+        // Only one trustee may fill each mix slot
+        if let Some(existing) = Self::get_mix_in_slot(
+            store,
+            self.election_id,
+            self.contest_index,
+            self.batch,
+            self.mix_index,
+        )? {
+            if existing.id != self.id {
+                return Err(ValidationError::MixSlotTaken(
+                    self.mix_index,
+                    existing.trustee_index,
+                ));
+            }
+        }
+
         // Make sure we have all the ciphertexts in the mix
         if self.mixed_ciphertexts.len() != self.vote_ids.len() {
             return Err(ValidationError::MixWrongNumberOfVotes);
         }
 
-        let input_ciphertexts = if self.prev_mix_id.is_some() {
+        let (reference_time, input_ciphertexts) = if self.prev_mix_id.is_some() {
             let prev_mix: MixTransaction = store
-                .get_transaction(self.prev_mix_id.unwrap())
+                .get_transaction(self.prev_mix_id.unwrap())?
                 .ok_or(ValidationError::MissingPrevMixTransaction)?
                 .into();
 
@@ -192,12 +348,18 @@ impl CryptoBallotTransaction for MixTransaction {
                 return Err(ValidationError::InvalidPrevMixTransaction);
             }
 
-            prev_mix.mixed_ciphertexts
+            (prev_mix.timestamp, prev_mix.mixed_ciphertexts)
         } else {
             if self.mix_index != 0 {
                 return Err(ValidationError::OutOfOrderMix);
             }
 
+            let voting_end_id = Identifier::new(self.election_id, TransactionType::VotingEnd, None);
+            let voting_end: VotingEndTransaction = store
+                .get_transaction(voting_end_id)?
+                .ok_or(ValidationError::MisingVotingEndTransaction)?
+                .into();
+
             // Check that vote-ids are in ascending order with no duplicates
             // TODO: Do this in a single function, I think we can use "is_sorted_by" to disallow equalities
             if !&self.vote_ids.is_sorted() {
@@ -207,44 +369,53 @@ impl CryptoBallotTransaction for MixTransaction {
                 return Err(ValidationError::MixVoteIdsNotSorted);
             }
 
-            // TODO: Support batching
-            //       - Check that vote_ids.len() <= batch-size
-            //       - Validate batching - make sure batched votes are exactly correct
-            //       - This will require reading the votes in order and checking for first, or ranging off the final vote_ids of prev mix
+            // Make sure the votes in this mix are exactly the votes in this batch.
+            // Since batches are a deterministic partition of the votes, this ensures that all
+            // batches together cover every vote exactly once.
+            let batch = mix_config
+                .vote_batch(store, self.election_id, self.contest_index, self.batch)?
+                .ok_or(ValidationError::MixBatchDoesNotExist(self.batch))?;
 
-            // Make sure all votes are accounted for
-            let votes = store.range(
-                Identifier::start(self.election_id, TransactionType::Vote, None),
-                Identifier::end(self.election_id, TransactionType::Vote, None),
-            );
-
-            if votes.len() != self.vote_ids.len() {
+            if batch.len() != self.vote_ids.len() {
                 return Err(ValidationError::MixWrongNumberOfVotes);
             }
 
-            for (i, vote) in votes.iter().enumerate() {
-                if self.vote_ids[i] != vote.id() {
+            let mut ciphertexts = Vec::with_capacity(batch.len());
+            for (i, (vote_id, selections)) in batch.into_iter().enumerate() {
+                if self.vote_ids[i] != vote_id {
                     return Err(ValidationError::MixVotesNotAccountedFor);
                 }
+                ciphertexts.push(selections);
             }
 
-            let mut ciphertexts = Vec::with_capacity(self.vote_ids.len());
-            for vote in votes {
-                let vote: VoteTransaction = vote.into();
+            (voting_end.timestamp, ciphertexts)
+        };
 
-                for encrypted_vote in vote.encrypted_votes {
-                    if encrypted_vote.contest_index == self.contest_index {
-                        ciphertexts.push(encrypted_vote.selections);
-                    }
-                }
-            }
+        // Validate the timestamp against the store's clock and the previous stage
+        if self.timestamp > store.current_time()? {
+            return Err(ValidationError::TimestampInFuture(self.timestamp));
+        }
+        if self.timestamp < reference_time {
+            return Err(ValidationError::MixTimestampBeforePrevious);
+        }
 
-            ciphertexts
-        };
+        // Validate that it is this trustee's turn, or that the trustees ahead of it have timed out
+        let already_mixed = self.previous_mixers(store)?;
+        if already_mixed.contains(&self.trustee_index) {
+            return Err(ValidationError::TrusteeAlreadyMixed(self.trustee_index));
+        }
+        let slot_order = mix_config.slot_order(&trustees.trustees, self.mix_index, &already_mixed);
+        let position = slot_order
+            .iter()
+            .position(|index| *index == self.trustee_index)
+            .ok_or(ValidationError::OutOfOrderMix)?;
+        if self.timestamp < mix_config.slot_opens_at(reference_time, position) {
+            return Err(ValidationError::OutOfOrderMix);
+        }
 
         let enc_key_tx = Identifier::new(self.election_id, TransactionType::EncryptionKey, None);
         let key_tx: EncryptionKeyTransaction = store
-            .get_transaction(enc_key_tx)
+            .get_transaction(enc_key_tx)?
             .ok_or(ValidationError::EncryptionKeyTransactionDoesNotExist)?
             .into();
 
@@ -264,6 +435,24 @@ impl CryptoBallotTransaction for MixTransaction {
     }
 }
 
+// The ciphertexts of the latest vote of each voter that voted in a contest, in ascending vote-id order
+fn contest_votes<'a, S: Store>(
+    store: &'a S,
+    election_id: Identifier,
+    contest_index: u32,
+) -> impl Iterator<Item = Result<(Identifier, Vec<Ciphertext>), StoreError>> + 'a {
+    latest_votes(store, election_id).filter_map(move |vote| match vote {
+        Ok(vote) => {
+            let id = vote.id;
+            vote.encrypted_votes
+                .into_iter()
+                .find(|encrypted_vote| encrypted_vote.contest_index == contest_index)
+                .map(|encrypted_vote| Ok((id, encrypted_vote.selections)))
+        }
+        Err(err) => Some(Err(err)),
+    })
+}
+
 /// Do a mixnet shuffle
 /// This is an expensive and time-consuming operation, so should ideally be offloaded to it's own thread
 pub fn mix<R: Rng + CryptoRng>(
@@ -0,0 +1,132 @@
+use crate::*;
+
+// Crockford's base32 alphabet, which avoids letters that are easily confused with digits
+const TRACKING_CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+// Number of base32 characters in a tracking code (80 bits of the ballot hash)
+const TRACKING_CODE_LEN: usize = 16;
+
+/// A receipt given to a voter when their vote is built.
+///
+/// The receipt reveals nothing about how the voter voted. It lets the voter, or anyone they share it with, check
+/// that their vote was recorded unchanged and went on to be counted (see `verify_receipt`).
+///
+/// The ballot hash is the same commitment shown to the voter in a Benaloh cast-or-challenge (see `BenalohBallot`),
+/// so a voter who challenged their device can check that the ballot they cast is the one that was recorded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VoteReceipt {
+    pub election_id: Identifier,
+    pub vote_id: Identifier,
+
+    /// SHA-256 hash of the encrypted votes
+    #[serde(with = "hex_serde")]
+    pub ballot_hash: [u8; 32],
+
+    /// A short, human-readable code derived from the ballot hash, for looking up the vote on the bulletin board
+    pub tracking_code: String,
+}
+
+impl VoteReceipt {
+    /// Create a receipt for a vote
+    pub fn new(vote: &VoteTransaction) -> Self {
+        let ballot_hash = benaloh_commitment(&vote.encrypted_votes);
+
+        VoteReceipt {
+            election_id: vote.election,
+            vote_id: vote.id,
+            ballot_hash,
+            tracking_code: tracking_code(&ballot_hash),
+        }
+    }
+}
+
+impl VoteTransaction {
+    /// Create a receipt for this vote, to be kept by the voter
+    pub fn receipt(&self) -> VoteReceipt {
+        VoteReceipt::new(self)
+    }
+}
+
+/// Format a ballot hash as a tracking code, such as `7K2M-Q9XD-4HTW-0BNA`
+pub fn tracking_code(ballot_hash: &[u8; 32]) -> String {
+    let mut code = String::with_capacity(TRACKING_CODE_LEN + TRACKING_CODE_LEN / 4);
+    for i in 0..TRACKING_CODE_LEN {
+        // Read 5 bits at a time, starting from the most significant bit
+        let bit = i * 5;
+        let pair = u16::from_be_bytes([ballot_hash[bit / 8], ballot_hash[bit / 8 + 1]]);
+        let index = (pair >> (11 - bit % 8)) & 0x1f;
+
+        if i > 0 && i % 4 == 0 {
+            code.push('-');
+        }
+        code.push(TRACKING_CODE_ALPHABET[index as usize] as char);
+    }
+
+    code
+}
+
+/// Verify a voter's receipt against the store.
+///
+/// The verification does the following:
+///  - Validates that the vote is present in the store, unchanged since the receipt was issued
+///  - Validates that the vote has not been replaced by a later revision
+///  - For each contest in the vote, validates that the vote was counted. If the election has a mix-net, the vote
+///    must be in the first mix of its contest. Otherwise the vote must have been decrypted directly.
+///    Votes in homomorphic contests are always counted, since every latest vote is part of the homomorphic total.
+///
+/// Votes are only mixed or decrypted after voting ends, so a receipt can only be fully verified once they have been.
+pub fn verify_receipt<S: Store>(store: &S, receipt: &VoteReceipt) -> Result<(), ValidationError> {
+    if receipt.vote_id.election_id != receipt.election_id.election_id
+        || receipt.tracking_code != tracking_code(&receipt.ballot_hash)
+    {
+        return Err(ValidationError::ReceiptMismatch(receipt.vote_id));
+    }
+
+    let vote = store.get_vote(receipt.vote_id)?.tx;
+    if benaloh_commitment(&vote.encrypted_votes) != receipt.ballot_hash {
+        return Err(ValidationError::ReceiptMismatch(receipt.vote_id));
+    }
+
+    if !vote_is_latest(store, &vote)? {
+        return Err(ValidationError::VoteReplaced);
+    }
+
+    let election = store.get_election(vote.election)?;
+    for encrypted_vote in &vote.encrypted_votes {
+        let contest_index = encrypted_vote.contest_index;
+        let homomorphic = election
+            .get_contest(contest_index)
+            .map_or(false, |contest| contest.homomorphic);
+        if homomorphic {
+            continue;
+        }
+
+        let counted = if election.mix_config.is_some() {
+            let mut mixed = false;
+            for mix in store.get_multiple(election.id, TransactionType::Mix) {
+                let mix: MixTransaction = mix?.into();
+                if mix.mix_index == 0
+                    && mix.contest_index == contest_index
+                    && mix.vote_ids.contains(&vote.id)
+                {
+                    mixed = true;
+                    break;
+                }
+            }
+            mixed
+        } else {
+            let decryption_id =
+                DecryptionTransaction::build_id(election.id, vote.id, contest_index, 0);
+            store.get_transaction(decryption_id)?.is_some()
+        };
+
+        if !counted {
+            return Err(ValidationError::ReceiptVoteNotCounted(
+                receipt.vote_id,
+                contest_index,
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,941 @@
+use crate::homomorphic::find_total;
+use crate::wellformed::to_cryptid;
+use crate::*;
+use cryptid::curve::CurveElem;
+use cryptid::elgamal::Ciphertext;
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::{PublicKey, SecretKey};
+use indexmap::IndexMap;
+use prost::Message;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use std::collections::HashSet;
+use x25519_dalek as x25519;
+
+/// Transaction 12: TrusteeReshare
+///
+/// Replaces an election's trustees, for example when a trustee has lost their key or left the election.
+///
+/// A threshold of the current trustees (the dealers) each reshare their share of the election's secret key to the new
+/// trustees in a ReshareShare transaction. The new trustees then hold shares of the same secret, so the encryption key
+/// is unchanged and votes that have already been cast can still be decrypted.
+///
+/// Reshares are numbered from 1. A reshare takes effect once every new trustee has posted a ResharePublicKey
+/// transaction, and a later reshare abandons any earlier reshare that has not yet taken effect.
+///
+/// Trustees can be reshared after voting has ended, for example when a trustee loses their key before the votes are
+/// decrypted, but not once mixing or decryption has started. Mixes and partial decryptions are checked against the
+/// trustees holding the key when they are posted, so the trustees can't change part way through a mix chain or a
+/// decryption.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrusteeReshareTransaction {
+    pub id: Identifier,
+    pub election: Identifier,
+    #[serde(with = "EdPublicKeyHex")]
+    pub authority_public_key: PublicKey,
+    pub generation: u8,
+
+    /// The current trustees that reshare their key shares, exactly as many as the current threshold
+    pub dealers: Vec<u8>,
+
+    /// The new trustees, replacing `ElectionTransaction.trustees` once the reshare takes effect
+    pub trustees: Vec<Trustee>,
+    pub trustees_threshold: u8,
+
+    /// The x25519 public key of each new trustee, used to encrypt their shares (see `Trustee::x25519_public_key`)
+    pub x25519_public_keys: Vec<TrusteeX25519PublicKey>,
+}
+
+/// A trustee's x25519 public key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrusteeX25519PublicKey {
+    pub trustee_index: u8,
+    #[serde(with = "X25519PublicKeyHex")]
+    pub public_key: x25519::PublicKey,
+}
+
+/// Transaction 13: ReshareShare
+///
+/// A dealer's share of the election's secret key, reshared to each of the new trustees.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReshareShareTransaction {
+    pub id: Identifier,
+    pub election: Identifier,
+    pub generation: u8,
+    pub trustee_index: u8,
+    #[serde(with = "EdPublicKeyHex")]
+    pub trustee_public_key: PublicKey,
+
+    /// Commitments to the coefficients of the polynomial the dealer's share is reshared with
+    pub commitment: Vec<CurveElem>,
+
+    /// One encrypted share per new trustee
+    #[serde(with = "indexmap::serde_seq")]
+    pub shares: IndexMap<u8, EncryptedShare>,
+}
+
+/// Transaction 14: ResharePublicKey
+///
+/// Posted by each new trustee once it has received its shares, publishing the verification key that its partial
+/// decryptions are checked against.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResharePublicKeyTransaction {
+    pub id: Identifier,
+    pub election: Identifier,
+    pub generation: u8,
+    pub trustee_index: u8,
+    #[serde(with = "EdPublicKeyHex")]
+    pub trustee_public_key: PublicKey,
+
+    /// The trustee's key share times the generator
+    pub verification_key: CurveElem,
+}
+
+/// A partial decryption made by a trustee that joined the election in a reshare.
+///
+/// Includes a Chaum-Pedersen proof that `log_G(verification_key) == log_c1(share)`, so the partial decryption was made
+/// with the trustee's key share.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResharedDecryptShare {
+    pub share: CurveElem,
+    #[serde(with = "ScalarHex")]
+    pub challenge: Scalar,
+    #[serde(with = "ScalarHex")]
+    pub response: Scalar,
+}
+
+/// The trustees holding shares of an election's secret key
+#[derive(Clone)]
+pub struct TrusteeSet {
+    /// The reshare that appointed these trustees, or 0 for the election's own trustees
+    pub generation: u8,
+    pub trustees: Vec<Trustee>,
+    pub threshold: u8,
+}
+
+impl TrusteeSet {
+    pub fn get(&self, trustee_index: u8) -> Option<&Trustee> {
+        self.trustees
+            .iter()
+            .find(|trustee| trustee.index == trustee_index)
+    }
+}
+
+impl TrusteeReshareTransaction {
+    pub fn new(
+        election_id: Identifier,
+        authority_public_key: PublicKey,
+        generation: u8,
+        dealers: Vec<u8>,
+        trustees: Vec<Trustee>,
+        trustees_threshold: u8,
+        x25519_public_keys: &[(u8, x25519::PublicKey)],
+    ) -> Self {
+        TrusteeReshareTransaction {
+            id: Self::build_id(election_id, generation),
+            election: election_id,
+            authority_public_key,
+            generation,
+            dealers,
+            trustees,
+            trustees_threshold,
+            x25519_public_keys: x25519_public_keys
+                .iter()
+                .map(|(trustee_index, public_key)| TrusteeX25519PublicKey {
+                    trustee_index: *trustee_index,
+                    public_key: *public_key,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn build_id(election_id: Identifier, generation: u8) -> Identifier {
+        let mut unique_info = [0; 16];
+        unique_info[0] = generation;
+        Identifier::new(
+            election_id,
+            TransactionType::TrusteeReshare,
+            Some(unique_info),
+        )
+    }
+
+    /// The new trustees
+    pub fn trustee_set(&self) -> TrusteeSet {
+        let trustees = self
+            .trustees
+            .iter()
+            .map(|trustee| {
+                let mut trustee = trustee.clone();
+                trustee.threshold = self.trustees_threshold;
+                trustee.num_trustees = self.trustees.len();
+                trustee
+            })
+            .collect();
+
+        TrusteeSet {
+            generation: self.generation,
+            trustees,
+            threshold: self.trustees_threshold,
+        }
+    }
+
+    /// Whether every new trustee has posted a ResharePublicKey transaction
+    pub fn is_complete<S: Store>(&self, store: &S) -> Result<bool, ValidationError> {
+        for trustee in &self.trustees {
+            let id = ResharePublicKeyTransaction::build_id(
+                self.election,
+                self.generation,
+                trustee.index,
+            );
+            if store.get_transaction(id)?.is_none() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl CryptoBallotTransaction for TrusteeReshareTransaction {
+    #[inline(always)]
+    fn id(&self) -> Identifier {
+        self.id
+    }
+
+    #[inline(always)]
+    fn public(&self) -> Option<PublicKey> {
+        Some(self.authority_public_key)
+    }
+
+    #[inline(always)]
+    fn election_id(&self) -> Identifier {
+        self.election
+    }
+
+    #[inline(always)]
+    fn tx_type() -> TransactionType {
+        TransactionType::TrusteeReshare
+    }
+
+    /// Validate the transaction
+    ///
+    /// The validation does the following:
+    ///  - Validates that this transaction has been signed by the election authority
+    ///  - Validates that key generation has finished, and that mixing and decryption have not started
+    ///  - Validates that this is the next reshare
+    ///  - Validates the new trustees, and that the dealers are a threshold of the current trustees
+    fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
+        let election = store.get_election(self.election)?;
+
+        if Self::build_id(self.election, self.generation) != self.id {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
+        if self.authority_public_key != election.authority_public {
+            return Err(ValidationError::AuthorityPublicKeyMismatch);
+        }
+
+        // The key must exist before it can be reshared
+        let encryption_key_id = EncryptionKeyTransaction::build_id(self.election);
+        if store.get_transaction(encryption_key_id)?.is_none() {
+            return Err(ValidationError::EncryptionKeyTransactionDoesNotExist);
+        }
+        ensure_not_decrypting(store, self.election)?;
+
+        // Reshares are numbered in order
+        let latest = store
+            .get_multiple(self.election, TransactionType::TrusteeReshare)
+            .last()
+            .transpose()?
+            .map_or(0, |tx| TrusteeReshareTransaction::from(tx).generation);
+        if self.generation == 0 || self.generation as u16 != latest as u16 + 1 {
+            return Err(ValidationError::ReshareOutOfOrder(self.generation));
+        }
+
+        validate_trustees(&self.trustees, self.trustees_threshold)?;
+
+        // Every new trustee needs an x25519 public key to receive their shares
+        if self.x25519_public_keys.len() != self.trustees.len() {
+            return Err(ValidationError::ReshareX25519KeysMismatch);
+        }
+        for trustee in &self.trustees {
+            if !self
+                .x25519_public_keys
+                .iter()
+                .any(|key| key.trustee_index == trustee.index)
+            {
+                return Err(ValidationError::TrusteeMissing(trustee.index));
+            }
+        }
+
+        // The dealers are exactly a threshold of the current trustees
+        let current = current_trustees(store, &election)?;
+        if self.dealers.len() != current.threshold as usize {
+            return Err(ValidationError::InvalidReshareDealers);
+        }
+        let mut dealers = HashSet::with_capacity(self.dealers.len());
+        for dealer in &self.dealers {
+            if current.get(*dealer).is_none() || !dealers.insert(*dealer) {
+                return Err(ValidationError::InvalidReshareDealers);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ReshareShareTransaction {
+    pub fn new(
+        election_id: Identifier,
+        generation: u8,
+        trustee_index: u8,
+        trustee_public_key: PublicKey,
+        commitment: Vec<CurveElem>,
+        shares: IndexMap<u8, EncryptedShare>,
+    ) -> Self {
+        ReshareShareTransaction {
+            id: Self::build_id(election_id, generation, trustee_index),
+            election: election_id,
+            generation,
+            trustee_index,
+            trustee_public_key,
+            commitment,
+            shares,
+        }
+    }
+
+    pub fn build_id(election_id: Identifier, generation: u8, trustee_index: u8) -> Identifier {
+        let mut unique_info = [0; 16];
+        unique_info[0] = generation;
+        unique_info[1] = trustee_index;
+        Identifier::new(
+            election_id,
+            TransactionType::ReshareShare,
+            Some(unique_info),
+        )
+    }
+}
+
+impl CryptoBallotTransaction for ReshareShareTransaction {
+    #[inline(always)]
+    fn id(&self) -> Identifier {
+        self.id
+    }
+
+    #[inline(always)]
+    fn public(&self) -> Option<PublicKey> {
+        Some(self.trustee_public_key)
+    }
+
+    #[inline(always)]
+    fn election_id(&self) -> Identifier {
+        self.election
+    }
+
+    #[inline(always)]
+    fn tx_type() -> TransactionType {
+        TransactionType::ReshareShare
+    }
+
+    /// Validate the transaction
+    ///
+    /// The validation does the following:
+    ///  - Validates that this transaction has been signed by one of the reshare's dealers
+    ///  - Validates that the reshare is the latest, and that mixing and decryption have not started
+    ///  - Validates that there is one coefficient per new trustee threshold, and one share per new trustee
+    ///  - Validates that the dealer reshares its own key share, checked against its verification key
+    fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
+        let election = store.get_election(self.election)?;
+
+        if Self::build_id(self.election, self.generation, self.trustee_index) != self.id {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
+        let reshare = latest_reshare(store, self.election, self.generation)?;
+        ensure_not_decrypting(store, self.election)?;
+
+        // Validate that this trustee is a dealer
+        if !reshare.dealers.contains(&self.trustee_index) {
+            return Err(ValidationError::TrusteeNotDealer(self.trustee_index));
+        }
+        let dealers = previous_trustees(store, &election, self.generation)?;
+        let dealer = dealers
+            .get(self.trustee_index)
+            .ok_or(ValidationError::TrusteeDoesNotExist(self.trustee_index))?;
+        if dealer.public_key != self.trustee_public_key {
+            return Err(ValidationError::TrusteePublicKeyMismatch(
+                self.trustee_index,
+            ));
+        }
+
+        if self.commitment.len() != reshare.trustees_threshold as usize {
+            return Err(ValidationError::ReshareCommitmentInvalid(
+                self.trustee_index,
+            ));
+        }
+
+        // The constant term is the dealer's key share weighted by its Lagrange coefficient, so that the dealers'
+        // constant terms sum to the secret key. Checking each dealer on its own means no dealer can shift its part
+        // of the key onto another.
+        let dealer_key =
+            dealer_verification_key(store, self.election, dealers.generation, self.trustee_index)?;
+        let coefficient = lagrange_coefficient(self.trustee_index, &reshare.dealers);
+        if self.commitment.first() != Some(&dealer_key.scaled(&to_cryptid(&coefficient))) {
+            return Err(ValidationError::ReshareDealerKeyMismatch(
+                self.trustee_index,
+            ));
+        }
+
+        // Validate that every new trustee has been given a share
+        if self.shares.len() != reshare.trustees.len() {
+            return Err(ValidationError::WrongNumberOfShares);
+        }
+        for trustee in &reshare.trustees {
+            if !self.shares.contains_key(&trustee.index) {
+                return Err(ValidationError::TrusteeShareMissing(trustee.index));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ResharePublicKeyTransaction {
+    pub fn new(
+        election_id: Identifier,
+        generation: u8,
+        trustee_index: u8,
+        trustee_public_key: PublicKey,
+        verification_key: CurveElem,
+    ) -> Self {
+        ResharePublicKeyTransaction {
+            id: Self::build_id(election_id, generation, trustee_index),
+            election: election_id,
+            generation,
+            trustee_index,
+            trustee_public_key,
+            verification_key,
+        }
+    }
+
+    pub fn build_id(election_id: Identifier, generation: u8, trustee_index: u8) -> Identifier {
+        let mut unique_info = [0; 16];
+        unique_info[0] = generation;
+        unique_info[1] = trustee_index;
+        Identifier::new(
+            election_id,
+            TransactionType::ResharePublicKey,
+            Some(unique_info),
+        )
+    }
+}
+
+impl CryptoBallotTransaction for ResharePublicKeyTransaction {
+    #[inline(always)]
+    fn id(&self) -> Identifier {
+        self.id
+    }
+
+    #[inline(always)]
+    fn public(&self) -> Option<PublicKey> {
+        Some(self.trustee_public_key)
+    }
+
+    #[inline(always)]
+    fn election_id(&self) -> Identifier {
+        self.election
+    }
+
+    #[inline(always)]
+    fn tx_type() -> TransactionType {
+        TransactionType::ResharePublicKey
+    }
+
+    /// Validate the transaction
+    ///
+    /// The validation does the following:
+    ///  - Validates that this transaction has been signed by one of the reshare's new trustees
+    ///  - Validates that the reshare is the latest, and that mixing and decryption have not started
+    ///  - Validates that every dealer has reshared, and that together they reshared the election's secret key
+    ///  - Validates that the verification key is the one committed to by the dealers
+    fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
+        if Self::build_id(self.election, self.generation, self.trustee_index) != self.id {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
+        let reshare = latest_reshare(store, self.election, self.generation)?;
+        ensure_not_decrypting(store, self.election)?;
+
+        // Validate that this trustee is one of the new trustees
+        let trustee = reshare
+            .trustees
+            .iter()
+            .find(|trustee| trustee.index == self.trustee_index)
+            .ok_or(ValidationError::TrusteeDoesNotExist(self.trustee_index))?;
+        if trustee.public_key != self.trustee_public_key {
+            return Err(ValidationError::TrusteePublicKeyMismatch(
+                self.trustee_index,
+            ));
+        }
+
+        let mut shares = Vec::with_capacity(reshare.dealers.len());
+        for dealer in &reshare.dealers {
+            let share_id =
+                ReshareShareTransaction::build_id(self.election, self.generation, *dealer);
+            let share: ReshareShareTransaction = store
+                .get_transaction(share_id)?
+                .ok_or(ValidationError::MissingReshareShare(*dealer))?
+                .into();
+            shares.push(share);
+        }
+
+        // The dealers' polynomials must sum to a polynomial whose constant is the election's secret key
+        let encryption_key: EncryptionKeyTransaction = store
+            .get_transaction(EncryptionKeyTransaction::build_id(self.election))?
+            .ok_or(ValidationError::EncryptionKeyTransactionDoesNotExist)?
+            .into();
+        let reshared_key = shares.iter().fold(identity(), |sum, share| {
+            sum + evaluate_commitment(&share.commitment, 0)
+        });
+        if reshared_key != encryption_key.encryption_key.y {
+            return Err(ValidationError::ReshareChangesEncryptionKey);
+        }
+
+        let verification_key = shares.iter().fold(identity(), |sum, share| {
+            sum + evaluate_commitment(&share.commitment, self.trustee_index)
+        });
+        if verification_key != self.verification_key {
+            return Err(ValidationError::VerificationKeyMismatch(self.trustee_index));
+        }
+
+        Ok(())
+    }
+}
+
+impl ResharedDecryptShare {
+    /// Partially decrypt a ciphertext with a key share from a reshare, proving it was made with that key share
+    pub fn new<R: CryptoRng + RngCore>(
+        rng: &mut R,
+        key_share: &Scalar,
+        ciphertext: &Ciphertext,
+    ) -> Result<Self, Error> {
+        let verification_key = verification_key(key_share);
+        let share = ciphertext.c1.scaled(&to_cryptid(key_share));
+
+        let nonce = Scalar::random(rng);
+        let commitments = (
+            CurveElem::generator().scaled(&to_cryptid(&nonce)),
+            ciphertext.c1.scaled(&to_cryptid(&nonce)),
+        );
+        let challenge =
+            decrypt_share_challenge(&verification_key, ciphertext, &share, &commitments)?;
+
+        Ok(ResharedDecryptShare {
+            share,
+            challenge,
+            response: nonce + challenge * key_share,
+        })
+    }
+
+    /// Verify that this partial decryption of the ciphertext was made with the key share behind `verification_key`
+    pub fn verify(&self, verification_key: &CurveElem, ciphertext: &Ciphertext) -> bool {
+        let challenge = to_cryptid(&self.challenge);
+        let response = to_cryptid(&self.response);
+
+        // Recompute the commitments: a = z⋅G - e⋅H and b = z⋅c1 - e⋅D
+        let commitments = (
+            CurveElem::generator().scaled(&response) - verification_key.scaled(&challenge),
+            ciphertext.c1.scaled(&response) - self.share.scaled(&challenge),
+        );
+
+        match decrypt_share_challenge(verification_key, ciphertext, &self.share, &commitments) {
+            Ok(expected) => expected == self.challenge,
+            Err(_) => false,
+        }
+    }
+}
+
+impl Trustee {
+    /// This trustee's share of the election's secret key, from the shares it received during key generation.
+    ///
+    /// Key shares are only needed to reshare the key, and must never be published.
+    pub fn key_share(
+        &self,
+        sk: &SecretKey,
+        x25519_public_keys: &[(u8, x25519::PublicKey)],
+        shares: &[(u8, EncryptedShare)], // From, Share
+        election_id: Identifier,
+    ) -> Result<Scalar, ValidationError> {
+        let decrypted = self.decrypt_shares(sk, shares, x25519_public_keys, election_id)?;
+
+        Ok(decrypted
+            .iter()
+            .fold(Scalar::zero(), |sum, (_, share)| sum + from_cryptid(share)))
+    }
+
+    /// Reshare this trustee's key share to the new trustees, for a ReshareShare transaction.
+    ///
+    /// Returns the commitment to the resharing polynomial, and the encrypted share for each new trustee.
+    pub fn reshare<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        sk: &SecretKey,
+        key_share: &Scalar,
+        reshare: &TrusteeReshareTransaction,
+    ) -> Result<(Vec<CurveElem>, IndexMap<u8, EncryptedShare>), ValidationError> {
+        if !reshare.dealers.contains(&self.index) {
+            return Err(ValidationError::TrusteeNotDealer(self.index));
+        }
+
+        // The dealers' weighted shares sum to the secret key, so the new polynomials do too
+        let mut coefficients = Vec::with_capacity(reshare.trustees_threshold as usize);
+        coefficients.push(lagrange_coefficient(self.index, &reshare.dealers) * key_share);
+        for _ in 1..reshare.trustees_threshold {
+            coefficients.push(Scalar::random(rng));
+        }
+
+        let commitment = coefficients.iter().map(verification_key).collect();
+
+        let mut shares = IndexMap::with_capacity(reshare.x25519_public_keys.len());
+        for key in &reshare.x25519_public_keys {
+            let share = evaluate_polynomial(&coefficients, key.trustee_index);
+            let shared_secret = self.shared_secret(sk, reshare.election, &key.public_key);
+            let encrypted = EncryptedShare::new(rng, shared_secret, &to_cryptid(&share));
+
+            shares.insert(key.trustee_index, encrypted);
+        }
+
+        Ok((commitment, shares))
+    }
+
+    /// Receive this trustee's shares from a reshare, checking each against its dealer's commitment.
+    ///
+    /// Returns this trustee's new key share. `dealer_x25519_public_keys` are the dealers' x25519 public keys (see
+    /// `trustee_x25519_public_keys`).
+    pub fn receive_reshare(
+        &self,
+        sk: &SecretKey,
+        dealer_x25519_public_keys: &[(u8, x25519::PublicKey)],
+        shares: &[ReshareShareTransaction],
+        election_id: Identifier,
+    ) -> Result<Scalar, ValidationError> {
+        let mut key_share = Scalar::zero();
+        for share_tx in shares {
+            let encrypted = share_tx
+                .shares
+                .get(&self.index)
+                .ok_or(ValidationError::TrusteeShareMissing(self.index))?;
+            let encrypted = [(share_tx.trustee_index, encrypted.clone())];
+            let decrypted =
+                self.decrypt_shares(sk, &encrypted, dealer_x25519_public_keys, election_id)?;
+            let share = from_cryptid(&decrypted[0].1);
+
+            if verification_key(&share) != evaluate_commitment(&share_tx.commitment, self.index) {
+                return Err(ValidationError::ReshareShareInvalid(share_tx.trustee_index));
+            }
+
+            key_share += share;
+        }
+
+        Ok(key_share)
+    }
+}
+
+/// The verification key for a key share, published in a ResharePublicKey transaction
+pub fn verification_key(key_share: &Scalar) -> CurveElem {
+    CurveElem::generator().scaled(&to_cryptid(key_share))
+}
+
+/// The trustees holding shares of an election's secret key: the trustees of the latest reshare to take effect, or the
+/// election's own qualified trustees (see `qualified_trustees`) if it has not been reshared.
+pub fn current_trustees<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+) -> Result<TrusteeSet, ValidationError> {
+    trustees_before(store, election, None)
+}
+
+/// The trustees that deal in a reshare: those holding the election's key when the reshare was made.
+pub fn previous_trustees<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+    generation: u8,
+) -> Result<TrusteeSet, ValidationError> {
+    trustees_before(store, election, Some(generation))
+}
+
+/// Whether a public key belongs to a trustee that has held the election's key: one of the election's own qualified
+/// trustees, or a trustee appointed by a reshare that has taken effect.
+pub fn has_held_key<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+    public_key: &PublicKey,
+) -> Result<bool, ValidationError> {
+    let holds_key = |trustees: &[Trustee]| {
+        trustees
+            .iter()
+            .any(|trustee| trustee.public_key == *public_key)
+    };
+
+    if holds_key(&qualified_trustees(store, election)?) {
+        return Ok(true);
+    }
+    for reshare in store.get_multiple(election.id, TransactionType::TrusteeReshare) {
+        let reshare: TrusteeReshareTransaction = reshare?.into();
+        if holds_key(&reshare.trustees) && reshare.is_complete(store)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// The x25519 public keys of the trustees appointed by a reshare, or of the election's own trustees for generation 0.
+pub fn trustee_x25519_public_keys<S: Store>(
+    store: &S,
+    election_id: Identifier,
+    generation: u8,
+) -> Result<Vec<(u8, x25519::PublicKey)>, ValidationError> {
+    if generation == 0 {
+        return store
+            .get_multiple(election_id, TransactionType::KeyGenCommitment)
+            .map(|tx| -> Result<_, ValidationError> {
+                let tx: KeyGenCommitmentTransaction = tx?.into();
+                Ok((tx.trustee_index, tx.x25519_public_key))
+            })
+            .collect();
+    }
+
+    let reshare_id = TrusteeReshareTransaction::build_id(election_id, generation);
+    let reshare: TrusteeReshareTransaction = store
+        .get_transaction(reshare_id)?
+        .ok_or(ValidationError::ReshareDoesNotExist(generation))?
+        .into();
+    Ok(reshare
+        .x25519_public_keys
+        .iter()
+        .map(|key| (key.trustee_index, key.public_key))
+        .collect())
+}
+
+/// Decrypt a vote from partial decryptions made by trustees that joined the election in a reshare.
+pub fn decrypt_vote_reshared(
+    ciphertexts: &[Ciphertext],
+    partials: &[PartialDecryptionTransaction],
+) -> Result<Vec<Selection>, ValidationError> {
+    let mut results = Vec::with_capacity(ciphertexts.len());
+    for (i, ciphertext) in ciphertexts.iter().enumerate() {
+        let message = combine_partials(ciphertext, partials, i)?;
+        let raw_selection = decode_message(message)?;
+
+        let selection = Selection::decode(raw_selection.as_slice())?;
+        results.push(selection);
+    }
+
+    Ok(results)
+}
+
+/// Decrypt the totals of a homomorphic contest from partial decryptions made by trustees that joined the election in
+/// a reshare (see `decrypt_totals`).
+pub fn decrypt_totals_reshared(
+    contest: &Contest,
+    ciphertexts: &[Ciphertext],
+    num_votes: usize,
+    partials: &[PartialDecryptionTransaction],
+) -> Result<Vec<Selection>, ValidationError> {
+    let max_counter =
+        contest
+            .max_counter()
+            .ok_or(ValidationError::UnsupportedHomomorphicContest(
+                contest.index,
+            ))?;
+    let max_total = num_votes as u64 * max_counter as u64;
+
+    let mut results = Vec::with_capacity(ciphertexts.len());
+    for (i, (ciphertext, candidate)) in ciphertexts.iter().zip(&contest.candidates).enumerate() {
+        let message = combine_partials(ciphertext, partials, i)?;
+
+        // The message is total⋅G
+        let total = find_total(message, max_total)
+            .ok_or(ValidationError::HomomorphicTotalOutOfRange(contest.index))?;
+        results.push(Selection {
+            write_in: false,
+            score: total as u32,
+            selection: candidate.id.clone(),
+        });
+    }
+
+    Ok(results)
+}
+
+// The trustees of the latest reshare to take effect before `generation`
+fn trustees_before<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+    generation: Option<u8>,
+) -> Result<TrusteeSet, ValidationError> {
+    let mut trustees = TrusteeSet {
+        generation: 0,
+        trustees: qualified_trustees(store, election)?,
+        threshold: election.trustees_threshold,
+    };
+
+    for reshare in store.get_multiple(election.id, TransactionType::TrusteeReshare) {
+        let reshare: TrusteeReshareTransaction = reshare?.into();
+        if generation.map_or(false, |generation| reshare.generation >= generation) {
+            break;
+        }
+        if reshare.is_complete(store)? {
+            trustees = reshare.trustee_set();
+        }
+    }
+
+    Ok(trustees)
+}
+
+// Get a reshare, making sure it has not been abandoned for a later one
+fn latest_reshare<S: Store>(
+    store: &S,
+    election_id: Identifier,
+    generation: u8,
+) -> Result<TrusteeReshareTransaction, ValidationError> {
+    let reshare_id = TrusteeReshareTransaction::build_id(election_id, generation);
+    let reshare: TrusteeReshareTransaction = store
+        .get_transaction(reshare_id)?
+        .ok_or(ValidationError::ReshareDoesNotExist(generation))?
+        .into();
+
+    if generation < u8::MAX {
+        let next_id = TrusteeReshareTransaction::build_id(election_id, generation + 1);
+        if store.get_transaction(next_id)?.is_some() {
+            return Err(ValidationError::ReshareOutOfOrder(generation));
+        }
+    }
+
+    Ok(reshare)
+}
+
+// Mixes and partial decryptions are made by the trustees holding the key when they are posted, so the trustees can't
+// change once either has started
+fn ensure_not_decrypting<S: Store>(
+    store: &S,
+    election_id: Identifier,
+) -> Result<(), ValidationError> {
+    for tx_type in [TransactionType::Mix, TransactionType::PartialDecryption].iter() {
+        if store.get_multiple(election_id, *tx_type).next().is_some() {
+            return Err(ValidationError::ReshareAfterDecryptionStarted);
+        }
+    }
+
+    Ok(())
+}
+
+// The verification key of a dealer's key share: from its KeyGenPublicKey transaction if it is one of the election's
+// own trustees, otherwise from the ResharePublicKey transaction of the reshare that appointed it
+fn dealer_verification_key<S: Store>(
+    store: &S,
+    election_id: Identifier,
+    generation: u8,
+    trustee_index: u8,
+) -> Result<CurveElem, ValidationError> {
+    if generation == 0 {
+        let public_key = qualified_public_key(store, election_id, trustee_index)?;
+        return Ok(*public_key.public_key_proof.h_i());
+    }
+
+    let public_key_id =
+        ResharePublicKeyTransaction::build_id(election_id, generation, trustee_index);
+    let public_key: ResharePublicKeyTransaction = store
+        .get_transaction(public_key_id)?
+        .ok_or(ValidationError::TrusteeMissing(trustee_index))?
+        .into();
+    Ok(public_key.verification_key)
+}
+
+// The Lagrange coefficient for `index` when interpolating at zero from `indexes`
+pub(crate) fn lagrange_coefficient(index: u8, indexes: &[u8]) -> Scalar {
+    let x = Scalar::from(index as u64);
+    let mut numerator = Scalar::one();
+    let mut denominator = Scalar::one();
+    for other in indexes.iter().filter(|other| **other != index) {
+        let other = Scalar::from(*other as u64);
+        numerator *= other;
+        denominator *= other - x;
+    }
+
+    numerator * denominator.invert()
+}
+
+// Evaluate a polynomial at `index` using Horner's method
+fn evaluate_polynomial(coefficients: &[Scalar], index: u8) -> Scalar {
+    let x = Scalar::from(index as u64);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::zero(), |sum, coefficient| sum * x + coefficient)
+}
+
+// The commitment to a polynomial's value at `index`, from the commitments to its coefficients
+pub(crate) fn evaluate_commitment(commitment: &[CurveElem], index: u8) -> CurveElem {
+    let x = to_cryptid(&Scalar::from(index as u64));
+    commitment
+        .iter()
+        .rev()
+        .fold(identity(), |sum, coefficient| sum.scaled(&x) + *coefficient)
+}
+
+// Combine partial decryptions by Lagrange interpolation to recover the message point c2 - x⋅c1.
+// Any `threshold` or more partial decryptions can be used.
+fn combine_partials(
+    ciphertext: &Ciphertext,
+    partials: &[PartialDecryptionTransaction],
+    position: usize,
+) -> Result<CurveElem, ValidationError> {
+    let indexes: Vec<u8> = partials.iter().map(|tx| tx.trustee_index).collect();
+
+    let mut message = ciphertext.c2;
+    for partial in partials {
+        let share = partial
+            .reshared_decryption
+            .get(position)
+            .ok_or(ValidationError::PartialDecryptionProofFailed)?;
+        let coefficient = lagrange_coefficient(partial.trustee_index, &indexes);
+        message = message - share.share.scaled(&to_cryptid(&coefficient));
+    }
+
+    Ok(message)
+}
+
+// The plaintext that cryptid embedded in a message point, the inverse of `encode_bytes`
+fn decode_message(message: CurveElem) -> Result<Vec<u8>, ValidationError> {
+    message
+        .decoded()
+        .map_err(|e| ValidationError::VoteDecryptionFailed(e))
+}
+
+// Fiat-Shamir challenge for a reshared partial decryption
+fn decrypt_share_challenge(
+    verification_key: &CurveElem,
+    ciphertext: &Ciphertext,
+    share: &CurveElem,
+    commitments: &(CurveElem, CurveElem),
+) -> Result<Scalar, Error> {
+    let mut hasher = Sha512::new();
+    hasher.update(b"cryptoballot reshared decryption proof");
+    hasher.update(&serde_cbor::to_vec(verification_key)?);
+    hasher.update(&serde_cbor::to_vec(&(&ciphertext.c1, &ciphertext.c2))?);
+    hasher.update(&serde_cbor::to_vec(share)?);
+    hasher.update(&serde_cbor::to_vec(commitments)?);
+
+    Ok(Scalar::from_hash(hasher))
+}
+
+fn from_cryptid(scalar: &cryptid::Scalar) -> Scalar {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&scalar.as_bytes()[..]);
+    Scalar::from_bytes_mod_order(bytes)
+}
+
+pub(crate) fn identity() -> CurveElem {
+    CurveElem::generator() - CurveElem::generator()
+}
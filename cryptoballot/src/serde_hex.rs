@@ -1,5 +1,8 @@
 // We define in our crate:
 use crate::Error;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
 use ed25519_dalek::PublicKey;
 use ed25519_dalek::Signature;
 use rsa::RSAPublicKey;
@@ -75,3 +78,77 @@ impl Hex<RSAPublicKey> for RSAPublicKeyHex {
         Ok(serde_cbor::from_slice(bytes)?)
     }
 }
+
+// a single-purpose type for use in `#[serde(with)]`
+pub enum RistrettoPointHex {}
+
+impl Hex<RistrettoPoint> for RistrettoPointHex {
+    type Error = Error;
+
+    fn create_bytes(point: &RistrettoPoint) -> Cow<[u8]> {
+        point.compress().to_bytes().to_vec().into()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<RistrettoPoint, Error> {
+        ristretto_point_from_bytes(bytes)
+    }
+}
+
+// a single-purpose type for use in `#[serde(with)]`
+pub enum EdwardsPointHex {}
+
+impl Hex<EdwardsPoint> for EdwardsPointHex {
+    type Error = Error;
+
+    fn create_bytes(point: &EdwardsPoint) -> Cow<[u8]> {
+        point.compress().to_bytes().to_vec().into()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<EdwardsPoint, Error> {
+        if bytes.len() != 32 {
+            return Err(Error::InvalidEdwardsPoint);
+        }
+
+        CompressedEdwardsY::from_slice(bytes)
+            .decompress()
+            .ok_or(Error::InvalidEdwardsPoint)
+    }
+}
+
+// a single-purpose type for use in `#[serde(with)]`
+pub enum ScalarHex {}
+
+impl Hex<Scalar> for ScalarHex {
+    type Error = Error;
+
+    fn create_bytes(scalar: &Scalar) -> Cow<[u8]> {
+        scalar.to_bytes().to_vec().into()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Scalar, Error> {
+        scalar_from_bytes(bytes)
+    }
+}
+
+/// Decode a compressed ristretto point
+pub(crate) fn ristretto_point_from_bytes(bytes: &[u8]) -> Result<RistrettoPoint, Error> {
+    if bytes.len() != 32 {
+        return Err(Error::InvalidRistrettoPoint);
+    }
+
+    CompressedRistretto::from_slice(bytes)
+        .decompress()
+        .ok_or(Error::InvalidRistrettoPoint)
+}
+
+/// Decode a canonically encoded scalar
+pub(crate) fn scalar_from_bytes(bytes: &[u8]) -> Result<Scalar, Error> {
+    if bytes.len() != 32 {
+        return Err(Error::InvalidScalar);
+    }
+
+    let mut scalar_bytes: [u8; 32] = [0; 32];
+    scalar_bytes.copy_from_slice(bytes);
+
+    Scalar::from_canonical_bytes(scalar_bytes).ok_or(Error::InvalidScalar)
+}
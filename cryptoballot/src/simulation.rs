@@ -0,0 +1,838 @@
+use crate::wellformed::to_cryptid;
+use crate::*;
+use cryptid::elgamal::Ciphertext;
+use cryptid::threshold::KeygenCommitment;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use indexmap::IndexMap;
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+/// The election to run in a simulation (see `simulate_election`)
+#[derive(Clone)]
+pub struct ElectionSpec {
+    pub num_trustees: u8,
+    pub trustees_threshold: u8,
+
+    /// Authenticators use Schnorr blind signatures. Every voter is authenticated by the first `authenticators_threshold`.
+    pub num_authenticators: u8,
+    pub authenticators_threshold: u8,
+
+    pub ballots: Vec<Ballot>,
+    pub contests: Vec<Contest>,
+    pub mix_config: Option<MixConfig>,
+
+    /// Voters are given each ballot in turn
+    pub num_voters: usize,
+
+    /// Key generation shares to corrupt, as (sender, recipient) trustee indexes. Each recipient complains about the
+    /// sender once the other trustees have posted their public keys, who then post new ones without the sender.
+    pub bad_shares: Vec<(u8, u8)>,
+
+    /// Reshare the key to new trustees once voting has ended, before the votes are mixed and decrypted
+    pub reshare: Option<ReshareSpec>,
+}
+
+/// The new trustees for a reshare in a simulation (see `TrusteeReshareTransaction`)
+///
+/// The first `trustees_threshold` of the election's trustees deal the reshare.
+#[derive(Clone)]
+pub struct ReshareSpec {
+    pub num_trustees: u8,
+    pub trustees_threshold: u8,
+}
+
+/// The outcome of a simulated election
+pub struct SimulationResult {
+    pub election: Signed<ElectionTransaction>,
+
+    /// The election's trustees, with their secret keys
+    pub trustees: Vec<(Trustee, SecretKey)>,
+
+    /// Every transaction in the election, in the order it was posted
+    pub transactions: Vec<SignedTransaction>,
+
+    /// The plaintext of every vote, by vote id
+    pub votes: Vec<(Identifier, Vec<PlaintextVote>)>,
+
+    pub tally: IndexMap<String, TallyResult>,
+}
+
+/// Run an entire election, from key generation to the tally.
+///
+/// Each transaction is validated against the store and then inserted into it with `insert`, exactly as a node would,
+/// so the simulation exercises the full protocol. Votes are chosen by `vote_generator`, which is called with the
+/// simulation's RNG, the voter's index, and the contest being voted on.
+///
+/// All keys, votes, encryptions, blind signatures and shuffles are drawn from an RNG seeded with `seed`. Timestamps are
+/// taken from the store's clock, so given the same seed and a fixed clock (see `MemStore::set_time`) the simulation
+/// produces exactly the same transactions every time.
+///
+/// Contests are decrypted by the first `trustees_threshold` trustees holding the key, after being mixed if the election
+/// has a mix-net. These are the trustees not disqualified by a complaint, or the new trustees if the spec has a reshare.
+pub fn simulate_election<S, F, V>(
+    seed: [u8; 32],
+    spec: &ElectionSpec,
+    store: &mut S,
+    insert: F,
+    mut vote_generator: V,
+) -> Result<SimulationResult, Error>
+where
+    S: Store,
+    F: FnMut(&mut S, SignedTransaction),
+    V: FnMut(&mut dyn RngCore, usize, &Contest) -> Vec<Selection>,
+{
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let mut board = Board {
+        store,
+        insert,
+        transactions: Vec::new(),
+    };
+
+    // Create the election authority, trustees and authenticators
+    let (authority_secret, authority_public) = keypair(&mut rng);
+
+    let mut trustees = Vec::with_capacity(spec.num_trustees as usize);
+    for index in 1..=spec.num_trustees {
+        let (secret, public_key) = keypair(&mut rng);
+        let trustee = Trustee {
+            index,
+            public_key,
+            num_trustees: spec.num_trustees as usize,
+            threshold: spec.trustees_threshold,
+        };
+        trustees.push((trustee, secret));
+    }
+
+    let ballot_ids: Vec<String> = spec
+        .ballots
+        .iter()
+        .map(|ballot| ballot.id.clone())
+        .collect();
+    let authenticators: Vec<_> = (0..spec.num_authenticators)
+        .map(|_| Authenticator::new_schnorr_with_rng(&mut rng, &ballot_ids))
+        .collect();
+
+    let mut election = ElectionTransaction::new(authority_public);
+    election.id = ElectionTransaction::build_id(rng.gen());
+    election.trustees = trustees
+        .iter()
+        .map(|(trustee, _)| trustee.clone())
+        .collect();
+    election.trustees_threshold = spec.trustees_threshold;
+    election.authenticators = authenticators
+        .iter()
+        .map(|(authenticator, _)| authenticator.clone())
+        .collect();
+    election.authenticators_threshold = spec.authenticators_threshold;
+    election.mix_config = spec.mix_config.clone();
+    election.ballots = spec.ballots.clone();
+    election.contests = spec.contests.clone();
+
+    let election = Signed::sign(&authority_secret, election)?;
+    let election_id = election.id;
+    board.post(election.clone())?;
+
+    // Distributed key generation
+    let mut commitments = Vec::with_capacity(trustees.len());
+    let mut x25519_public_keys = Vec::with_capacity(trustees.len());
+    for (trustee, secret) in trustees.iter() {
+        let x25519_public_key = trustee.x25519_public_key(secret, election_id);
+        let commitment = trustee.keygen_commitment(secret, election_id);
+        commitments.push((trustee.index, commitment.clone()));
+        x25519_public_keys.push((trustee.index, x25519_public_key));
+
+        let commitment_tx = KeyGenCommitmentTransaction::new(
+            election_id,
+            trustee.index,
+            trustee.public_key,
+            x25519_public_key,
+            commitment,
+        );
+        board.post(Signed::sign(secret, commitment_tx)?)?;
+    }
+
+    let mut all_shares = Vec::with_capacity(trustees.len());
+    for (trustee, secret) in trustees.iter() {
+        let mut shares = trustee.generate_shares(
+            &mut rng,
+            secret,
+            &x25519_public_keys,
+            election_id,
+            &commitments,
+        );
+        for (_, recipient) in spec
+            .bad_shares
+            .iter()
+            .filter(|(sender, _)| *sender == trustee.index)
+        {
+            let (_, recipient_public_key) = x25519_public_keys
+                .iter()
+                .find(|(index, _)| index == recipient)
+                .ok_or(ValidationError::TrusteeDoesNotExist(*recipient))?;
+            let shared_secret = trustee.shared_secret(secret, election_id, recipient_public_key);
+            let bad_share = to_cryptid(&curve25519_dalek::scalar::Scalar::one());
+            shares.insert(
+                *recipient,
+                EncryptedShare::new(&mut rng, shared_secret, &bad_share),
+            );
+        }
+        all_shares.push((trustee.index, shares.clone()));
+
+        let share_tx =
+            KeyGenShareTransaction::new(election_id, trustee.index, trustee.public_key, shares);
+        board.post(Signed::sign(secret, share_tx)?)?;
+    }
+
+    // The shares received by each trustee, from every trustee
+    let received_shares: Vec<Vec<(u8, EncryptedShare)>> = trustees
+        .iter()
+        .map(|(trustee, _)| {
+            all_shares
+                .iter()
+                .map(|(from, shares)| (*from, shares[&trustee.index].clone()))
+                .collect()
+        })
+        .collect();
+
+    // Trustees that were sent an invalid share complain about its sender, but only once the others have posted their
+    // public keys
+    let mut pubkeys = Vec::with_capacity(trustees.len());
+    let mut complaints = Vec::new();
+    for ((trustee, secret), shares) in trustees.iter().zip(received_shares.iter()) {
+        let invalid_shares = trustee.invalid_shares(
+            secret,
+            &x25519_public_keys,
+            &commitments,
+            shares,
+            election_id,
+        );
+        if !invalid_shares.is_empty() {
+            complaints.extend(
+                invalid_shares
+                    .into_iter()
+                    .map(|accused| (trustee, secret, accused)),
+            );
+            continue;
+        }
+
+        let public_key_tx = public_key_tx(
+            trustee,
+            secret,
+            &x25519_public_keys,
+            &commitments,
+            shares,
+            &[],
+            election_id,
+        )?;
+        pubkeys.push(public_key_tx.clone());
+        board.post(Signed::sign(secret, public_key_tx)?)?;
+    }
+
+    for (trustee, secret, accused) in complaints {
+        let (_, accused_public_key) = x25519_public_keys
+            .iter()
+            .find(|(index, _)| *index == accused)
+            .ok_or(ValidationError::TrusteeDoesNotExist(accused))?;
+        let shared_secret =
+            trustee.shared_secret_proof(&mut rng, secret, election_id, accused_public_key)?;
+        let complaint_tx = KeyGenComplaintTransaction::new(
+            election_id,
+            trustee.index,
+            trustee.public_key,
+            accused,
+            shared_secret,
+        );
+        board.post(Signed::sign(secret, complaint_tx)?)?;
+    }
+
+    // The trustees left after the complaints, with the shares they received from each other
+    let mut disqualified: Vec<u8> = disqualified_trustees(&*board.store, election_id)?
+        .into_iter()
+        .collect();
+    disqualified.sort_unstable();
+    let qualified_commitments: Vec<(u8, KeygenCommitment)> = commitments
+        .iter()
+        .filter(|(index, _)| !disqualified.contains(index))
+        .cloned()
+        .collect();
+    let keygen_trustees: Vec<(&Trustee, &SecretKey, Vec<(u8, EncryptedShare)>)> = trustees
+        .iter()
+        .zip(received_shares.iter())
+        .filter(|((trustee, _), _)| !disqualified.contains(&trustee.index))
+        .map(|((trustee, secret), shares)| {
+            let shares = shares
+                .iter()
+                .filter(|(from, _)| !disqualified.contains(from))
+                .cloned()
+                .collect();
+            (trustee, secret, shares)
+        })
+        .collect();
+
+    // The public keys posted before the complaints are stale, so every qualified trustee posts a new one
+    if !disqualified.is_empty() {
+        pubkeys.clear();
+        for (trustee, secret, shares) in keygen_trustees.iter() {
+            let public_key_tx = public_key_tx(
+                trustee,
+                secret,
+                &x25519_public_keys,
+                &qualified_commitments,
+                shares,
+                &disqualified,
+                election_id,
+            )?;
+            pubkeys.push(public_key_tx.clone());
+            board.post(Signed::sign(secret, public_key_tx)?)?;
+        }
+    }
+
+    let encryption_key_tx = EncryptionKeyTransaction::new(
+        election_id,
+        authority_public,
+        pubkeys
+            .first()
+            .ok_or(ValidationError::InvalidTrusteeThreshold)?
+            .public_key,
+    );
+    let encryption_key_tx = Signed::sign(&authority_secret, encryption_key_tx)?;
+    board.post(encryption_key_tx.clone())?;
+    let encryption_key = &encryption_key_tx.encryption_key;
+
+    // Voting
+    let mut votes = Vec::with_capacity(spec.num_voters);
+    let mut sessions = SchnorrSessions::new();
+    for voter in 0..spec.num_voters {
+        let ballot = election
+            .ballots
+            .get(voter % election.ballots.len().max(1))
+            .ok_or(ValidationError::BallotDoesNotExist)?;
+        let (voter_secret, voter_public) = keypair(&mut rng);
+
+        let mut plaintext = Vec::with_capacity(ballot.contests.len());
+        let mut encrypted_votes = Vec::with_capacity(ballot.contests.len());
+        for contest_index in ballot.contests.iter() {
+            let contest = election
+                .get_contest(*contest_index)
+                .ok_or(Error::CannotFindContet(*contest_index))?;
+            let selections = vote_generator(&mut rng, voter, contest);
+
+            encrypted_votes.push(encrypt_vote_with_proofs(
+                encryption_key,
+                election_id,
+                &voter_public,
+                contest,
+                selections.clone(),
+                &mut rng,
+            )?);
+            plaintext.push(PlaintextVote {
+                contest_index: *contest_index,
+                selections,
+            });
+        }
+
+        let mut vote = VoteTransaction::with_anonymous_key(
+            election_id,
+            ballot.id.clone(),
+            encrypted_votes,
+            voter_public,
+        );
+
+        // Blind-sign the voter's anonymous key with each authenticator
+        let auth_package = AuthPackage::new(election_id, ballot.id.clone(), voter_public);
+        for (authenticator, authn_secrets) in authenticators
+            .iter()
+            .take(spec.authenticators_threshold as usize)
+        {
+            let authn_public = &authenticator.public_keys[&ballot.id];
+            let voter_id = voter.to_string();
+            let commitment = sessions.open_with_rng(&mut rng, authenticator, &voter_id)?;
+            let (blinded_auth_package, unblinder) =
+                auth_package.blind_with_rng(&mut rng, authn_public, Some(&commitment))?;
+            let authentication = authenticator.authenticate(
+                &authn_secrets[&ballot.id],
+                &blinded_auth_package,
+                Some(sessions.close(authenticator, &voter_id)?),
+            )?;
+            vote.authentication
+                .push(authentication.unblind(authn_public, unblinder)?);
+        }
+
+        let vote = Signed::sign(&voter_secret, vote)?;
+        votes.push((vote.id, plaintext));
+        board.post(vote)?;
+    }
+
+    // Voting is over
+    let timestamp = board.store.current_time()?;
+    let voting_end_tx = VotingEndTransaction::new(election_id, authority_public, timestamp);
+    let voting_end_id = voting_end_tx.id;
+    board.post(Signed::sign(&authority_secret, voting_end_tx)?)?;
+
+    // Reshare the key to new trustees, who then mix and decrypt in place of the election's own trustees
+    let reshared = match &spec.reshare {
+        Some(reshare_spec) => {
+            let mut new_trustees = Vec::with_capacity(reshare_spec.num_trustees as usize);
+            let mut new_x25519_public_keys = Vec::with_capacity(reshare_spec.num_trustees as usize);
+            for index in 1..=reshare_spec.num_trustees {
+                let (secret, public_key) = keypair(&mut rng);
+                let trustee = Trustee {
+                    index,
+                    public_key,
+                    num_trustees: reshare_spec.num_trustees as usize,
+                    threshold: reshare_spec.trustees_threshold,
+                };
+                new_x25519_public_keys
+                    .push((index, trustee.x25519_public_key(&secret, election_id)));
+                new_trustees.push((trustee, secret));
+            }
+
+            let dealers = keygen_trustees
+                .iter()
+                .take(spec.trustees_threshold as usize);
+            let reshare_tx = TrusteeReshareTransaction::new(
+                election_id,
+                authority_public,
+                1,
+                dealers
+                    .clone()
+                    .map(|(trustee, _, _)| trustee.index)
+                    .collect(),
+                new_trustees
+                    .iter()
+                    .map(|(trustee, _)| trustee.clone())
+                    .collect(),
+                reshare_spec.trustees_threshold,
+                &new_x25519_public_keys,
+            );
+            let reshare_tx = Signed::sign(&authority_secret, reshare_tx)?;
+            board.post(reshare_tx.clone())?;
+
+            let mut reshare_shares = Vec::with_capacity(spec.trustees_threshold as usize);
+            for (trustee, secret, shares) in dealers {
+                let key_share =
+                    trustee.key_share(secret, &x25519_public_keys, shares, election_id)?;
+                let (commitment, shares) =
+                    trustee.reshare(&mut rng, secret, &key_share, &reshare_tx)?;
+                let share_tx = ReshareShareTransaction::new(
+                    election_id,
+                    1,
+                    trustee.index,
+                    trustee.public_key,
+                    commitment,
+                    shares,
+                );
+                reshare_shares.push(share_tx.clone());
+                board.post(Signed::sign(secret, share_tx)?)?;
+            }
+
+            let mut reshared_trustees = Vec::with_capacity(new_trustees.len());
+            for (trustee, secret) in new_trustees {
+                let key_share = trustee.receive_reshare(
+                    &secret,
+                    &x25519_public_keys,
+                    &reshare_shares,
+                    election_id,
+                )?;
+                let public_key_tx = ResharePublicKeyTransaction::new(
+                    election_id,
+                    1,
+                    trustee.index,
+                    trustee.public_key,
+                    verification_key(&key_share),
+                );
+                board.post(Signed::sign(&secret, public_key_tx)?)?;
+                reshared_trustees.push((trustee, secret, key_share));
+            }
+
+            Some(reshared_trustees)
+        }
+        None => None,
+    };
+
+    // The trustees holding the key, who mix and decrypt
+    let key_holders: Vec<(&Trustee, &SecretKey)> = match &reshared {
+        Some(reshared) => reshared
+            .iter()
+            .map(|(trustee, secret, _)| (trustee, secret))
+            .collect(),
+        None => keygen_trustees
+            .iter()
+            .map(|(trustee, secret, _)| (*trustee, *secret))
+            .collect(),
+    };
+    let key_holder_trustees: Vec<Trustee> = key_holders
+        .iter()
+        .map(|(trustee, _)| (*trustee).clone())
+        .collect();
+    let threshold = spec
+        .reshare
+        .as_ref()
+        .map_or(spec.trustees_threshold, |reshare| {
+            reshare.trustees_threshold
+        });
+
+    // Gather everything the trustees need to decrypt, mixing votes first if there is a mix-net
+    let mut targets = Vec::new();
+    for contest in election.contests.iter() {
+        if contest.homomorphic {
+            let (totals, num_votes) = homomorphic_aggregate(&*board.store, election_id, contest)?;
+            targets.push(DecryptionTarget {
+                upstream_id: voting_end_id,
+                upstream_index: 0,
+                contest_index: contest.index,
+                ciphertexts: totals,
+                homomorphic_votes: Some(num_votes),
+            });
+        } else if let Some(mix_config) = &election.mix_config {
+            let batches = mix_config.batch_votes(&*board.store, election_id, contest.index)?;
+            for (batch, batch_votes) in batches.into_iter().enumerate() {
+                let vote_ids: Vec<Identifier> = batch_votes.iter().map(|(id, _)| *id).collect();
+                let mut ciphertexts: Vec<Vec<Ciphertext>> =
+                    batch_votes.into_iter().map(|(_, vote)| vote).collect();
+
+                // Each mix is made by the trustee scheduled for its slot
+                let mut prev_mix_id = None;
+                let mut mixed_by = Vec::with_capacity(threshold as usize);
+                for mix_index in 0..threshold {
+                    let trustee_index =
+                        mix_config.slot_order(&key_holder_trustees, mix_index, &mixed_by)[0];
+                    let (trustee, secret) = key_holders
+                        .iter()
+                        .find(|(trustee, _)| trustee.index == trustee_index)
+                        .ok_or(ValidationError::TrusteeDoesNotExist(trustee_index))?;
+
+                    let (mixed_ciphertexts, proof) = mix(
+                        &mut rng,
+                        ciphertexts,
+                        encryption_key,
+                        trustee.index,
+                        mix_index,
+                        contest.index,
+                        batch as u32,
+                    )?;
+                    let mix_tx = MixTransaction::new(
+                        election_id,
+                        prev_mix_id,
+                        trustee,
+                        mix_index,
+                        timestamp,
+                        contest.index,
+                        batch as u32,
+                        vote_ids.clone(),
+                        mixed_ciphertexts,
+                        proof,
+                    );
+                    let mix_tx = Signed::sign(secret, mix_tx)?;
+                    board.post(mix_tx.clone())?;
+
+                    prev_mix_id = Some(mix_tx.id);
+                    ciphertexts = mix_tx.tx.mixed_ciphertexts;
+                    mixed_by.push(trustee.index);
+                }
+
+                if let Some(final_mix_id) = prev_mix_id {
+                    for (upstream_index, ciphertexts) in ciphertexts.into_iter().enumerate() {
+                        targets.push(DecryptionTarget {
+                            upstream_id: final_mix_id,
+                            upstream_index: upstream_index as u16,
+                            contest_index: contest.index,
+                            ciphertexts,
+                            homomorphic_votes: None,
+                        });
+                    }
+                }
+            }
+        } else {
+            for vote in latest_votes(&*board.store, election_id) {
+                let vote = vote?;
+                for encrypted_vote in vote.encrypted_votes {
+                    if encrypted_vote.contest_index == contest.index {
+                        targets.push(DecryptionTarget {
+                            upstream_id: vote.id,
+                            upstream_index: 0,
+                            contest_index: contest.index,
+                            ciphertexts: encrypted_vote.selections,
+                            homomorphic_votes: None,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    // Decryption
+    for target in targets {
+        let mut partials = Vec::with_capacity(threshold as usize);
+        for (index, (trustee, secret)) in key_holders.iter().enumerate().take(threshold as usize) {
+            let partial_tx = match &reshared {
+                Some(reshared) => {
+                    let key_share = &reshared[index].2;
+                    let mut reshared_decryption = Vec::with_capacity(target.ciphertexts.len());
+                    for ciphertext in target.ciphertexts.iter() {
+                        reshared_decryption
+                            .push(ResharedDecryptShare::new(&mut rng, key_share, ciphertext)?);
+                    }
+
+                    PartialDecryptionTransaction::new_reshared(
+                        election_id,
+                        target.upstream_id,
+                        target.upstream_index,
+                        trustee.index,
+                        target.contest_index,
+                        trustee.public_key,
+                        reshared_decryption,
+                    )
+                }
+                None => {
+                    let mut partial_decryption = Vec::with_capacity(target.ciphertexts.len());
+                    for ciphertext in target.ciphertexts.iter() {
+                        partial_decryption.push(trustee.partial_decrypt(
+                            &mut rng,
+                            secret,
+                            &x25519_public_keys,
+                            &qualified_commitments,
+                            &keygen_trustees[index].2,
+                            ciphertext,
+                            election_id,
+                        )?);
+                    }
+
+                    PartialDecryptionTransaction::new(
+                        election_id,
+                        target.upstream_id,
+                        target.upstream_index,
+                        trustee.index,
+                        target.contest_index,
+                        trustee.public_key,
+                        partial_decryption,
+                    )
+                }
+            };
+            partials.push(partial_tx.clone());
+            board.post(Signed::sign(secret, partial_tx)?)?;
+        }
+
+        let contest = election
+            .get_contest(target.contest_index)
+            .ok_or(Error::CannotFindContet(target.contest_index))?;
+        let decrypted_vote = match (target.homomorphic_votes, reshared.is_some()) {
+            (Some(num_votes), true) => {
+                decrypt_totals_reshared(contest, &target.ciphertexts, num_votes, &partials)?
+            }
+            (Some(num_votes), false) => decrypt_totals(
+                contest,
+                &target.ciphertexts,
+                num_votes,
+                election.trustees_threshold,
+                &election.trustees,
+                &pubkeys,
+                &partials,
+            )?,
+            (None, true) => decrypt_vote_reshared(&target.ciphertexts, &partials)?,
+            (None, false) => decrypt_vote(
+                &target.ciphertexts,
+                election.trustees_threshold,
+                &election.trustees,
+                &pubkeys,
+                &partials,
+            )?,
+        };
+
+        let decryption_tx = DecryptionTransaction::new(
+            election_id,
+            target.upstream_id,
+            target.contest_index,
+            target.upstream_index,
+            partials
+                .iter()
+                .map(|partial| partial.trustee_index)
+                .collect(),
+            decrypted_vote,
+        );
+        board.post(Signed::sign(&trustees[0].1, decryption_tx)?)?;
+    }
+
+    // Tally
+    let tally = tally_election(&*board.store, &election)?;
+    let tally_tx = TallyTransaction::new(election_id, tally.clone());
+    board.post(Signed::sign(&authority_secret, tally_tx)?)?;
+
+    Ok(SimulationResult {
+        election,
+        trustees,
+        transactions: board.transactions,
+        votes,
+        tally,
+    })
+}
+
+// A trustee's KeyGenPublicKey transaction, listing the disqualified trustees left out of `commitments` and `shares`
+fn public_key_tx(
+    trustee: &Trustee,
+    secret: &SecretKey,
+    x25519_public_keys: &[(u8, x25519_dalek::PublicKey)],
+    commitments: &[(u8, KeygenCommitment)],
+    shares: &[(u8, EncryptedShare)],
+    disqualified: &[u8],
+    election_id: Identifier,
+) -> Result<KeyGenPublicKeyTransaction, ValidationError> {
+    let (public_key, public_key_proof) = trustee.generate_public_key(
+        secret,
+        x25519_public_keys,
+        commitments,
+        shares,
+        election_id,
+    )?;
+
+    Ok(KeyGenPublicKeyTransaction::new(
+        election_id,
+        trustee.index,
+        trustee.public_key,
+        public_key,
+        public_key_proof,
+        disqualified.to_vec(),
+    ))
+}
+
+// Validates transactions and inserts them into the store, keeping a copy of each
+struct Board<'a, S, F> {
+    store: &'a mut S,
+    insert: F,
+    transactions: Vec<SignedTransaction>,
+}
+
+impl<'a, S, F> Board<'a, S, F>
+where
+    S: Store,
+    F: FnMut(&mut S, SignedTransaction),
+{
+    fn post<T: Into<SignedTransaction>>(&mut self, tx: T) -> Result<(), ValidationError> {
+        let tx = tx.into();
+        tx.validate(&*self.store)?;
+        (self.insert)(&mut *self.store, tx.clone());
+        self.transactions.push(tx);
+
+        Ok(())
+    }
+}
+
+// Ciphertexts to be decrypted, and the transaction they come from
+struct DecryptionTarget {
+    upstream_id: Identifier,
+    upstream_index: u16,
+    contest_index: u32,
+    ciphertexts: Vec<Ciphertext>,
+
+    // For homomorphic contests, the number of votes summed into the ciphertexts
+    homomorphic_votes: Option<usize>,
+}
+
+fn keypair<R: CryptoRng + RngCore>(rng: &mut R) -> (SecretKey, PublicKey) {
+    let Keypair { public, secret } = Keypair::generate(rng);
+    (secret, public)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn contest(index: u32, id: &str, homomorphic: bool) -> Contest {
+        Contest {
+            id: id.to_string(),
+            index,
+            contest_type: ContestType::Plurality,
+            write_in: false,
+            num_winners: 1,
+            candidates: ["Alice", "Bob", "Carol"]
+                .iter()
+                .map(|name| Candidate {
+                    id: name.to_string(),
+                    properties: IndexMap::new(),
+                })
+                .collect(),
+            homomorphic,
+            max_score: None,
+            properties: IndexMap::new(),
+        }
+    }
+
+    #[test]
+    fn simulated_election_is_deterministic() {
+        let spec = ElectionSpec {
+            num_trustees: 3,
+            trustees_threshold: 2,
+            num_authenticators: 2,
+            authenticators_threshold: 2,
+            ballots: vec![Ballot {
+                id: "BALLOT".to_string(),
+                contests: vec![0, 1],
+                properties: IndexMap::new(),
+            }],
+            contests: vec![contest(0, "MIXED", false), contest(1, "SUMMED", true)],
+            mix_config: Some(MixConfig {
+                timeout_secs: 600,
+                batch_size: Some(3),
+            }),
+            num_voters: 5,
+            bad_shares: vec![],
+            reshare: None,
+        };
+
+        // The first voter always votes for Alice, everyone else votes at random
+        let run = |seed: [u8; 32]| {
+            let mut store = MemStore::default();
+            store.set_time(1_600_000_000);
+            simulate_election(
+                seed,
+                &spec,
+                &mut store,
+                |store, tx| store.set(tx),
+                |rng: &mut dyn RngCore, voter: usize, contest: &Contest| {
+                    let candidate = match voter {
+                        0 => 0,
+                        _ => rng.gen_range(0, contest.candidates.len()),
+                    };
+                    vec![Selection {
+                        write_in: false,
+                        score: 0,
+                        selection: contest.candidates[candidate].id.clone(),
+                    }]
+                },
+            )
+            .unwrap()
+        };
+
+        let result = run([7; 32]);
+        assert_eq!(result.votes.len(), 5);
+
+        // Both the mixed and the homomorphic contest are tallied from the votes that were cast
+        for (contest_index, contest_id) in [(0, "MIXED"), (1, "SUMMED")].iter() {
+            let tally = &result.tally[*contest_id];
+            assert_eq!(tally.num_votes, 5);
+
+            for candidate in ["Alice", "Bob", "Carol"].iter() {
+                let expected = result
+                    .votes
+                    .iter()
+                    .filter(|(_, vote)| vote[*contest_index].selections[0].selection == *candidate)
+                    .count();
+                let total = tally.totals.get(*candidate).cloned().unwrap_or_default();
+                assert_eq!(total, Decimal::from(expected));
+            }
+        }
+
+        // The same seed gives exactly the same election, a different seed does not
+        let as_bytes = |txs: &[SignedTransaction]| -> Vec<Vec<u8>> {
+            txs.iter().map(|tx| tx.as_bytes()).collect()
+        };
+        let again = run([7; 32]);
+        assert_eq!(
+            as_bytes(&result.transactions),
+            as_bytes(&again.transactions)
+        );
+        let other = run([8; 32]);
+        assert_ne!(result.election.id, other.election.id);
+    }
+}
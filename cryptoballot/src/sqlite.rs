@@ -0,0 +1,309 @@
+use crate::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::path::Path;
+
+// Number of transactions read from the database at a time when iterating over a range
+const RANGE_PAGE_SIZE: i64 = 256;
+
+/// A persistent store backed by an SQLite database
+///
+/// Transactions are keyed by their 32 byte binary identifier. Since identifiers sort by election, then transaction
+/// type, then unique-info, range scans over an election or transaction type are ordered index scans.
+///
+/// Requires the `sqlite` feature.
+pub struct SqliteStore {
+    conn: Connection,
+    time: Option<u64>,
+}
+
+impl SqliteStore {
+    /// Open (or create) an SQLite store at the given path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Open a temporary store that is discarded when dropped
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS transactions (
+                id BLOB PRIMARY KEY NOT NULL,
+                tx BLOB NOT NULL
+            ) WITHOUT ROWID;
+            CREATE TABLE IF NOT EXISTS board_leaves (
+                position INTEGER PRIMARY KEY,
+                id BLOB UNIQUE NOT NULL,
+                leaf BLOB NOT NULL
+            );",
+        )?;
+
+        Ok(SqliteStore { conn, time: None })
+    }
+
+    /// Insert a transaction, replacing any existing transaction with the same id
+    pub fn set(&self, tx: SignedTransaction) -> Result<(), StoreError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO transactions (id, tx) VALUES (?1, ?2)",
+            params![&tx.id().to_array()[..], tx.as_bytes()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Fix the time reported by `current_time`, instead of using the system clock
+    pub fn set_time(&mut self, time: u64) {
+        self.time = Some(time);
+    }
+
+    // Read the next page of transactions in a range
+    fn page(
+        &self,
+        after: Option<&[u8]>,
+        start: &[u8],
+        end_inclusive: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, rusqlite::Error> {
+        let read_row = |row: &rusqlite::Row| -> rusqlite::Result<(Vec<u8>, Vec<u8>)> {
+            Ok((row.get(0)?, row.get(1)?))
+        };
+
+        // After the first page, continue from the last transaction read
+        let page = match after {
+            Some(after) => {
+                let mut stmt = self.conn.prepare_cached(
+                    "SELECT id, tx FROM transactions WHERE id > ?1 AND id <= ?2 ORDER BY id LIMIT ?3",
+                )?;
+                let rows =
+                    stmt.query_map(params![after, end_inclusive, RANGE_PAGE_SIZE], read_row)?;
+                rows.collect::<Result<_, _>>()?
+            }
+            None => {
+                let mut stmt = self.conn.prepare_cached(
+                    "SELECT id, tx FROM transactions WHERE id >= ?1 AND id <= ?2 ORDER BY id LIMIT ?3",
+                )?;
+                let rows =
+                    stmt.query_map(params![start, end_inclusive, RANGE_PAGE_SIZE], read_row)?;
+                rows.collect::<Result<_, _>>()?
+            }
+        };
+
+        Ok(page)
+    }
+}
+
+impl Store for SqliteStore {
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        let tx: Option<Vec<u8>> = self
+            .conn
+            .prepare_cached("SELECT tx FROM transactions WHERE id = ?1")?
+            .query_row(params![&id.to_array()[..]], |row| row.get(0))
+            .optional()?;
+
+        match tx {
+            Some(tx) => SignedTransaction::from_bytes(&tx)
+                .map(Some)
+                .map_err(|_| StoreError::Corrupt(id.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_> {
+        Box::new(SqliteRange {
+            store: self,
+            start: start.to_array(),
+            end: end_inclusive.to_array(),
+            last: None,
+            page: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    fn current_time(&self) -> Result<u64, StoreError> {
+        Ok(self.time.unwrap_or_else(unix_time))
+    }
+}
+
+impl BoardStore for SqliteStore {
+    fn board_leaves(&self) -> Result<Vec<(Identifier, MerkleHash)>, StoreError> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id, leaf FROM board_leaves ORDER BY position")?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+
+        rows.map(|row| {
+            let (id, leaf) = row?;
+            let corrupt = || StoreError::Corrupt(hex::encode(&id));
+            let tx_id = id
+                .as_slice()
+                .try_into()
+                .ok()
+                .and_then(|id| Identifier::from_array(id).ok())
+                .ok_or_else(corrupt)?;
+            let leaf = leaf.as_slice().try_into().map_err(|_| corrupt())?;
+
+            Ok((tx_id, MerkleHash(leaf)))
+        })
+        .collect()
+    }
+
+    fn append_leaf(&mut self, tx: SignedTransaction, leaf: MerkleHash) -> Result<(), StoreError> {
+        let id = tx.id().to_array();
+
+        // The transaction and its leaf are committed together
+        let db_tx = self.conn.transaction()?;
+        db_tx.execute(
+            "INSERT OR REPLACE INTO transactions (id, tx) VALUES (?1, ?2)",
+            params![&id[..], tx.as_bytes()],
+        )?;
+        db_tx.execute(
+            "INSERT INTO board_leaves (id, leaf) VALUES (?1, ?2)",
+            params![&id[..], &leaf.0[..]],
+        )?;
+        db_tx.commit()?;
+
+        Ok(())
+    }
+}
+
+// Reads a range of transactions a page at a time, so that the whole range never needs to be in memory
+struct SqliteRange<'a> {
+    store: &'a SqliteStore,
+    start: [u8; 32],
+    end: [u8; 32],
+    last: Option<Vec<u8>>,
+    page: VecDeque<(Vec<u8>, Vec<u8>)>,
+    done: bool,
+}
+
+impl<'a> Iterator for SqliteRange<'a> {
+    type Item = Result<SignedTransaction, StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            match self
+                .store
+                .page(self.last.as_deref(), &self.start, &self.end)
+            {
+                Ok(page) => {
+                    self.done = (page.len() as i64) < RANGE_PAGE_SIZE;
+                    self.page = page.into();
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+
+        let (id, tx) = self.page.pop_front()?;
+        let decoded =
+            SignedTransaction::from_bytes(&tx).map_err(|_| StoreError::Corrupt(hex::encode(&id)));
+        self.last = Some(id);
+
+        Some(decoded)
+    }
+}
+
+impl From<rusqlite::Error> for StoreError {
+    fn from(err: rusqlite::Error) -> Self {
+        StoreError::Backend(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqlite_store_persists_and_ranges_in_order() {
+        let path =
+            std::env::temp_dir().join(format!("cryptoballot-{}.sqlite", uuid::Uuid::new_v4()));
+
+        let (authority_secret, authority_public) = generate_keypair();
+        let election = ElectionTransaction::new(authority_public);
+        let election = Signed::sign(&authority_secret, election).unwrap();
+
+        // More voting-ends than fit in a single page, inserted out of order
+        let num_txs = RANGE_PAGE_SIZE as u64 * 2 + 1;
+        let mut expected = Vec::new();
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            store.set(election.clone().into()).unwrap();
+            for i in (0..num_txs).rev() {
+                let mut voting_end = VotingEndTransaction::new(election.id, authority_public, i);
+                voting_end.id.unique_info[8..16].copy_from_slice(&i.to_be_bytes());
+                expected.push(voting_end.id);
+                let voting_end = Signed::sign(&authority_secret, voting_end).unwrap();
+                store.set(voting_end.into()).unwrap();
+            }
+        }
+        expected.sort();
+
+        // Re-open the store and read the transactions back
+        let store = SqliteStore::open(&path).unwrap();
+        assert!(store.get_election(election.id).is_ok());
+        assert!(store
+            .get_transaction(Identifier::new(election.id, TransactionType::Tally, None))
+            .unwrap()
+            .is_none());
+
+        let ids: Vec<Identifier> = store
+            .get_multiple(election.id, TransactionType::VotingEnd)
+            .map(|tx| tx.unwrap().id())
+            .collect();
+        assert_eq!(ids, expected);
+
+        // Ranges are inclusive at both ends
+        let ids: Vec<Identifier> = store
+            .range(expected[1], expected[3])
+            .map(|tx| tx.unwrap().id())
+            .collect();
+        assert_eq!(ids, expected[1..=3].to_vec());
+
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bulletin_board_survives_restart() {
+        let path =
+            std::env::temp_dir().join(format!("cryptoballot-{}.sqlite", uuid::Uuid::new_v4()));
+
+        let (authority_secret, authority_public) = generate_keypair();
+        let elections: Vec<SignedTransaction> = (0..3)
+            .map(|_| {
+                let election = ElectionTransaction::new(authority_public);
+                Signed::sign(&authority_secret, election).unwrap().into()
+            })
+            .collect();
+
+        let roots: Vec<BoardRoot> = {
+            let mut board = BulletinBoard::new(SqliteStore::open(&path).unwrap()).unwrap();
+            for election in &elections {
+                board.post(election.clone()).unwrap();
+            }
+            elections.iter().map(|e| board.root(e.id())).collect()
+        };
+
+        // Re-open the board over the same database, the trees are unchanged and still append-only
+        let mut board = BulletinBoard::new(SqliteStore::open(&path).unwrap()).unwrap();
+        for (election, root) in elections.iter().zip(&roots) {
+            assert_eq!(&board.root(election.id()), root);
+            let proof = board.prove(election.id()).unwrap();
+            proof.verify(election, root).unwrap();
+            assert!(matches!(
+                board.post(election.clone()),
+                Err(ValidationError::AlreadyPosted(_))
+            ));
+        }
+
+        drop(board);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -15,38 +15,68 @@ impl TransactionNotFound {
     }
 }
 
+/// An error reading from a transaction store
+#[derive(Debug, Clone, Error)]
+pub enum StoreError {
+    #[error("{0}")]
+    NotFound(#[from] TransactionNotFound),
+
+    #[error("cryptoballot: stored transaction {0} cannot be decoded")]
+    Corrupt(String),
+
+    #[error("cryptoballot: store backend error: {0}")]
+    Backend(String),
+
+    #[error("cryptoballot: store has no agreed time to validate time-dependent transactions")]
+    TimeUnavailable,
+}
+
+/// An iterator over transactions read from a store
+pub type TransactionIter<'a> = Box<dyn Iterator<Item = Result<SignedTransaction, StoreError>> + 'a>;
+
 /// A transaction store
+///
+/// Reads are fallible, so that backend errors (I/O, corrupt data) are reported rather than being treated as missing transactions.
 pub trait Store {
     /// Get a transaction of an unknown type
-    fn get_transaction(&self, id: Identifier) -> Option<SignedTransaction>;
+    ///
+    /// Returns `Ok(None)` if the transaction does not exist.
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError>;
 
-    fn range(&self, start: Identifier, end_inclusive: Identifier) -> Vec<SignedTransaction>;
+    /// Iterate over every transaction with an id between `start` and `end_inclusive`, in ascending id order
+    ///
+    /// Transactions are read lazily, so large ranges (such as every vote in an election) need not fit in memory.
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_>;
 
+    /// Iterate over every transaction of the given type in an election, in ascending id order
     fn get_multiple(
         &self,
         election_id: Identifier,
         tx_type: TransactionType,
-    ) -> Vec<SignedTransaction> {
+    ) -> TransactionIter<'_> {
         let start = Identifier::start(election_id, tx_type, None);
         let end = Identifier::end(election_id, tx_type, None);
 
         self.range(start, end)
     }
 
+    /// The current time, in seconds since the unix epoch
+    ///
+    /// This is used to validate time-dependent transactions, such as voting windows and mix timeouts. Stores backed by a
+    /// blockchain should return the time agreed upon by consensus (e.g. the block time) so that every
+    /// node validates these transactions identically, and return `StoreError::TimeUnavailable` rather than fall back to a
+    /// local clock when there is no agreed time. Defaults to the local system clock.
+    fn current_time(&self) -> Result<u64, StoreError> {
+        Ok(unix_time())
+    }
+
     // TODO: Macro these methods
 
     /// Get an election transaction
-    fn get_election(
-        &self,
-        id: Identifier,
-    ) -> Result<Signed<ElectionTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::Election(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(id, TransactionType::Election)),
-            },
-            None => Err(TransactionNotFound::new(id, TransactionType::Election)),
+    fn get_election(&self, id: Identifier) -> Result<Signed<ElectionTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::Election(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::Election).into()),
         }
     }
 
@@ -54,44 +84,26 @@ pub trait Store {
     fn get_keygen_public_key(
         &self,
         id: Identifier,
-    ) -> Result<Signed<KeyGenPublicKeyTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::KeyGenPublicKey(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(
-                    id,
-                    TransactionType::KeyGenPublicKey,
-                )),
-            },
-            None => Err(TransactionNotFound::new(
-                id,
-                TransactionType::KeyGenPublicKey,
-            )),
+    ) -> Result<Signed<KeyGenPublicKeyTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::KeyGenPublicKey(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::KeyGenPublicKey).into()),
         }
     }
 
     /// Get an Vote transaction
-    fn get_vote(&self, id: Identifier) -> Result<Signed<VoteTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::Vote(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(id, TransactionType::Vote)),
-            },
-            None => Err(TransactionNotFound::new(id, TransactionType::Vote)),
+    fn get_vote(&self, id: Identifier) -> Result<Signed<VoteTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::Vote(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::Vote).into()),
         }
     }
 
     /// Get an Mix transaction
-    fn get_mix(&self, id: Identifier) -> Result<Signed<MixTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::Mix(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(id, TransactionType::Mix)),
-            },
-            None => Err(TransactionNotFound::new(id, TransactionType::Mix)),
+    fn get_mix(&self, id: Identifier) -> Result<Signed<MixTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::Mix(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::Mix).into()),
         }
     }
 
@@ -99,35 +111,26 @@ pub trait Store {
     fn get_partial_decryption(
         &self,
         id: Identifier,
-    ) -> Result<Signed<PartialDecryptionTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::PartialDecryption(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(
-                    id,
-                    TransactionType::PartialDecryption,
-                )),
-            },
-            None => Err(TransactionNotFound::new(
-                id,
-                TransactionType::PartialDecryption,
-            )),
+    ) -> Result<Signed<PartialDecryptionTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::PartialDecryption(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::PartialDecryption).into()),
         }
     }
 
     /// Get a Decryption transaction
-    fn get_decryption(
-        &self,
-        id: Identifier,
-    ) -> Result<Signed<DecryptionTransaction>, TransactionNotFound> {
-        let tx = self.get_transaction(id);
-        match tx {
-            Some(tx) => match tx {
-                SignedTransaction::Decryption(e) => Ok(e),
-                _ => Err(TransactionNotFound::new(id, TransactionType::Decryption)),
-            },
-            None => Err(TransactionNotFound::new(id, TransactionType::Decryption)),
+    fn get_decryption(&self, id: Identifier) -> Result<Signed<DecryptionTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::Decryption(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::Decryption).into()),
+        }
+    }
+
+    /// Get a Tally transaction
+    fn get_tally(&self, id: Identifier) -> Result<Signed<TallyTransaction>, StoreError> {
+        match self.get_transaction(id)? {
+            Some(SignedTransaction::Tally(e)) => Ok(e),
+            _ => Err(TransactionNotFound::new(id, TransactionType::Tally).into()),
         }
     }
 }
@@ -135,43 +138,36 @@ pub trait Store {
 /// A simple store that uses an in-memory BTreeMap
 #[derive(Default, Clone)]
 pub struct MemStore {
-    pub(crate) inner: BTreeMap<String, SignedTransaction>,
+    pub(crate) inner: BTreeMap<[u8; 32], SignedTransaction>,
+    pub(crate) time: Option<u64>,
+    pub(crate) board: Vec<(Identifier, MerkleHash)>,
 }
 
 impl MemStore {
     pub fn set(&mut self, tx: SignedTransaction) {
-        self.inner.insert(tx.id().to_string(), tx);
+        self.inner.insert(tx.id().to_array(), tx);
+    }
+
+    /// Fix the time reported by `current_time`, instead of using the system clock
+    pub fn set_time(&mut self, time: u64) {
+        self.time = Some(time);
     }
 }
 
 impl Store for MemStore {
-    fn get_transaction(&self, id: Identifier) -> Option<SignedTransaction> {
-        let key = id.to_string();
-        self.inner.get(&key).cloned()
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        Ok(self.inner.get(&id.to_array()).cloned())
     }
 
-    fn range(&self, start: Identifier, end_inclusive: Identifier) -> Vec<SignedTransaction> {
-        let mut results = Vec::new();
-
-        let start = start.to_string();
-        let end = end_inclusive.to_string();
-
-        // TODO: Go back to using array keys (faster)
-        // OLD CODE For Array Keys:
-        //let election_id = election_id.to_array();
-        //let mut start: [u8; 32] = [0; 32];
-        //start[..15].copy_from_slice(&election_id[..15]);
-        //start[16] = tx_type as u8;
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_> {
+        let start = start.to_array();
+        let end = end_inclusive.to_array();
 
-        // End at the next transaction type
-        //let mut end: [u8; 32] = [0; 32];
-        //end[..15].copy_from_slice(&election_id[..15]);
-        //end[16] = (tx_type as u8) + 1;
+        Box::new(self.inner.range(start..=end).map(|(_, v)| Ok(v.clone())))
+    }
 
-        for (_, v) in self.inner.range(start..=end) {
-            results.push(v.clone())
-        }
-        results
+    fn current_time(&self) -> Result<u64, StoreError> {
+        Ok(self.time.unwrap_or_else(unix_time))
     }
 }
 
@@ -184,3 +180,59 @@ impl From<Vec<SignedTransaction>> for MemStore {
         memstore
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A store whose backend always fails
+    struct BrokenStore;
+
+    impl Store for BrokenStore {
+        fn get_transaction(
+            &self,
+            _id: Identifier,
+        ) -> Result<Option<SignedTransaction>, StoreError> {
+            Err(StoreError::Backend("disk on fire".to_string()))
+        }
+
+        fn range(&self, _start: Identifier, _end_inclusive: Identifier) -> TransactionIter<'_> {
+            Box::new(std::iter::once(Err(StoreError::Backend(
+                "disk on fire".to_string(),
+            ))))
+        }
+    }
+
+    #[test]
+    fn backend_errors_are_not_missing_transactions() {
+        let (authority_secret, authority_public) = generate_keypair();
+        let election_id = ElectionTransaction::new(authority_public).id;
+
+        // Missing transactions are reported as not found
+        let store = MemStore::default();
+        assert!(store.get_transaction(election_id).unwrap().is_none());
+        assert!(matches!(
+            store.get_election(election_id),
+            Err(StoreError::NotFound(_))
+        ));
+        assert_eq!(latest_votes(&store, election_id).count(), 0);
+
+        // Backend errors are surfaced, rather than being treated as missing transactions
+        let store = BrokenStore;
+        assert!(matches!(
+            store.get_election(election_id),
+            Err(StoreError::Backend(_))
+        ));
+        assert!(matches!(
+            latest_votes(&store, election_id).next(),
+            Some(Err(StoreError::Backend(_)))
+        ));
+
+        let voting_end = VotingEndTransaction::new(election_id, authority_public, 0);
+        let voting_end = Signed::sign(&authority_secret, voting_end).unwrap();
+        assert!(matches!(
+            voting_end.validate(&store),
+            Err(ValidationError::Store(StoreError::Backend(_)))
+        ));
+    }
+}
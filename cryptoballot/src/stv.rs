@@ -0,0 +1,247 @@
+use indexmap::IndexMap;
+use rust_decimal::prelude::*;
+use std::cmp::Ordering;
+
+/// The quota a candidate must reach to be elected in an Instant-Runoff or Single Transferable Vote count.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quota {
+    /// The Droop quota: `floor(valid-votes / (seats + 1)) + 1`. This is the quota used by most STV elections.
+    Droop,
+
+    /// The Hare quota: `valid-votes / seats`. It favours smaller parties at the expense of larger ones.
+    Hare,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Quota::Droop
+    }
+}
+
+impl Quota {
+    /// Calculate the quota for the given number of valid votes and seats
+    pub fn value(&self, num_votes: Decimal, num_seats: usize) -> Decimal {
+        match self {
+            Quota::Droop => (num_votes / Decimal::from(num_seats + 1)).floor() + Decimal::one(),
+            Quota::Hare => num_votes / Decimal::from(num_seats),
+        }
+    }
+}
+
+/// The round-by-round report of an Instant-Runoff or Single Transferable Vote count.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferReport {
+    pub quota: Decimal,
+    pub rounds: Vec<TransferRound>,
+}
+
+/// A single round of an Instant-Runoff or Single Transferable Vote count.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferRound {
+    /// Votes held by each continuing candidate at the start of the round
+    pub totals: IndexMap<String, Decimal>,
+
+    /// Candidates that reached the quota and were elected in this round, in order of election
+    pub elected: Vec<String>,
+
+    /// Candidates that were eliminated in this round
+    pub eliminated: Vec<String>,
+
+    /// Votes transferred to each continuing candidate at the end of this round
+    pub transfers: IndexMap<String, Decimal>,
+
+    /// Votes that have been exhausted because their ballot has no further continuing preferences
+    pub exhausted: Decimal,
+}
+
+/// Count ranked ballots using the Single Transferable Vote.
+///
+/// Each ballot is a list of candidates in order of preference. Surpluses are transferred fractionally
+/// (Gregory method) and ties are broken by looking back through earlier rounds, then by candidate-id.
+/// Instant-Runoff is the single-seat case.
+///
+/// `candidates` are the contest's candidates, including those that no ballot ranks. Write-ins on ballots are counted too.
+///
+/// Returns every candidate in ranked order (elected candidates first, in order of election) and the round-by-round report.
+pub fn count_stv(
+    num_seats: usize,
+    quota: Quota,
+    candidates: Vec<String>,
+    ballots: Vec<Vec<String>>,
+) -> (Vec<String>, TransferReport) {
+    // Remove repeated preferences and empty ballots
+    let mut ballots: Vec<(Vec<String>, Decimal)> = ballots
+        .into_iter()
+        .map(|ballot| {
+            let mut preferences: Vec<String> = Vec::with_capacity(ballot.len());
+            for candidate in ballot {
+                if !preferences.contains(&candidate) {
+                    preferences.push(candidate);
+                }
+            }
+            (preferences, Decimal::one())
+        })
+        .filter(|(preferences, _)| !preferences.is_empty())
+        .collect();
+
+    let mut continuing: Vec<String> = candidates
+        .into_iter()
+        .chain(
+            ballots
+                .iter()
+                .flat_map(|(preferences, _)| preferences.iter().cloned()),
+        )
+        .collect();
+    continuing.sort();
+    continuing.dedup();
+
+    let quota = if num_seats == 0 {
+        Decimal::zero()
+    } else {
+        quota.value(Decimal::from(ballots.len()), num_seats)
+    };
+
+    let mut elected: Vec<String> = Vec::with_capacity(num_seats);
+    let mut eliminated: Vec<String> = Vec::new();
+    let mut rounds: Vec<TransferRound> = Vec::new();
+
+    while !continuing.is_empty() {
+        // Allocate each ballot to its highest continuing preference
+        let mut totals: IndexMap<String, Decimal> = continuing
+            .iter()
+            .map(|candidate| (candidate.clone(), Decimal::zero()))
+            .collect();
+        let mut exhausted = Decimal::zero();
+        let holders: Vec<Option<String>> = ballots
+            .iter()
+            .map(|(preferences, _)| highest_continuing(preferences, &continuing))
+            .collect();
+        for ((_, weight), holder) in ballots.iter().zip(holders.iter()) {
+            match holder {
+                Some(candidate) => *totals.get_mut(candidate).unwrap() += *weight,
+                None => exhausted += *weight,
+            }
+        }
+
+        // Record what was transferred at the end of the previous round
+        if let Some(previous) = rounds.last_mut() {
+            previous.transfers = totals
+                .iter()
+                .map(|(candidate, total)| {
+                    let before = previous.totals.get(candidate).cloned().unwrap_or_default();
+                    (candidate.clone(), *total - before)
+                })
+                .collect();
+        }
+
+        let mut round = TransferRound {
+            totals: totals.clone(),
+            elected: vec![],
+            eliminated: vec![],
+            transfers: IndexMap::new(),
+            exhausted,
+        };
+
+        let seats_left = num_seats - elected.len();
+        if seats_left == 0 {
+            rounds.push(round);
+            break;
+        }
+
+        // If there are only as many continuing candidates as seats left, they are all elected
+        if continuing.len() <= seats_left {
+            let mut remaining = continuing.clone();
+            remaining.sort_by(|a, b| compare_candidates(b, a, &totals, &rounds));
+            round.elected = remaining.clone();
+            elected.append(&mut remaining);
+            continuing.clear();
+            rounds.push(round);
+            break;
+        }
+
+        let mut reached_quota: Vec<String> = continuing
+            .iter()
+            .filter(|candidate| totals[*candidate] >= quota)
+            .cloned()
+            .collect();
+
+        if !reached_quota.is_empty() {
+            reached_quota.sort_by(|a, b| compare_candidates(b, a, &totals, &rounds));
+            reached_quota.truncate(seats_left);
+
+            // Transfer the surplus of each elected candidate at a fractional value
+            for ((_, weight), holder) in ballots.iter_mut().zip(holders.iter()) {
+                if let Some(candidate) = holder {
+                    if reached_quota.contains(candidate) {
+                        let total = totals[candidate];
+                        *weight = *weight * (total - quota) / total;
+                    }
+                }
+            }
+
+            continuing.retain(|candidate| !reached_quota.contains(candidate));
+            round.elected = reached_quota.clone();
+            elected.append(&mut reached_quota);
+        } else {
+            // Eliminate the candidate with the fewest votes and transfer their votes at full value
+            let lowest = continuing
+                .iter()
+                .min_by(|a, b| compare_candidates(a, b, &totals, &rounds))
+                .cloned()
+                .unwrap();
+
+            continuing.retain(|candidate| candidate != &lowest);
+            round.eliminated = vec![lowest.clone()];
+            eliminated.push(lowest);
+        }
+
+        rounds.push(round);
+    }
+
+    // Rank the remaining candidates by their votes in the last round, then eliminated candidates in reverse order of elimination
+    let mut ranking = elected;
+    if let Some(last) = rounds.last() {
+        let mut remaining = continuing;
+        remaining.sort_by(|a, b| compare_candidates(b, a, &last.totals, &rounds));
+        ranking.append(&mut remaining);
+    }
+    ranking.extend(eliminated.into_iter().rev());
+
+    (ranking, TransferReport { quota, rounds })
+}
+
+fn highest_continuing(preferences: &[String], continuing: &[String]) -> Option<String> {
+    preferences
+        .iter()
+        .find(|candidate| continuing.contains(*candidate))
+        .cloned()
+}
+
+// Compare two candidates by their current votes, breaking ties with the most recent round in which they differed, and then by candidate-id.
+fn compare_candidates(
+    a: &str,
+    b: &str,
+    totals: &IndexMap<String, Decimal>,
+    rounds: &[TransferRound],
+) -> Ordering {
+    let current = totals
+        .get(a)
+        .cloned()
+        .unwrap_or_default()
+        .cmp(&totals.get(b).cloned().unwrap_or_default());
+    if current != Ordering::Equal {
+        return current;
+    }
+
+    for round in rounds.iter().rev() {
+        if let (Some(a_total), Some(b_total)) = (round.totals.get(a), round.totals.get(b)) {
+            if a_total != b_total {
+                return a_total.cmp(b_total);
+            }
+        }
+    }
+
+    // Lower candidate-ids are ranked higher
+    b.cmp(a)
+}
@@ -1,9 +1,19 @@
 use crate::*;
+use ed25519_dalek::PublicKey;
 use indexmap::IndexMap;
 use rust_decimal::prelude::*;
+use std::collections::HashMap;
 use tallystick::RankedCandidate;
 use tallystick::RankedWinners;
 
+/// Transaction 11: Tally
+///
+/// After all votes have been decrypted, any node may produce a TallyTransaction. The tally contains the
+/// results for every contest in the election, keyed by contest-id.
+///
+/// Validating a TallyTransaction recomputes the tally from the DecryptionTransactions in the store,
+/// making the tally the canonical and verifiable result of the election.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TallyTransaction {
     pub id: Identifier,
     pub election_id: Identifier,
@@ -11,38 +21,265 @@ pub struct TallyTransaction {
     pub tally: IndexMap<String, TallyResult>,
 }
 
+impl TallyTransaction {
+    /// Create a new TallyTransaction with the results of every contest
+    pub fn new(election_id: Identifier, tally: IndexMap<String, TallyResult>) -> Self {
+        TallyTransaction {
+            id: Self::build_id(election_id),
+            election_id,
+            tally,
+        }
+    }
+
+    pub fn build_id(election_id: Identifier) -> Identifier {
+        Identifier::new(election_id, TransactionType::Tally, None)
+    }
+}
+
+impl CryptoBallotTransaction for TallyTransaction {
+    #[inline(always)]
+    fn id(&self) -> Identifier {
+        self.id
+    }
+
+    /// Any node may produce a tally
+    #[inline(always)]
+    fn public(&self) -> Option<PublicKey> {
+        None
+    }
+
+    #[inline(always)]
+    fn election_id(&self) -> Identifier {
+        self.election_id
+    }
+
+    #[inline(always)]
+    fn tx_type() -> TransactionType {
+        TransactionType::Tally
+    }
+
+    /// Validate the transaction
+    ///
+    /// The validation does the following:
+    ///  - Validates that all votes have been decrypted
+    ///  - Recomputes the tally for every contest and checks that it matches
+    fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
+        // Check the ID
+        if Self::build_id(self.election_id) != self.id {
+            return Err(ValidationError::IdentifierBadComposition);
+        }
+
+        let election = store.get_election(self.election_id)?;
+
+        let expected = tally_election(store, &election)?;
+
+        if expected.len() != self.tally.len() {
+            return Err(ValidationError::TallyWrongNumberOfContests);
+        }
+
+        for (contest_id, expected_result) in expected.iter() {
+            match self.tally.get(contest_id) {
+                Some(result) if result.same_outcome(expected_result) => {}
+                _ => {
+                    return Err(ValidationError::TallyMismatch(
+                        expected_result.contest_index,
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Tally every contest in an election from the DecryptionTransactions in the store.
+///
+/// Returns an error if voting has not ended or if any vote has not yet been decrypted.
+pub fn tally_election<S: Store>(
+    store: &S,
+    election: &ElectionTransaction,
+) -> Result<IndexMap<String, TallyResult>, ValidationError> {
+    // Make sure voting end exists
+    let voting_end_id = Identifier::new(election.id, TransactionType::VotingEnd, None);
+    if store.get_transaction(voting_end_id)?.is_none() {
+        return Err(ValidationError::MisingVotingEndTransaction);
+    }
+
+    // Count the number of votes cast in each contest, ignoring votes that have been replaced
+    let mut num_votes: HashMap<u32, usize> = HashMap::with_capacity(election.contests.len());
+    for vote in latest_votes(store, election.id) {
+        for encrypted_vote in vote?.encrypted_votes {
+            *num_votes.entry(encrypted_vote.contest_index).or_insert(0) += 1;
+        }
+    }
+
+    // If there's a mixnet, only decryptions of the final mix are counted
+    let threshold = current_trustees(store, election)?.threshold;
+    let mut final_mixes: HashMap<Identifier, bool> = HashMap::new();
+    for mix in store.get_multiple(election.id, TransactionType::Mix) {
+        let mix: MixTransaction = mix?.into();
+        final_mixes.insert(mix.id, mix.mix_index + 1 == threshold);
+    }
+
+    // Gather decrypted votes for each contest
+    let mut decrypted: HashMap<u32, Vec<(Identifier, Vec<Selection>)>> =
+        HashMap::with_capacity(election.contests.len());
+    for decryption in store.get_multiple(election.id, TransactionType::Decryption) {
+        let decryption: DecryptionTransaction = decryption?.into();
+
+        let counted = match decryption.upstream_id.transaction_type {
+            TransactionType::Vote => election.mix_config.is_none(),
+            TransactionType::Mix => *final_mixes.get(&decryption.upstream_id).unwrap_or(&false),
+            TransactionType::VotingEnd => true,
+            _ => false,
+        };
+
+        if counted {
+            decrypted
+                .entry(decryption.contest_index)
+                .or_insert(vec![])
+                .push((decryption.id, decryption.decrypted_vote));
+        }
+    }
+
+    let mut results = IndexMap::with_capacity(election.contests.len());
+    for contest in &election.contests {
+        let mut votes = decrypted.remove(&contest.index).unwrap_or_default();
+        let expected = *num_votes.get(&contest.index).unwrap_or(&0);
+
+        // Homomorphic contests have a single decryption of the totals for every candidate
+        if contest.homomorphic {
+            if votes.len() != 1 {
+                return Err(ValidationError::TallyMissingDecryptions(
+                    contest.index,
+                    1,
+                    votes.len(),
+                ));
+            }
+            let (_, totals) = votes.remove(0);
+
+            let result = TallyResult::tally_homomorphic(contest, expected, totals)?;
+            results.insert(contest.id.clone(), result);
+            continue;
+        }
+
+        // Make sure all votes have been decrypted
+        if votes.len() != expected {
+            return Err(ValidationError::TallyMissingDecryptions(
+                contest.index,
+                expected,
+                votes.len(),
+            ));
+        }
+
+        let result = TallyResult::tally(contest, votes);
+
+        results.insert(contest.id.clone(), result);
+    }
+
+    Ok(results)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TallyResult {
     pub contest_id: String,
     pub contest_index: u32,
 
+    /// The number of votes counted, not including spoiled ballots
     pub num_votes: usize,
     pub totals: IndexMap<String, Decimal>,
     pub results: Vec<RankedCandidate<String>>,
     pub winners: RankedWinners<String>,
     pub spoiled_ballots: IndexMap<Identifier, SpoiledBallotError>,
+
+    /// Round-by-round report for InstantRunoff and SingleTransferable contests
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transfer_report: Option<TransferReport>,
 }
 
 impl TallyResult {
-    pub fn tally(
-        contest_id: String,
-        contest_index: u32,
-        num_winners: u32,
-        contest_type: ContestType,
-        votes: Vec<Vec<Selection>>,
-    ) -> Self {
-        let num_votes = votes.len();
+    /// Check that two tallies have the same outcome.
+    ///
+    /// Tallystick does not guarantee the order of tied candidates, so totals, results, winners and
+    /// spoiled ballots are compared without regard to their ordering.
+    pub fn same_outcome(&self, other: &TallyResult) -> bool {
+        if self.contest_id != other.contest_id
+            || self.contest_index != other.contest_index
+            || self.num_votes != other.num_votes
+        {
+            return false;
+        }
 
-        // Make sure selections are in order
-        let votes: Vec<Vec<Selection>> = votes
-            .into_iter()
-            .map(|mut vote| {
-                vote.sort_by(|a, b| a.score.cmp(&b.score));
-                vote
-            })
-            .collect();
+        // IndexMap equality does not consider ordering
+        if self.totals != other.totals {
+            return false;
+        }
+
+        let sorted_results = |results: &Vec<RankedCandidate<String>>| {
+            let mut results: Vec<String> = results
+                .iter()
+                .map(|r| serde_json::to_string(r).unwrap_or_default())
+                .collect();
+            results.sort();
+            results
+        };
+        if sorted_results(&self.results) != sorted_results(&other.results) {
+            return false;
+        }
+
+        let sorted_winners = |winners: &RankedWinners<String>| {
+            let mut winners = winners.clone().into_unranked();
+            winners.sort();
+            winners
+        };
+        if sorted_winners(&self.winners) != sorted_winners(&other.winners) {
+            return false;
+        }
+
+        let spoiled = |tally: &TallyResult| {
+            let mut spoiled: Vec<(Identifier, String)> = tally
+                .spoiled_ballots
+                .iter()
+                .map(|(id, err)| (*id, err.to_string()))
+                .collect();
+            spoiled.sort();
+            spoiled
+        };
+        if spoiled(self) != spoiled(other) {
+            return false;
+        }
+
+        self.transfer_report == other.transfer_report
+    }
+
+    /// Tally a single contest.
+    ///
+    /// Votes that do not follow the rules of the contest are not counted. Instead they are recorded
+    /// in `spoiled_ballots` with the reason they were spoiled, keyed by the id of their DecryptionTransaction.
+    pub fn tally(contest: &Contest, votes: Vec<(Identifier, Vec<Selection>)>) -> Self {
+        let contest_id = contest.id.clone();
+        let contest_index = contest.index;
+        let num_winners = contest.num_winners;
+
+        // Set aside spoiled ballots and make sure selections are in order
+        let mut spoiled_ballots = IndexMap::new();
+        let mut valid_votes = Vec::with_capacity(votes.len());
+        for (id, mut vote) in votes {
+            match contest.check_selections(&vote) {
+                Ok(()) => {
+                    vote.sort_by(|a, b| a.score.cmp(&b.score));
+                    valid_votes.push(vote);
+                }
+                Err(e) => {
+                    spoiled_ballots.insert(id, e);
+                }
+            }
+        }
+        let votes = valid_votes;
+        let num_votes = votes.len();
 
-        match contest_type {
+        match contest.contest_type {
             ContestType::Plurality => {
                 use tallystick::plurality::DefaultPluralityTally;
                 let mut tally = DefaultPluralityTally::new(num_winners as usize);
@@ -68,7 +305,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::Score => {
@@ -95,7 +333,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::Approval => {
@@ -122,7 +361,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::Condorcet => {
@@ -151,7 +391,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::SchulzeWinning => {
@@ -181,7 +422,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::SchulzeMargin => {
@@ -211,16 +453,46 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::SchulzeRatio => {
-                // use tallystick::schulze::SchulzeTally;
-                // use tallystick::schulze::Variant;
+                use tallystick::schulze::SchulzeTally;
+                use tallystick::schulze::Variant;
 
-                // TODO: Decimal needs to implement NumCast before this can work.
+                // The ratio variant divides support by opposition, so it needs a float count type
+                let mut tally =
+                    SchulzeTally::<String, f64>::new(num_winners as usize, Variant::Ratio);
 
-                unimplemented!();
+                for vote in votes {
+                    let vote: Vec<(String, u32)> = vote.into_iter().map(|v| v.into()).collect();
+                    tally
+                        .ranked_add(&vote)
+                        .expect("Unexpected duplicate candidate");
+                }
+
+                let mut totals = IndexMap::new();
+                for (candidate, total) in tally.totals() {
+                    totals.insert(
+                        format!("{} > {}", candidate.0, candidate.1),
+                        Decimal::from_f64(total).unwrap_or_default(),
+                    );
+                }
+
+                let ranked = tally.ranked();
+                let winners = tally.winners();
+
+                TallyResult {
+                    contest_id,
+                    contest_index,
+                    num_votes,
+                    totals,
+                    results: ranked,
+                    winners,
+                    spoiled_ballots,
+                    transfer_report: None,
+                }
             }
             ContestType::Borda => {
                 use tallystick::borda::DefaultBordaTally;
@@ -250,7 +522,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::BordaClassic => {
@@ -281,14 +554,17 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::BordaDowdall => {
-                use tallystick::borda::DefaultBordaTally;
+                use tallystick::borda::BordaTally;
                 use tallystick::borda::Variant;
 
-                let mut tally = DefaultBordaTally::new(num_winners as usize, Variant::Dowdall);
+                // Dowdall assigns fractional points, so it needs a float count type
+                let mut tally =
+                    BordaTally::<String, f64>::new(num_winners as usize, Variant::Dowdall);
 
                 for vote in votes {
                     let vote: Vec<String> = vote.into_iter().map(|v| v.selection).collect();
@@ -299,7 +575,7 @@ impl TallyResult {
 
                 let mut totals = IndexMap::new();
                 for (candidate, total) in tally.totals() {
-                    totals.insert(candidate, total.into());
+                    totals.insert(candidate, Decimal::from_f64(total).unwrap_or_default());
                 }
 
                 let ranked = tally.ranked();
@@ -312,7 +588,8 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
                 }
             }
             ContestType::BordaModifiedClassic => {
@@ -344,9 +621,425 @@ impl TallyResult {
                     totals,
                     results: ranked,
                     winners,
-                    spoiled_ballots: IndexMap::new(),
+                    spoiled_ballots,
+                    transfer_report: None,
+                }
+            }
+            ContestType::InstantRunoff { quota } => {
+                Self::tally_transferable(contest, 1, quota, votes, spoiled_ballots)
+            }
+            ContestType::SingleTransferable { quota } => {
+                Self::tally_transferable(contest, num_winners, quota, votes, spoiled_ballots)
+            }
+        }
+    }
+
+    /// Tally a homomorphic contest from the decrypted total for each candidate.
+    ///
+    /// Votes for homomorphic contests are proven well-formed when they are cast, so there are no spoiled ballots.
+    /// Returns an error if the contest type can't be tallied homomorphically.
+    pub fn tally_homomorphic(
+        contest: &Contest,
+        num_votes: usize,
+        totals: Vec<Selection>,
+    ) -> Result<Self, ValidationError> {
+        let contest_id = contest.id.clone();
+        let contest_index = contest.index;
+        let num_winners = contest.num_winners;
+
+        let (totals, ranked, winners) = match contest.contest_type {
+            ContestType::Plurality => {
+                use tallystick::plurality::DefaultPluralityTally;
+                let mut tally = DefaultPluralityTally::new(num_winners as usize);
+
+                for total in totals {
+                    tally.add_weighted(total.selection, total.score as u64);
+                }
+
+                let mut totals = IndexMap::new();
+                for (candidate, total) in tally.totals() {
+                    totals.insert(candidate, total.into());
+                }
+
+                (totals, tally.ranked(), tally.winners())
+            }
+            ContestType::Approval => {
+                use tallystick::approval::DefaultApprovalTally;
+                let mut tally = DefaultApprovalTally::new(num_winners as usize);
+
+                for total in totals {
+                    tally.add_weighted(vec![total.selection], total.score as u64);
+                }
+
+                let mut totals = IndexMap::new();
+                for (candidate, total) in tally.totals() {
+                    totals.insert(candidate, total.into());
                 }
+
+                (totals, tally.ranked(), tally.winners())
             }
+            ContestType::Score => {
+                use tallystick::score::DefaultScoreTally;
+                let mut tally = DefaultScoreTally::new(num_winners as usize);
+
+                for total in totals {
+                    if total.score > 0 {
+                        tally.add_ref(&vec![(total.selection, total.score as u64)]);
+                    }
+                }
+
+                let mut totals = IndexMap::new();
+                for (candidate, total) in tally.totals() {
+                    totals.insert(candidate, total.into());
+                }
+
+                (totals, tally.ranked(), tally.winners())
+            }
+            _ => {
+                return Err(ValidationError::UnsupportedHomomorphicContest(
+                    contest_index,
+                ))
+            }
+        };
+
+        Ok(TallyResult {
+            contest_id,
+            contest_index,
+            num_votes,
+            totals,
+            results: ranked,
+            winners,
+            spoiled_ballots: IndexMap::new(),
+            transfer_report: None,
+        })
+    }
+
+    fn tally_transferable(
+        contest: &Contest,
+        num_winners: u32,
+        quota: Quota,
+        votes: Vec<Vec<Selection>>,
+        spoiled_ballots: IndexMap<Identifier, SpoiledBallotError>,
+    ) -> Self {
+        let num_votes = votes.len();
+        let candidates: Vec<String> = contest.candidates.iter().map(|c| c.id.clone()).collect();
+        let ballots: Vec<Vec<String>> = votes
+            .into_iter()
+            .map(|vote| vote.into_iter().map(|v| v.selection).collect())
+            .collect();
+
+        let (ranking, report) = count_stv(num_winners as usize, quota, candidates, ballots);
+
+        // Totals are the first-preference votes, later rounds are in the transfer report
+        let totals = match report.rounds.first() {
+            Some(first_round) => first_round.totals.clone(),
+            None => IndexMap::new(),
+        };
+
+        // Ties are broken during the count, so every candidate has their own rank
+        let ranked: Vec<RankedCandidate<String>> = ranking
+            .into_iter()
+            .enumerate()
+            .map(|(rank, candidate)| RankedCandidate { candidate, rank })
+            .collect();
+        let winners = RankedWinners::from_ranked(ranked.clone(), num_winners as usize);
+
+        TallyResult {
+            contest_id: contest.id.clone(),
+            contest_index: contest.index,
+            num_votes,
+            totals,
+            results: ranked,
+            winners,
+            spoiled_ballots,
+            transfer_report: Some(report),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked_vote(candidates: &[&str]) -> Vec<Selection> {
+        candidates
+            .iter()
+            .enumerate()
+            .map(|(rank, candidate)| Selection {
+                write_in: false,
+                score: rank as u32,
+                selection: candidate.to_string(),
+            })
+            .collect()
+    }
+
+    fn test_contest(contest_type: ContestType, num_winners: u32) -> Contest {
+        let candidate = |id: &str| Candidate {
+            id: id.to_string(),
+            properties: IndexMap::new(),
+        };
+
+        Contest {
+            id: "TEST".to_string(),
+            index: 0,
+            contest_type,
+            num_winners,
+            write_in: false,
+            candidates: vec![candidate("Alice"), candidate("Bob"), candidate("Carlos")],
+            homomorphic: false,
+            max_score: None,
+            properties: IndexMap::new(),
+        }
+    }
+
+    // Give each vote a unique decryption id
+    fn with_ids(votes: Vec<Vec<Selection>>) -> Vec<(Identifier, Vec<Selection>)> {
+        votes
+            .into_iter()
+            .enumerate()
+            .map(|(i, vote)| {
+                let mut unique_info = [0; 16];
+                unique_info[0] = i as u8;
+                let id = Identifier::new_from_str_id(
+                    "000000000000000000000000000000",
+                    TransactionType::Decryption,
+                    Some(unique_info),
+                )
+                .unwrap();
+                (id, vote)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_schulze_ratio() {
+        let mut votes = Vec::new();
+        for _ in 0..3 {
+            votes.push(ranked_vote(&["Alice", "Bob", "Carlos"]));
+        }
+        for _ in 0..2 {
+            votes.push(ranked_vote(&["Bob", "Carlos", "Alice"]));
+        }
+
+        let result =
+            TallyResult::tally(&test_contest(ContestType::SchulzeRatio, 1), with_ids(votes));
+
+        assert_eq!(result.num_votes, 5);
+        assert!(!result.totals.is_empty());
+        assert_eq!(result.winners.into_unranked(), vec!["Alice".to_string()]);
+    }
+
+    #[test]
+    fn test_instant_runoff() {
+        let mut votes = Vec::new();
+        for _ in 0..4 {
+            votes.push(ranked_vote(&["Alice"]));
         }
+        for _ in 0..3 {
+            votes.push(ranked_vote(&["Bob", "Carlos"]));
+        }
+        for _ in 0..2 {
+            votes.push(ranked_vote(&["Carlos", "Bob"]));
+        }
+
+        let contest_type = ContestType::InstantRunoff {
+            quota: Quota::Droop,
+        };
+        let result = TallyResult::tally(&test_contest(contest_type, 1), with_ids(votes));
+
+        // Carlos is eliminated and his votes give Bob a majority
+        let report = result.transfer_report.unwrap();
+        assert_eq!(report.quota, Decimal::from(5));
+        assert_eq!(report.rounds[0].eliminated, vec!["Carlos".to_string()]);
+        assert_eq!(report.rounds[0].transfers["Bob"], Decimal::from(2));
+        assert_eq!(report.rounds[1].elected, vec!["Bob".to_string()]);
+        assert_eq!(result.totals["Alice"], Decimal::from(4));
+        assert_eq!(result.winners.into_unranked(), vec!["Bob".to_string()]);
+
+        // Candidates with no votes are still ranked, after the candidates that had votes
+        let votes = vec![
+            ranked_vote(&["Alice"]),
+            ranked_vote(&["Alice", "Bob"]),
+            ranked_vote(&["Bob"]),
+        ];
+        let contest_type = ContestType::InstantRunoff {
+            quota: Quota::Droop,
+        };
+        let result = TallyResult::tally(&test_contest(contest_type, 1), with_ids(votes));
+        assert_eq!(result.totals["Carlos"], Decimal::zero());
+        assert_eq!(result.results.len(), 3);
+        assert_eq!(result.results[0].candidate, "Alice");
+        assert_eq!(result.results[2].candidate, "Carlos");
+    }
+
+    #[test]
+    fn test_single_transferable_vote() {
+        let mut votes = Vec::new();
+        for _ in 0..6 {
+            votes.push(ranked_vote(&["Alice", "Bob"]));
+        }
+        for _ in 0..2 {
+            votes.push(ranked_vote(&["Bob"]));
+        }
+        for _ in 0..2 {
+            votes.push(ranked_vote(&["Carlos"]));
+        }
+
+        // Alice is elected in the first round and her surplus elects Bob
+        let contest_type = ContestType::SingleTransferable {
+            quota: Quota::Droop,
+        };
+        let result = TallyResult::tally(&test_contest(contest_type, 2), with_ids(votes.clone()));
+        let report = result.transfer_report.unwrap();
+        assert_eq!(report.quota, Decimal::from(4));
+        assert_eq!(report.rounds[0].elected, vec!["Alice".to_string()]);
+        let mut winners = result.winners.into_unranked();
+        winners.sort();
+        assert_eq!(winners, vec!["Alice".to_string(), "Bob".to_string()]);
+
+        // With a Hare quota of 5, Alice is still elected first
+        let contest_type = ContestType::SingleTransferable { quota: Quota::Hare };
+        let result = TallyResult::tally(&test_contest(contest_type, 2), with_ids(votes));
+        let report = result.transfer_report.unwrap();
+        assert_eq!(report.quota, Decimal::from(5));
+        assert_eq!(report.rounds[0].elected, vec!["Alice".to_string()]);
+    }
+
+    #[test]
+    fn test_spoiled_ballots() {
+        let plurality = |candidate: &str, write_in: bool| Selection {
+            write_in,
+            score: 0,
+            selection: candidate.to_string(),
+        };
+
+        let votes = vec![
+            vec![plurality("Alice", false)],
+            vec![plurality("Alice", false)],
+            vec![plurality("Bob", false)],
+            // Overvote
+            vec![plurality("Bob", false), plurality("Carlos", false)],
+            // Unknown candidate
+            vec![plurality("Dave", false)],
+            // Write-ins are not allowed
+            vec![plurality("Dave", true)],
+            // No selections
+            vec![],
+        ];
+
+        let result = TallyResult::tally(&test_contest(ContestType::Plurality, 1), with_ids(votes));
+        assert_eq!(result.num_votes, 3);
+        assert_eq!(result.winners.into_unranked(), vec!["Alice".to_string()]);
+
+        let reasons: Vec<String> = result
+            .spoiled_ballots
+            .values()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                SpoiledBallotError::TooManySelections.to_string(),
+                SpoiledBallotError::CandidateNotFound.to_string(),
+                SpoiledBallotError::WriteInNotAllowed.to_string(),
+                SpoiledBallotError::NoSelections.to_string(),
+            ]
+        );
+
+        // Duplicate candidates and gaps in the ranking are spoiled instead of panicking
+        let duplicate = vec![
+            Selection {
+                write_in: false,
+                score: 0,
+                selection: "Alice".to_string(),
+            },
+            Selection {
+                write_in: false,
+                score: 1,
+                selection: "Alice".to_string(),
+            },
+        ];
+        let mut gap = ranked_vote(&["Alice", "Bob"]);
+        gap[1].score = 2;
+        let votes = vec![ranked_vote(&["Bob", "Alice"]), duplicate, gap];
+
+        let result = TallyResult::tally(&test_contest(ContestType::Condorcet, 1), with_ids(votes));
+        assert_eq!(result.num_votes, 1);
+        let reasons: Vec<String> = result
+            .spoiled_ballots
+            .values()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                SpoiledBallotError::DuplicateCandidate.to_string(),
+                SpoiledBallotError::InvalidRanking.to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_score_out_of_range() {
+        let mut contest = test_contest(ContestType::Score, 1);
+        contest.max_score = Some(5);
+
+        let scored = |scores: &[u32]| -> Vec<Selection> {
+            ["Alice", "Bob", "Carlos"]
+                .iter()
+                .zip(scores)
+                .map(|(candidate, score)| Selection {
+                    write_in: false,
+                    score: *score,
+                    selection: candidate.to_string(),
+                })
+                .collect()
+        };
+
+        let votes = vec![scored(&[5, 3, 0]), scored(&[2, 6, 0]), scored(&[1, 4, 2])];
+
+        let result = TallyResult::tally(&contest, with_ids(votes));
+        assert_eq!(result.num_votes, 2);
+        let reasons: Vec<String> = result
+            .spoiled_ballots
+            .values()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![SpoiledBallotError::ScoreOutOfRange.to_string()]
+        );
+        assert_eq!(result.totals["Alice"], Decimal::from(6));
+        assert_eq!(result.totals["Bob"], Decimal::from(7));
+    }
+
+    #[test]
+    fn test_homomorphic_score() {
+        let mut contest = test_contest(ContestType::Score, 1);
+        contest.homomorphic = true;
+        contest.max_score = Some(5);
+
+        let totals: Vec<Selection> = ["Alice", "Bob", "Carlos"]
+            .iter()
+            .zip(&[7, 12, 0])
+            .map(|(candidate, total)| Selection {
+                write_in: false,
+                score: *total,
+                selection: candidate.to_string(),
+            })
+            .collect();
+
+        let result = TallyResult::tally_homomorphic(&contest, 3, totals.clone()).unwrap();
+        assert_eq!(result.num_votes, 3);
+        assert!(result.spoiled_ballots.is_empty());
+        assert_eq!(result.totals["Bob"], Decimal::from(12));
+        assert_eq!(result.winners.into_unranked(), vec!["Bob".to_string()]);
+
+        // Ranked contests can't be tallied homomorphically
+        contest.contest_type = ContestType::Borda;
+        assert!(matches!(
+            TallyResult::tally_homomorphic(&contest, 3, totals),
+            Err(ValidationError::UnsupportedHomomorphicContest(0))
+        ));
     }
 }
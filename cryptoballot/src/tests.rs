@@ -22,9 +22,20 @@ fn end_to_end_election_no_mix() {
         id: "TESTCONTEST".to_string(),
         index: 0,
         contest_type: ContestType::Plurality,
-        write_in: true,
+        write_in: false,
         num_winners: 1,
-        candidates: vec![],
+        candidates: vec![
+            Candidate {
+                id: "Barak Obama".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+            Candidate {
+                id: "Santa".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+        ],
+        homomorphic: false,
+        max_score: None,
         properties: indexmap::IndexMap::new(),
     };
 
@@ -32,7 +43,7 @@ fn end_to_end_election_no_mix() {
     let (authenticator, authn_secrets) =
         Authenticator::new(256, &vec![ballot_id.to_string()]).unwrap();
     let authn_secret = authn_secrets.get(ballot_id).unwrap();
-    let authn_public = authenticator.public_keys.get(ballot_id).unwrap().as_ref();
+    let authn_public = authenticator.public_keys.get(ballot_id).unwrap();
 
     // Create 3 trustees
     let (trustee_1, trustee_1_secret) = Trustee::new(1, 3, 2);
@@ -205,6 +216,7 @@ fn end_to_end_election_no_mix() {
         trustee_1.public_key,
         pk_1,
         pk_1_proof,
+        vec![],
     );
     let pk_1_tx = Signed::sign(&trustee_1_secret, pk_1_tx).unwrap();
     pk_1_tx.validate(&store).unwrap();
@@ -229,6 +241,7 @@ fn end_to_end_election_no_mix() {
         trustee_2.public_key,
         pk_2,
         pk_2_proof,
+        vec![],
     );
     let pk_2_tx = Signed::sign(&trustee_2_secret, pk_2_tx).unwrap();
     pk_2_tx.validate(&store).unwrap();
@@ -253,6 +266,7 @@ fn end_to_end_election_no_mix() {
         trustee_3.public_key,
         pk_3,
         pk_3_proof,
+        vec![],
     );
     let pk_3_tx = Signed::sign(&trustee_3_secret, pk_3_tx).unwrap();
     pk_3_tx.validate(&store).unwrap();
@@ -272,33 +286,89 @@ fn end_to_end_election_no_mix() {
         selection: "Barak Obama".to_string(),
     };
 
-    // Encrypt the secret vote
-    let selections = encrypt_vote(
+    let plaintext = vec![PlaintextVote {
+        contest_index: 0,
+        selections: vec![selection.clone()],
+    }];
+
+    // Encrypt the secret vote, and challenge the voter's device to prove it encrypted honestly
+    let (_, challenged_public) = generate_keypair();
+    let challenged = BenalohBallot::encrypt(
         &encryption_key_tx.encryption_key,
-        vec![selection.clone()],
+        election.inner(),
+        &challenged_public,
+        plaintext.clone(),
         &mut test_rng,
     )
     .unwrap();
+    let commitment = challenged.commitment();
+    let challenge = challenged.challenge();
+    assert_eq!(challenge.commitment, commitment);
+    verify_benaloh_challenge(
+        &encryption_key_tx.encryption_key,
+        election.inner(),
+        &challenge,
+    )
+    .unwrap();
 
-    let encrypted_vote = EncryptedVote {
-        contest_index: 0,
-        selections,
-    };
+    // A device that lies about the plaintext is caught
+    let mut dishonest = challenge.clone();
+    dishonest.plaintext[0].selections[0].selection = "Santa".to_string();
+    assert!(verify_benaloh_challenge(
+        &encryption_key_tx.encryption_key,
+        election.inner(),
+        &dishonest
+    )
+    .is_err());
+
+    // The challenged ballot is spoiled, so encrypt a fresh one under a fresh anonymous key and cast it
+    let (voter_secret, voter_public) = generate_keypair();
+    let encrypted_votes = BenalohBallot::encrypt(
+        &encryption_key_tx.encryption_key,
+        election.inner(),
+        &voter_public,
+        plaintext,
+        &mut test_rng,
+    )
+    .unwrap()
+    .cast();
 
     // Generate an empty vote transaction
-    let (mut vote, voter_secret) =
-        VoteTransaction::new(election.id(), ballot_id.to_string(), vec![encrypted_vote]);
+    let mut vote = VoteTransaction::with_anonymous_key(
+        election.id(),
+        ballot_id.to_string(),
+        encrypted_votes,
+        voter_public,
+    );
 
     // Create an auth package and blind it
     let auth_package = AuthPackage::new(election.id(), ballot_id.to_string(), vote.anonymous_key);
-    let (blinded_auth_package, unblinder) = auth_package.blind(&authn_public);
+    let (blinded_auth_package, unblinder) = auth_package.blind(&authn_public, None).unwrap();
 
     // Authenticate the voter (for a real election the voter would pass additional auth info)
-    let authentication = authenticator.authenticate(&authn_secret, &blinded_auth_package);
-    let authentication = authentication.unblind(&authn_public, unblinder);
+    let authentication = authenticator
+        .authenticate(&authn_secret, &blinded_auth_package, None)
+        .unwrap();
+    let authentication = authentication.unblind(&authn_public, unblinder).unwrap();
+
+    // A vote without authentication should fail validation
+    let unauthenticated_vote = Signed::sign(&voter_secret, vote.clone()).unwrap();
+    assert!(matches!(
+        unauthenticated_vote.validate(&store),
+        Err(ValidationError::NotEnoughAuthentications(1, 0))
+    ));
 
     // Attach the authentication to the vote
-    vote.authentication.push(authentication);
+    vote.authentication.push(authentication.clone());
+
+    // Authenticating twice with the same authenticator should fail validation
+    let mut duplicate_vote = vote.clone();
+    duplicate_vote.authentication.push(authentication);
+    let duplicate_vote = Signed::sign(&voter_secret, duplicate_vote).unwrap();
+    assert!(matches!(
+        duplicate_vote.validate(&store),
+        Err(ValidationError::DuplicateAuthentication(_))
+    ));
 
     // Sign and seal the vote transaction
     let vote = Signed::sign(&voter_secret, vote).unwrap();
@@ -307,11 +377,20 @@ fn end_to_end_election_no_mix() {
     vote.validate(&store).unwrap();
     store.set(vote.clone().into());
 
+    // The voter keeps a receipt, which can't be fully verified until their vote is decrypted
+    let receipt = vote.receipt();
+    assert_eq!(receipt.tracking_code.len(), 19);
+    assert!(matches!(
+        verify_receipt(&store, &receipt),
+        Err(ValidationError::ReceiptVoteNotCounted(_, 0))
+    ));
+
     // Voting is over!
     // ---------------
 
     // Generate VotingEnd transaction to mark the end of voting
-    let voting_end_tx = VotingEndTransaction::new(election.id, election.authority_public);
+    let voting_end_tx =
+        VotingEndTransaction::new(election.id, election.authority_public, unix_time());
     let voting_end_tx = Signed::sign(&authority_secret, voting_end_tx).unwrap();
     voting_end_tx.validate(&store).unwrap();
     store.set(voting_end_tx.clone().into());
@@ -394,11 +473,101 @@ fn end_to_end_election_no_mix() {
     // TODO: Add a decryptor public key to make it meaningful??  It does't really matter..
     let decrypted_tx = Signed::sign(&trustee_1_secret, decrypted_tx).unwrap();
     decrypted_tx.validate(&store).unwrap();
+
+    // Can't tally until all votes are decrypted
+    assert!(tally_election(&store, &election).is_err());
     store.set(decrypted_tx.clone().into());
 
+    // The voter's receipt shows their vote was counted, but a forged receipt does not
+    verify_receipt(&store, &receipt).unwrap();
+    let mut forged_receipt = receipt.clone();
+    forged_receipt.ballot_hash[0] ^= 1;
+    assert!(verify_receipt(&store, &forged_receipt).is_err());
+    forged_receipt.tracking_code = tracking_code(&forged_receipt.ballot_hash);
+    assert!(matches!(
+        verify_receipt(&store, &forged_receipt),
+        Err(ValidationError::ReceiptMismatch(_))
+    ));
+
     // Decrypted vote should match secret vote
     assert_eq!(selection, decrypted_tx.inner().decrypted_vote[0]);
 
+    // Tally the election
+    let tally = tally_election(&store, &election).unwrap();
+    assert_eq!(tally["TESTCONTEST"].num_votes, 1);
+    assert_eq!(
+        tally["TESTCONTEST"].winners.clone().into_unranked(),
+        vec!["Barak Obama".to_string()]
+    );
+
+    // A tampered tally should fail validation
+    let mut bad_tally = tally.clone();
+    bad_tally.get_mut("TESTCONTEST").unwrap().num_votes = 2;
+    let bad_tally_tx = TallyTransaction::new(election.id, bad_tally);
+    let bad_tally_tx = Signed::sign(&authority_secret, bad_tally_tx).unwrap();
+    assert!(bad_tally_tx.validate(&store).is_err());
+
+    let tally_tx = TallyTransaction::new(election.id, tally);
+    let tally_tx = Signed::sign(&authority_secret, tally_tx).unwrap();
+    tally_tx.validate(&store).unwrap();
+    store.set(tally_tx.clone().into());
+
+    // The whole election can be re-verified in bulk
+    let start = Identifier::start(election.id, TransactionType::Election, None);
+    let end = Identifier::end(election.id, TransactionType::Tally, None);
+    let all_txs: Vec<SignedTransaction> = store.range(start, end).map(|tx| tx.unwrap()).collect();
+    let mut replay = MemStore::default();
+    let failures = validate_bulk(&mut replay, all_txs.clone(), |store, tx| store.set(tx));
+    assert!(failures.is_empty());
+    assert!(replay.get_tally(tally_tx.id()).is_ok());
+
+    // Bulk verification reports each invalid transaction, and the transactions that depend on it
+    let tampered_txs = all_txs.into_iter().map(|tx| match tx {
+        SignedTransaction::Decryption(mut decryption) => {
+            decryption.sig = tally_tx.sig;
+            decryption.into()
+        }
+        tx => tx,
+    });
+    let failures = validate_bulk(&mut MemStore::default(), tampered_txs, |store, tx| {
+        store.set(tx)
+    });
+    assert_eq!(failures.len(), 2);
+    assert_eq!(failures[0].id, decrypted_tx.id());
+    assert!(matches!(
+        failures[0].error,
+        ValidationError::SignatureError(_)
+    ));
+    assert_eq!(failures[1].id, tally_tx.id());
+
+    // The election can be archived and verified offline
+    let mut archive_bytes = Vec::new();
+    let manifest = write_archive(&store, election.id, &mut archive_bytes).unwrap();
+    assert_eq!(manifest.transaction_count, 17);
+    assert_eq!(manifest.counts[&TransactionType::KeyGenShare], 3);
+
+    let archive = read_archive(archive_bytes.as_slice()).unwrap();
+    assert_eq!(archive.manifest, manifest);
+    let verification = verify_archive(archive);
+    assert!(verification.failures.is_empty());
+    assert_eq!(verification.tally.unwrap()["TESTCONTEST"].num_votes, 1);
+
+    // A truncated or altered archive is rejected
+    assert!(read_archive(&archive_bytes[..archive_bytes.len() - 1]).is_err());
+    let mut altered = read_archive(archive_bytes.as_slice()).unwrap();
+    altered.transactions.pop();
+    let mut altered_bytes = Vec::new();
+    serde_cbor::to_writer(&mut altered_bytes, &ARCHIVE_VERSION).unwrap();
+    serde_cbor::to_writer(&mut altered_bytes, &manifest).unwrap();
+    for tx in altered.transactions.iter() {
+        serde_cbor::to_writer(&mut altered_bytes, tx).unwrap();
+    }
+    serde_cbor::to_writer(&mut altered_bytes, &SignedTransaction::from(bad_tally_tx)).unwrap();
+    assert!(matches!(
+        read_archive(altered_bytes.as_slice()),
+        Err(Error::ArchiveManifestMismatch)
+    ));
+
     // Dump out the votes to JSON
     // To print out the transactions, do `cargo test -- --nocapture`
     println!(
@@ -420,6 +589,7 @@ fn end_to_end_election_no_mix() {
             SignedTransaction::from(partial_decrypt_1_tx),
             SignedTransaction::from(partial_decrypt_2_tx),
             SignedTransaction::from(decrypted_tx),
+            SignedTransaction::from(tally_tx),
         ])
         .unwrap()
     );
@@ -446,9 +616,20 @@ fn end_to_end_election_with_mix() {
         id: "TESTCONTEST".to_string(),
         index: 0,
         contest_type: ContestType::Plurality,
-        write_in: true,
+        write_in: false,
         num_winners: 1,
-        candidates: vec![],
+        candidates: vec![
+            Candidate {
+                id: "Barak Obama".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+            Candidate {
+                id: "Santa".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+        ],
+        homomorphic: false,
+        max_score: None,
         properties: indexmap::IndexMap::new(),
     };
 
@@ -456,7 +637,7 @@ fn end_to_end_election_with_mix() {
     let (authenticator, authn_secrets) =
         Authenticator::new(256, &vec![ballot_id.to_string()]).unwrap();
     let authn_secret = authn_secrets.get(ballot_id).unwrap();
-    let authn_public = authenticator.public_keys.get(ballot_id).unwrap().as_ref();
+    let authn_public = authenticator.public_keys.get(ballot_id).unwrap();
 
     // Create 3 trustees
     let (trustee_1, trustee_1_secret) = Trustee::new(1, 3, 2);
@@ -475,6 +656,11 @@ fn end_to_end_election_with_mix() {
         batch_size: None, // No Batching
     });
 
+    // Voting is open for an hour, ending at a fixed time so that mix timeouts can be tested
+    let voting_end_time = 1_600_000_000;
+    election.voting_start = Some(voting_end_time - 3600);
+    election.voting_end = Some(voting_end_time);
+
     // Finalize election transaction by signing it
     let election = Signed::sign(&authority_secret, election).unwrap();
 
@@ -633,6 +819,7 @@ fn end_to_end_election_with_mix() {
         trustee_1.public_key,
         pk_1,
         pk_1_proof,
+        vec![],
     );
     let pk_1_tx = Signed::sign(&trustee_1_secret, pk_1_tx).unwrap();
     pk_1_tx.validate(&store).unwrap();
@@ -657,6 +844,7 @@ fn end_to_end_election_with_mix() {
         trustee_2.public_key,
         pk_2,
         pk_2_proof,
+        vec![],
     );
     let pk_2_tx = Signed::sign(&trustee_2_secret, pk_2_tx).unwrap();
     pk_2_tx.validate(&store).unwrap();
@@ -681,6 +869,7 @@ fn end_to_end_election_with_mix() {
         trustee_3.public_key,
         pk_3,
         pk_3_proof,
+        vec![],
     );
     let pk_3_tx = Signed::sign(&trustee_3_secret, pk_3_tx).unwrap();
     pk_3_tx.validate(&store).unwrap();
@@ -700,34 +889,83 @@ fn end_to_end_election_with_mix() {
         selection: "Barak Obama".to_string(),
     };
 
-    // Encrypt the secret vote
-    let selections = encrypt_vote(
+    // Encrypt the secret vote, proving that it is well-formed
+    // The contest does not allow write-ins, so proofs are bound to the anonymous key which must be generated first
+    let (voter_secret, voter_public) = generate_keypair();
+    let encrypted_vote = encrypt_vote_with_proofs(
         &encryption_key_tx.encryption_key,
+        election.id,
+        &voter_public,
+        &election.contests[0],
         vec![selection.clone()],
         &mut test_rng,
     )
     .unwrap();
+    assert_eq!(encrypted_vote.proofs.len(), 1);
 
-    let encrypted_vote = EncryptedVote {
-        contest_index: 0,
-        selections,
+    // A selection that isn't a candidate can't be proven well-formed
+    let invalid_selection = Selection {
+        write_in: false,
+        score: 0,
+        selection: "Mickey Mouse".to_string(),
     };
+    assert!(encrypt_vote_with_proofs(
+        &encryption_key_tx.encryption_key,
+        election.id,
+        &voter_public,
+        &election.contests[0],
+        vec![invalid_selection],
+        &mut test_rng,
+    )
+    .is_err());
 
     // Generate an empty vote transaction
-    let (mut vote, voter_secret) =
-        VoteTransaction::new(election.id(), ballot_id.to_string(), vec![encrypted_vote]);
+    let mut vote = VoteTransaction::with_anonymous_key(
+        election.id(),
+        ballot_id.to_string(),
+        vec![encrypted_vote],
+        voter_public,
+    );
 
     // Create an auth package and blind it
     let auth_package = AuthPackage::new(election.id(), ballot_id.to_string(), vote.anonymous_key);
-    let (blinded_auth_package, unblinder) = auth_package.blind(&authn_public);
+    let (blinded_auth_package, unblinder) = auth_package.blind(&authn_public, None).unwrap();
 
     // Authenticate the voter (for a real election the voter would pass additional auth info)
-    let authentication = authenticator.authenticate(&authn_secret, &blinded_auth_package);
-    let authentication = authentication.unblind(&authn_public, unblinder);
+    let authentication = authenticator
+        .authenticate(&authn_secret, &blinded_auth_package, None)
+        .unwrap();
+    let authentication = authentication.unblind(&authn_public, unblinder).unwrap();
 
     // Attach the authentication to the vote
     vote.authentication.push(authentication);
 
+    // Votes can't be cast before voting opens
+    let early_vote = Signed::sign(&voter_secret, vote.clone()).unwrap();
+    store.set_time(voting_end_time - 3601);
+    assert!(matches!(
+        early_vote.validate(&store),
+        Err(ValidationError::VotingNotStarted)
+    ));
+    store.set_time(voting_end_time - 1800);
+
+    // A vote without well-formedness proofs should fail validation
+    let mut unproven_vote = vote.clone();
+    unproven_vote.encrypted_votes[0].proofs = vec![];
+    let unproven_vote = Signed::sign(&voter_secret, unproven_vote).unwrap();
+    assert!(unproven_vote.validate(&store).is_err());
+
+    // A proof attached to a different ciphertext should fail validation
+    let mut swapped_vote = vote.clone();
+    swapped_vote.encrypted_votes[0].selections = encrypt_vote(
+        &encryption_key_tx.encryption_key,
+        vec![selection.clone()],
+        &mut test_rng,
+    )
+    .unwrap();
+    let swapped_vote = Signed::sign(&voter_secret, swapped_vote).unwrap();
+    assert!(swapped_vote.validate(&store).is_err());
+
     // Sign and seal the vote transaction
     let vote = Signed::sign(&voter_secret, vote).unwrap();
 
@@ -744,53 +982,167 @@ fn end_to_end_election_with_mix() {
         selection: "Santa".to_string(),
     };
 
-    // Encrypt the secret vote
-    let selections_2_encrypted = encrypt_vote(
+    // The second voter is coerced into voting for Barak Obama
+    let coerced_selection = Selection {
+        write_in: false,
+        score: 0,
+        selection: "Barak Obama".to_string(),
+    };
+
+    // Encrypt the coerced vote. The voter's anonymous key ends in 0xFFFF, so the id of their replacement vote wraps
+    // around and sorts before the coerced vote.
+    let voter_secret_2 = ed25519_dalek::SecretKey::from_bytes(
+        &hex::decode("b4d3d1e7f3ddcfbfc0dc17d415afa5ae57ae79a120a8fa6b03ae600d314f569f").unwrap(),
+    )
+    .unwrap();
+    let voter_public_2 = ed25519_dalek::PublicKey::from(&voter_secret_2);
+    assert_eq!(voter_public_2.as_bytes()[14..16], [0xFF, 0xFF]);
+    let encrypted_vote_2 = encrypt_vote_with_proofs(
         &encryption_key_tx.encryption_key,
-        vec![selection_2.clone()],
+        election.id,
+        &voter_public_2,
+        &election.contests[0],
+        vec![coerced_selection],
         &mut test_rng,
     )
     .unwrap();
 
-    let encrypted_vote_2 = EncryptedVote {
-        contest_index: 0,
-        selections: selections_2_encrypted,
-    };
-
-    let (mut vote_2, voter_secret_2) =
-        VoteTransaction::new(election.id(), ballot_id.to_string(), vec![encrypted_vote_2]);
+    let mut vote_2 = VoteTransaction::with_anonymous_key(
+        election.id(),
+        ballot_id.to_string(),
+        vec![encrypted_vote_2],
+        voter_public_2,
+    );
 
     // Create an auth package and blind it
     let auth_package_2 =
         AuthPackage::new(election.id(), ballot_id.to_string(), vote_2.anonymous_key);
-    let (blinded_auth_package_2, unblinder_2) = auth_package_2.blind(&authn_public);
+    let (blinded_auth_package_2, unblinder_2) = auth_package_2.blind(&authn_public, None).unwrap();
 
     // Authenticate the voter (for a real election the voter would pass additional auth info)
-    let authentication_2 = authenticator.authenticate(&authn_secret, &blinded_auth_package_2);
-    let authentication_2 = authentication_2.unblind(&authn_public, unblinder_2);
+    let authentication_2 = authenticator
+        .authenticate(&authn_secret, &blinded_auth_package_2, None)
+        .unwrap();
+    let authentication_2 = authentication_2
+        .unblind(&authn_public, unblinder_2)
+        .unwrap();
 
     // Attach the authentication to the vote
     vote_2.authentication.push(authentication_2);
 
     // Sign and seal the vote transaction
-    let vote_2 = Signed::sign(&voter_secret_2, vote_2).unwrap();
+    let coerced_vote_2 = Signed::sign(&voter_secret_2, vote_2).unwrap();
 
     // Validate the vote transaction and store it
+    coerced_vote_2.validate(&store).unwrap();
+    store.set(coerced_vote_2.clone().into());
+
+    // Later, the voter replaces the coerced vote with their secret vote
+    let encrypted_vote_2 = encrypt_vote_with_proofs(
+        &encryption_key_tx.encryption_key,
+        election.id,
+        &voter_public_2,
+        &election.contests[0],
+        vec![selection_2.clone()],
+        &mut test_rng,
+    )
+    .unwrap();
+    let vote_2 = VoteTransaction::replace(&coerced_vote_2, vec![encrypted_vote_2]);
+    assert_eq!(vote_2.revision, 1);
+
+    // The first revision keeps the original id, made of the first 16 bytes of the anonymous key
+    assert_eq!(
+        coerced_vote_2.id().unique_info,
+        voter_public_2.as_bytes()[0..16]
+    );
+    assert!(vote_2.id < coerced_vote_2.id());
+
+    // Only the holder of the anonymous key can replace the vote
+    let (impostor_secret, _) = generate_keypair();
+    let impostor_vote_2 = Signed::sign(&impostor_secret, vote_2.clone()).unwrap();
+    assert!(impostor_vote_2.validate(&store).is_err());
+
+    // Revisions can't be skipped
+    let skipped_vote_2 = VoteTransaction::replace(&vote_2, vote_2.encrypted_votes.clone());
+    let skipped_vote_2 = Signed::sign(&voter_secret_2, skipped_vote_2).unwrap();
+    assert!(matches!(
+        skipped_vote_2.validate(&store),
+        Err(ValidationError::VoteRevisionOutOfOrder(2))
+    ));
+
+    let vote_2 = Signed::sign(&voter_secret_2, vote_2).unwrap();
     vote_2.validate(&store).unwrap();
     store.set(vote_2.clone().into());
 
+    // Only the replacement is counted
+    assert_eq!(latest_votes(&store, election.id).count(), 2);
+    assert!(!vote_is_latest(&store, coerced_vote_2.inner()).unwrap());
+
     // Voting is over!
     // ---------------
 
-    // Generate VotingEnd transaction to mark the end of voting
-    let voting_end_tx = VotingEndTransaction::new(election.id, election.authority_public);
-    let voting_end_tx = Signed::sign(&authority_secret, voting_end_tx).unwrap();
+    // Votes can't be cast once voting closes, even before the VotingEnd transaction is posted
+    store.set_time(voting_end_time);
+    let late_vote_2 = VoteTransaction::replace(&vote_2, vote_2.encrypted_votes.clone());
+    let late_vote_2 = Signed::sign(&voter_secret_2, late_vote_2).unwrap();
+    assert!(matches!(
+        late_vote_2.validate(&store),
+        Err(ValidationError::VotingHasEnded)
+    ));
+
+    // Trustees can't end voting before the scheduled voting_end
+    store.set_time(voting_end_time - 1);
+    let early_end_tx =
+        VotingEndTransaction::new(election.id, trustee_1.public_key, voting_end_time - 1);
+    let early_end_tx = Signed::sign(&trustee_1_secret, early_end_tx).unwrap();
+    assert!(matches!(
+        early_end_tx.validate(&store),
+        Err(ValidationError::VotingEndBeforeDeadline)
+    ));
+
+    // Once voting_end has passed, a trustee generates the VotingEnd transaction on the authority's behalf
+    store.set_time(voting_end_time);
+    let voting_end_tx = VotingEndTransaction::scheduled(&election, trustee_1.public_key).unwrap();
+    assert_eq!(voting_end_tx.timestamp, voting_end_time);
+    let voting_end_tx = Signed::sign(&trustee_1_secret, voting_end_tx).unwrap();
     voting_end_tx.validate(&store).unwrap();
     store.set(voting_end_tx.clone().into());
 
     let mut votes = vec![vote.clone(), vote_2.clone()];
     votes.sort_by(|v1, v2| v1.id().cmp(&v2.id()));
 
+    // Without batching, all votes are mixed in a single batch
+    let batches = election
+        .mix_config
+        .as_ref()
+        .unwrap()
+        .batch_votes(&store, election.id, 0)
+        .unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].len(), 2);
+
+    // With a batch-size of 1, each vote is mixed in its own batch, in vote-id order
+    let batched_config = MixConfig {
+        timeout_secs: 600,
+        batch_size: Some(1),
+    };
+    let batches = batched_config.batch_votes(&store, election.id, 0).unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0][0].0, votes[0].id());
+    assert_eq!(batches[1][0].0, votes[1].id());
+
+    // A single batch can be read without partitioning every vote
+    let batch = batched_config
+        .vote_batch(&store, election.id, 0, 1)
+        .unwrap()
+        .unwrap();
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch[0].0, votes[1].id());
+    assert!(batched_config
+        .vote_batch(&store, election.id, 0, 2)
+        .unwrap()
+        .is_none());
+
     let vote_ciphertexts = votes
         .iter()
         .map(|v| v.tx.encrypted_votes[0].selections.clone())
@@ -815,6 +1167,7 @@ fn end_to_end_election_with_mix() {
         None,
         &trustee_1,
         0,
+        voting_end_time,
         0,
         0,
         vote_ids,
@@ -825,6 +1178,87 @@ fn end_to_end_election_with_mix() {
     shuffle_tx_1.validate(&store).unwrap();
     store.set(shuffle_tx_1.clone().into());
 
+    // Once the votes are mixed, the voters' receipts show their latest votes were counted
+    for vote in votes.iter() {
+        verify_receipt(&store, &vote.receipt()).unwrap();
+    }
+    assert!(matches!(
+        verify_receipt(&store, &coerced_vote_2.receipt()),
+        Err(ValidationError::VoteReplaced)
+    ));
+
+    // Trustee 3 is next in line for the second mix, so it may only take the slot once trustee 2 has timed out
+    let (shuffle_3, proof_3) = mix(
+        &mut test_rng,
+        shuffle_tx_1.tx.mixed_ciphertexts.clone(),
+        &encryption_key_tx.encryption_key,
+        trustee_3.index,
+        1,
+        0,
+        0,
+    )
+    .unwrap();
+    let early_tx = MixTransaction::new(
+        election.id,
+        Some(shuffle_tx_1.id()),
+        &trustee_3,
+        1,
+        voting_end_time + 599,
+        0,
+        0,
+        shuffle_tx_1.vote_ids.clone(),
+        shuffle_3.clone(),
+        proof_3.clone(),
+    );
+    let early_tx = Signed::sign(&trustee_3_secret, early_tx).unwrap();
+    store.set_time(voting_end_time + 599);
+    assert!(early_tx.validate(&store).is_err());
+
+    let failover_tx = MixTransaction::new(
+        election.id,
+        Some(shuffle_tx_1.id()),
+        &trustee_3,
+        1,
+        voting_end_time + 600,
+        0,
+        0,
+        shuffle_tx_1.vote_ids.clone(),
+        shuffle_3,
+        proof_3,
+    );
+    let failover_tx = Signed::sign(&trustee_3_secret, failover_tx).unwrap();
+
+    // The timestamp may not be ahead of the store's clock
+    assert!(failover_tx.validate(&store).is_err());
+    store.set_time(voting_end_time + 600);
+    failover_tx.validate(&store).unwrap();
+
+    // Trustee 1 has already mixed, so it can never take the second mix
+    let (shuffle_1_again, proof_1_again) = mix(
+        &mut test_rng,
+        shuffle_tx_1.tx.mixed_ciphertexts.clone(),
+        &encryption_key_tx.encryption_key,
+        trustee_1.index,
+        1,
+        0,
+        0,
+    )
+    .unwrap();
+    let repeat_tx = MixTransaction::new(
+        election.id,
+        Some(shuffle_tx_1.id()),
+        &trustee_1,
+        1,
+        voting_end_time + 600,
+        0,
+        0,
+        shuffle_tx_1.vote_ids.clone(),
+        shuffle_1_again,
+        proof_1_again,
+    );
+    let repeat_tx = Signed::sign(&trustee_1_secret, repeat_tx).unwrap();
+    assert!(repeat_tx.validate(&store).is_err());
+
     // Generate the second mix transaction
     let (shuffle_2, proof) = mix(
         &mut test_rng,
@@ -842,6 +1276,7 @@ fn end_to_end_election_with_mix() {
         Some(shuffle_tx_1.id()),
         &trustee_2,
         1,
+        voting_end_time + 10,
         0,
         0,
         shuffle_tx_1.vote_ids.clone(),
@@ -852,6 +1287,9 @@ fn end_to_end_election_with_mix() {
     shuffle_tx_2.validate(&store).unwrap();
     store.set(shuffle_tx_2.clone().into());
 
+    // Now that trustee 2 has filled the slot, trustee 3 can no longer take it
+    assert!(failover_tx.validate(&store).is_err());
+
     // Generate a partial-decryption transactions
     let upstream_index = 0;
     let partial_decrypt_1_1 = trustee_1
@@ -934,6 +1372,9 @@ fn end_to_end_election_with_mix() {
     decrypted_tx_1.validate(&store).unwrap();
     store.set(decrypted_tx_1.clone().into());
 
+    // Can't tally until all votes are decrypted
+    assert!(tally_election(&store, &election).is_err());
+
     // Generate a second partial-decryption transactions
     let upstream_index = 1;
     let partial_decrypt_2_1 = trustee_1
@@ -1029,6 +1470,26 @@ fn end_to_end_election_with_mix() {
             ] == secret_votes
     );
 
+    // Tally the election
+    let tally = tally_election(&store, &election).unwrap();
+    assert_eq!(tally["TESTCONTEST"].num_votes, 2);
+
+    let tally_tx = TallyTransaction::new(election.id, tally);
+    let tally_tx = Signed::sign(&authority_secret, tally_tx).unwrap();
+    tally_tx.validate(&store).unwrap();
+    store.set(tally_tx.clone().into());
+
+    // Long after the election, its archive can still be verified, even though the voting window has passed. The
+    // replacement vote is replayed after the coerced vote it replaces, even though it comes first in the archive.
+    let mut archive_bytes = Vec::new();
+    write_archive(&store, election.id, &mut archive_bytes).unwrap();
+    let archive = read_archive(archive_bytes.as_slice()).unwrap();
+    let mut replay = MemStore::default();
+    replay.set_time(voting_end_time + 10 * 365 * 24 * 3600);
+    let failures = replay_archive(archive, &mut replay, |store, tx| store.set(tx));
+    assert!(failures.is_empty());
+    assert!(replay.get_tally(tally_tx.id()).is_ok());
+
     // Dump out the votes to JSON
     // To print out the transactions, do `cargo test -- --nocapture`
     println!(
@@ -1046,6 +1507,7 @@ fn end_to_end_election_with_mix() {
             SignedTransaction::from(pk_3_tx),
             SignedTransaction::from(encryption_key_tx),
             SignedTransaction::from(vote),
+            SignedTransaction::from(coerced_vote_2),
             SignedTransaction::from(vote_2),
             SignedTransaction::from(voting_end_tx),
             SignedTransaction::from(shuffle_tx_1),
@@ -1056,6 +1518,7 @@ fn end_to_end_election_with_mix() {
             SignedTransaction::from(partial_decrypt_2_2_tx),
             SignedTransaction::from(decrypted_tx_1),
             SignedTransaction::from(decrypted_tx_2),
+            SignedTransaction::from(tally_tx),
         ])
         .unwrap()
     );
@@ -1067,11 +1530,28 @@ fn test_all_elections() {
     //return;
     //#[allow(unreachable_code)]
 
+    verify_test_elections(MemStore::default, |store, tx| store.set(tx));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_all_elections_sqlite() {
+    verify_test_elections(
+        || SqliteStore::open_in_memory().unwrap(),
+        |store, tx| store.set(tx).unwrap(),
+    );
+}
+
+// Validate every election in `test_elections`, each in a fresh store
+fn verify_test_elections<S: Store>(
+    mut new_store: impl FnMut() -> S,
+    insert: impl Fn(&mut S, SignedTransaction),
+) {
     for entry in std::fs::read_dir("../test_elections").unwrap() {
         let entry = entry.unwrap();
         let path = entry.path();
         if path.is_dir() {
-            let mut store = MemStore::default();
+            let mut store = new_store();
 
             let mut paths: Vec<_> = std::fs::read_dir(path)
                 .unwrap()
@@ -1101,9 +1581,972 @@ fn test_all_elections() {
                             e
                         );
                     }
-                    store.set(tx);
+                    insert(&mut store, tx);
                 }
             }
         }
     }
 }
+
+#[test]
+fn end_to_end_election_homomorphic() {
+    let mut test_rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    let mut store = MemStore::default();
+
+    // Create election authority public and private key
+    let (authority_secret, authority_public) = generate_keypair();
+
+    // Create a ballot
+    let ballot_id = "TEST";
+
+    let ballot = Ballot {
+        id: ballot_id.to_string(),
+        contests: vec![0],
+        properties: indexmap::IndexMap::new(),
+    };
+
+    let contest = Contest {
+        id: "TESTCONTEST".to_string(),
+        index: 0,
+        contest_type: ContestType::Approval,
+        write_in: false,
+        num_winners: 1,
+        candidates: vec![
+            Candidate {
+                id: "Barak Obama".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+            Candidate {
+                id: "Santa".to_string(),
+                properties: indexmap::IndexMap::new(),
+            },
+        ],
+        homomorphic: true,
+        max_score: None,
+        properties: indexmap::IndexMap::new(),
+    };
+
+    // Create an authenticator
+    let (authenticator, authn_secrets) =
+        Authenticator::new(256, &vec![ballot_id.to_string()]).unwrap();
+    let authn_secret = authn_secrets.get(ballot_id).unwrap();
+    let authn_public = authenticator.public_keys.get(ballot_id).unwrap();
+
+    // Create 3 trustees
+    let (trustee_1, trustee_1_secret) = Trustee::new(1, 3, 2);
+    let (trustee_2, trustee_2_secret) = Trustee::new(2, 3, 2);
+    let (trustee_3, trustee_3_secret) = Trustee::new(3, 3, 2);
+
+    // Create an election transaction with a single ballot
+    let mut election = ElectionTransaction::new(authority_public);
+    election.ballots = vec![ballot];
+    election.contests = vec![contest];
+    election.authenticators = vec![authenticator.clone()];
+    election.trustees = vec![trustee_1.clone(), trustee_2.clone(), trustee_3.clone()];
+    election.trustees_threshold = 2;
+
+    // Finalize election transaction by signing it
+    let election = Signed::sign(&authority_secret, election).unwrap();
+
+    // Validate the election transaction and store it
+    election.validate(&store).unwrap();
+    store.set(election.clone().into());
+
+    // Generate keygen_commitment transactions for each trustee
+    let x25519_public_1 = trustee_1.x25519_public_key(&trustee_1_secret, election.id);
+    let commit_1 = trustee_1.keygen_commitment(&trustee_1_secret, election.id);
+
+    let commit_1_tx = KeyGenCommitmentTransaction::new(
+        election.id,
+        trustee_1.index,
+        trustee_1.public_key,
+        x25519_public_1,
+        commit_1,
+    );
+    let commit_1_tx = Signed::sign(&trustee_1_secret, commit_1_tx).unwrap();
+    commit_1_tx.validate(&store).unwrap();
+    store.set(commit_1_tx.clone().into());
+
+    let x25519_public_2 = trustee_2.x25519_public_key(&trustee_2_secret, election.id);
+    let commit_2 = trustee_1.keygen_commitment(&trustee_2_secret, election.id);
+    let commit_2_tx = KeyGenCommitmentTransaction::new(
+        election.id,
+        trustee_2.index,
+        trustee_2.public_key,
+        x25519_public_2,
+        commit_2,
+    );
+    let commit_2_tx = Signed::sign(&trustee_2_secret, commit_2_tx).unwrap();
+    commit_2_tx.validate(&store).unwrap();
+    store.set(commit_2_tx.clone().into());
+
+    let x25519_public_3 = trustee_3.x25519_public_key(&trustee_3_secret, election.id);
+    let commit_3 = trustee_3.keygen_commitment(&trustee_3_secret, election.id);
+    let commit_3_tx = KeyGenCommitmentTransaction::new(
+        election.id,
+        trustee_3.index,
+        trustee_3.public_key,
+        x25519_public_3,
+        commit_3,
+    );
+    let commit_3_tx = Signed::sign(&trustee_3_secret, commit_3_tx).unwrap();
+    commit_3_tx.validate(&store).unwrap();
+    store.set(commit_3_tx.clone().into());
+
+    // Grab cmommitments out of the commitment transactions
+    let commitments = [
+        (
+            commit_1_tx.inner().trustee_index,
+            commit_1_tx.inner().commitment.clone(),
+        ),
+        (
+            commit_2_tx.inner().trustee_index,
+            commit_2_tx.inner().commitment.clone(),
+        ),
+        (
+            commit_3_tx.inner().trustee_index,
+            commit_3_tx.inner().commitment.clone(),
+        ),
+    ];
+
+    // Grab x25519 public key out of the commitment transactions
+    let x25519_public_keys = [
+        (
+            commit_1_tx.inner().trustee_index,
+            commit_1_tx.inner().x25519_public_key.clone(),
+        ),
+        (
+            commit_2_tx.inner().trustee_index,
+            commit_2_tx.inner().x25519_public_key.clone(),
+        ),
+        (
+            commit_3_tx.inner().trustee_index,
+            commit_3_tx.inner().x25519_public_key.clone(),
+        ),
+    ];
+
+    // Generate keygen_share transaction for each trustee
+    let share_1 = trustee_1.generate_shares(
+        &mut test_rng,
+        &trustee_1_secret,
+        &x25519_public_keys,
+        election.id,
+        &commitments,
+    );
+    let share_1_tx = KeyGenShareTransaction::new(
+        election.id,
+        trustee_1.index,
+        trustee_1.public_key,
+        share_1.clone(),
+    );
+    let share_1_tx = Signed::sign(&trustee_1_secret, share_1_tx).unwrap();
+    share_1_tx.validate(&store).unwrap();
+    store.set(share_1_tx.clone().into());
+
+    let share_2 = trustee_2.generate_shares(
+        &mut test_rng,
+        &trustee_2_secret,
+        &x25519_public_keys,
+        election.id,
+        &commitments,
+    );
+    let share_2_tx = KeyGenShareTransaction::new(
+        election.id,
+        trustee_2.index,
+        trustee_2.public_key,
+        share_2.clone(),
+    );
+    let share_2_tx = Signed::sign(&trustee_2_secret, share_2_tx).unwrap();
+    share_2_tx.validate(&store).unwrap();
+    store.set(share_2_tx.clone().into());
+
+    let share_3 = trustee_3.generate_shares(
+        &mut test_rng,
+        &trustee_3_secret,
+        &x25519_public_keys,
+        election.id,
+        &commitments,
+    );
+    let share_3_tx = KeyGenShareTransaction::new(
+        election.id,
+        trustee_3.index,
+        trustee_3.public_key,
+        share_3.clone(),
+    );
+    let share_3_tx = Signed::sign(&trustee_3_secret, share_3_tx).unwrap();
+    share_3_tx.validate(&store).unwrap();
+    store.set(share_3_tx.clone().into());
+
+    // Generate keygen_public_key transaction for each trustee
+    let all_shares = vec![
+        (trustee_1.index, &share_1),
+        (trustee_2.index, &share_2),
+        (trustee_3.index, &share_3),
+    ];
+
+    let pk_1_shares: Vec<(u8, EncryptedShare)> = all_shares
+        .iter()
+        .map(|m| (m.0, m.1.get(&trustee_1.index).unwrap().clone()))
+        .collect();
+    let (pk_1, pk_1_proof) = trustee_1
+        .generate_public_key(
+            &trustee_1_secret,
+            &x25519_public_keys,
+            &commitments,
+            &pk_1_shares,
+            election.id,
+        )
+        .unwrap();
+    let pk_1_tx = KeyGenPublicKeyTransaction::new(
+        election.id,
+        trustee_1.index,
+        trustee_1.public_key,
+        pk_1,
+        pk_1_proof,
+        vec![],
+    );
+    let pk_1_tx = Signed::sign(&trustee_1_secret, pk_1_tx).unwrap();
+    pk_1_tx.validate(&store).unwrap();
+    store.set(pk_1_tx.clone().into());
+
+    let pk_2_shares: Vec<(u8, EncryptedShare)> = all_shares
+        .iter()
+        .map(|m| (m.0, m.1.get(&trustee_2.index).unwrap().clone()))
+        .collect();
+    let (pk_2, pk_2_proof) = trustee_2
+        .generate_public_key(
+            &trustee_2_secret,
+            &x25519_public_keys,
+            &commitments,
+            &pk_2_shares,
+            election.id,
+        )
+        .unwrap();
+    let pk_2_tx = KeyGenPublicKeyTransaction::new(
+        election.id,
+        trustee_2.index,
+        trustee_2.public_key,
+        pk_2,
+        pk_2_proof,
+        vec![],
+    );
+    let pk_2_tx = Signed::sign(&trustee_2_secret, pk_2_tx).unwrap();
+    pk_2_tx.validate(&store).unwrap();
+    store.set(pk_2_tx.clone().into());
+
+    let pk_3_shares: Vec<(u8, EncryptedShare)> = all_shares
+        .iter()
+        .map(|m| (m.0, m.1.get(&trustee_3.index).unwrap().clone()))
+        .collect();
+    let (pk_3, pk_3_proof) = trustee_3
+        .generate_public_key(
+            &trustee_3_secret,
+            &x25519_public_keys,
+            &commitments,
+            &pk_3_shares,
+            election.id,
+        )
+        .unwrap();
+    let pk_3_tx = KeyGenPublicKeyTransaction::new(
+        election.id,
+        trustee_3.index,
+        trustee_3.public_key,
+        pk_3,
+        pk_3_proof,
+        vec![],
+    );
+    let pk_3_tx = Signed::sign(&trustee_3_secret, pk_3_tx).unwrap();
+    pk_3_tx.validate(&store).unwrap();
+    store.set(pk_3_tx.clone().into());
+
+    // Generate an encryption_key transaction
+    let encryption_key_tx =
+        EncryptionKeyTransaction::new(election.id, authority_public, pk_1_tx.inner().public_key);
+    let encryption_key_tx = Signed::sign(&authority_secret, encryption_key_tx).unwrap();
+    encryption_key_tx.validate(&store).unwrap();
+    store.set(encryption_key_tx.clone().into());
+
+    // Cast two votes, each approving of a different set of candidates
+    let ballots = vec![vec!["Barak Obama", "Santa"], vec!["Barak Obama"]];
+    let mut votes = Vec::new();
+    for approved in ballots {
+        let selections: Vec<Selection> = approved
+            .into_iter()
+            .map(|candidate| Selection {
+                write_in: false,
+                score: 0,
+                selection: candidate.to_string(),
+            })
+            .collect();
+
+        // Encrypt the secret vote as a counter for each candidate, proving that each counter is zero or one
+        let (voter_secret, voter_public) = generate_keypair();
+        let encrypted_vote = encrypt_vote_with_proofs(
+            &encryption_key_tx.encryption_key,
+            election.id,
+            &voter_public,
+            &election.contests[0],
+            selections,
+            &mut test_rng,
+        )
+        .unwrap();
+        assert_eq!(encrypted_vote.selections.len(), 2);
+
+        let mut vote = VoteTransaction::with_anonymous_key(
+            election.id(),
+            ballot_id.to_string(),
+            vec![encrypted_vote],
+            voter_public,
+        );
+
+        // Authenticate the voter
+        let auth_package =
+            AuthPackage::new(election.id(), ballot_id.to_string(), vote.anonymous_key);
+        let (blinded_auth_package, unblinder) = auth_package.blind(&authn_public, None).unwrap();
+        let authentication = authenticator
+            .authenticate(&authn_secret, &blinded_auth_package, None)
+            .unwrap();
+        let authentication = authentication.unblind(&authn_public, unblinder).unwrap();
+        vote.authentication.push(authentication);
+
+        // A vote missing a counter should fail validation
+        let mut missing_counter = vote.clone();
+        missing_counter.encrypted_votes[0].selections.pop();
+        missing_counter.encrypted_votes[0].proofs.pop();
+        let missing_counter = Signed::sign(&voter_secret, missing_counter).unwrap();
+        assert!(missing_counter.validate(&store).is_err());
+
+        // Counters swapped between candidates no longer match their proofs
+        let mut swapped = vote.clone();
+        swapped.encrypted_votes[0].selections.swap(0, 1);
+        let swapped = Signed::sign(&voter_secret, swapped).unwrap();
+        assert!(swapped.validate(&store).is_err());
+
+        // Repeating the entry for a contest would count the voter twice, even though each copy's proofs verify
+        let mut repeated = vote.clone();
+        repeated
+            .encrypted_votes
+            .push(repeated.encrypted_votes[0].clone());
+        let repeated = Signed::sign(&voter_secret, repeated).unwrap();
+        assert!(matches!(
+            repeated.validate(&store),
+            Err(ValidationError::DuplicateContestVote(0))
+        ));
+
+        // Validate the vote transaction and store it
+        let vote = Signed::sign(&voter_secret, vote).unwrap();
+        vote.validate(&store).unwrap();
+        store.set(vote.clone().into());
+        votes.push(vote);
+    }
+
+    // A counter for anything other than zero or one can't be proven
+    let (_, voter_public) = generate_keypair();
+    let mut contest = election.contests[0].clone();
+    contest.contest_type = ContestType::Score;
+    contest.max_score = Some(2);
+    let overvote = encrypt_vote_with_proofs(
+        &encryption_key_tx.encryption_key,
+        election.id,
+        &voter_public,
+        &contest,
+        vec![Selection {
+            write_in: false,
+            score: 2,
+            selection: "Santa".to_string(),
+        }],
+        &mut test_rng,
+    )
+    .unwrap();
+    assert!(verify_vote_proofs(
+        &encryption_key_tx.encryption_key,
+        election.id,
+        &voter_public,
+        &election.contests[0],
+        &overvote,
+    )
+    .is_err());
+
+    // Voting is over!
+    // ---------------
+
+    // Generate VotingEnd transaction to mark the end of voting
+    let voting_end_tx =
+        VotingEndTransaction::new(election.id, election.authority_public, unix_time());
+    let voting_end_tx = Signed::sign(&authority_secret, voting_end_tx).unwrap();
+    voting_end_tx.validate(&store).unwrap();
+    store.set(voting_end_tx.clone().into());
+
+    // Individual votes in a homomorphic contest can't be decrypted
+    let partial_decrypt_vote = trustee_1
+        .partial_decrypt(
+            &mut test_rng,
+            &trustee_1_secret,
+            &x25519_public_keys,
+            &commitments,
+            &pk_1_shares,
+            &votes[0].encrypted_votes[0].selections[0],
+            election.id,
+        )
+        .unwrap();
+    let partial_decrypt_vote_tx = PartialDecryptionTransaction::new(
+        election.id,
+        votes[0].id,
+        0,
+        trustee_1.index,
+        0,
+        trustee_1.public_key,
+        vec![partial_decrypt_vote],
+    );
+    let partial_decrypt_vote_tx = Signed::sign(&trustee_1_secret, partial_decrypt_vote_tx).unwrap();
+    assert!(partial_decrypt_vote_tx.validate(&store).is_err());
+
+    // Instead, trustees partially decrypt the sum of all the votes
+    let (totals, num_votes) =
+        homomorphic_aggregate(&store, election.id, &election.contests[0]).unwrap();
+    assert_eq!(num_votes, 2);
+
+    let partial_decrypt_1 = totals
+        .iter()
+        .map(|total| {
+            trustee_1
+                .partial_decrypt(
+                    &mut test_rng,
+                    &trustee_1_secret,
+                    &x25519_public_keys,
+                    &commitments,
+                    &pk_1_shares,
+                    total,
+                    election.id,
+                )
+                .unwrap()
+        })
+        .collect();
+    let partial_decrypt_1_tx = PartialDecryptionTransaction::new(
+        election.id,
+        voting_end_tx.id,
+        0,
+        trustee_1.index,
+        0,
+        trustee_1.public_key,
+        partial_decrypt_1,
+    );
+    let partial_decrypt_1_tx = Signed::sign(&trustee_1_secret, partial_decrypt_1_tx).unwrap();
+    partial_decrypt_1_tx.validate(&store).unwrap();
+    store.set(partial_decrypt_1_tx.clone().into());
+
+    let partial_decrypt_2 = totals
+        .iter()
+        .map(|total| {
+            trustee_2
+                .partial_decrypt(
+                    &mut test_rng,
+                    &trustee_2_secret,
+                    &x25519_public_keys,
+                    &commitments,
+                    &pk_2_shares,
+                    total,
+                    election.id,
+                )
+                .unwrap()
+        })
+        .collect();
+    let partial_decrypt_2_tx = PartialDecryptionTransaction::new(
+        election.id,
+        voting_end_tx.id,
+        0,
+        trustee_2.index,
+        0,
+        trustee_2.public_key,
+        partial_decrypt_2,
+    );
+    let partial_decrypt_2_tx = Signed::sign(&trustee_2_secret, partial_decrypt_2_tx).unwrap();
+    partial_decrypt_2_tx.validate(&store).unwrap();
+    store.set(partial_decrypt_2_tx.clone().into());
+
+    let partials = vec![
+        partial_decrypt_1_tx.tx.clone(),
+        partial_decrypt_2_tx.tx.clone(),
+    ];
+    let pubkeys = vec![pk_1_tx.tx.clone(), pk_2_tx.tx.clone(), pk_3_tx.tx.clone()];
+
+    // Fully decrypt the totals
+    let decrypted = decrypt_totals(
+        &election.contests[0],
+        &totals,
+        num_votes,
+        election.trustees_threshold,
+        &election.trustees,
+        &pubkeys,
+        &partials,
+    )
+    .unwrap();
+    assert_eq!(decrypted[0].score, 2);
+    assert_eq!(decrypted[1].score, 1);
+
+    // A decryption transaction with the wrong totals should fail validation
+    let mut bad_decrypted = decrypted.clone();
+    bad_decrypted[1].score = 2;
+    let bad_decrypted_tx = DecryptionTransaction::new(
+        election.id,
+        voting_end_tx.id,
+        0,
+        0,
+        vec![trustee_1.index, trustee_2.index],
+        bad_decrypted,
+    );
+    let bad_decrypted_tx = Signed::sign(&trustee_1_secret, bad_decrypted_tx).unwrap();
+    assert!(bad_decrypted_tx.validate(&store).is_err());
+
+    // Create a decryption transaction for the totals
+    let decrypted_tx = DecryptionTransaction::new(
+        election.id,
+        voting_end_tx.id,
+        0,
+        0,
+        vec![trustee_1.index, trustee_2.index],
+        decrypted,
+    );
+    let decrypted_tx = Signed::sign(&trustee_1_secret, decrypted_tx).unwrap();
+    decrypted_tx.validate(&store).unwrap();
+    store.set(decrypted_tx.clone().into());
+
+    // Tally the election
+    let tally = tally_election(&store, &election).unwrap();
+    assert_eq!(tally["TESTCONTEST"].num_votes, 2);
+    assert_eq!(
+        tally["TESTCONTEST"].winners.clone().into_unranked(),
+        vec!["Barak Obama".to_string()]
+    );
+
+    let tally_tx = TallyTransaction::new(election.id, tally);
+    let tally_tx = Signed::sign(&authority_secret, tally_tx).unwrap();
+    tally_tx.validate(&store).unwrap();
+    store.set(tally_tx.clone().into());
+}
+
+// A plurality contest between two candidates
+fn simulated_contest(index: u32, id: &str, homomorphic: bool) -> Contest {
+    Contest {
+        id: id.to_string(),
+        index,
+        contest_type: ContestType::Plurality,
+        write_in: false,
+        num_winners: 1,
+        candidates: ["Barak Obama", "Santa"]
+            .iter()
+            .map(|name| Candidate {
+                id: name.to_string(),
+                properties: indexmap::IndexMap::new(),
+            })
+            .collect(),
+        homomorphic,
+        max_score: None,
+        properties: indexmap::IndexMap::new(),
+    }
+}
+
+// An election with a mixed and a homomorphic contest
+fn simulation_spec(num_trustees: u8, trustees_threshold: u8) -> ElectionSpec {
+    ElectionSpec {
+        num_trustees,
+        trustees_threshold,
+        num_authenticators: 1,
+        authenticators_threshold: 1,
+        ballots: vec![Ballot {
+            id: "TEST".to_string(),
+            contests: vec![0, 1],
+            properties: indexmap::IndexMap::new(),
+        }],
+        contests: vec![
+            simulated_contest(0, "MIXED", false),
+            simulated_contest(1, "SUMMED", true),
+        ],
+        mix_config: Some(MixConfig {
+            timeout_secs: 600,
+            batch_size: None,
+        }),
+        num_voters: 5,
+        bad_shares: vec![],
+        reshare: None,
+    }
+}
+
+// Run a simulated election where every voter picks a candidate at random, checking the tally against the votes cast
+// and that the election's archive replays without failures
+fn run_simulation(spec: &ElectionSpec) -> (MemStore, SimulationResult, ArchiveManifest) {
+    let mut store = MemStore::default();
+    store.set_time(1_600_000_000);
+    let result = simulate_election(
+        [3; 32],
+        spec,
+        &mut store,
+        |store, tx| store.set(tx),
+        |rng: &mut dyn rand::RngCore, _voter: usize, contest: &Contest| {
+            let candidate = rng.next_u32() as usize % contest.candidates.len();
+            vec![Selection {
+                write_in: false,
+                score: 0,
+                selection: contest.candidates[candidate].id.clone(),
+            }]
+        },
+    )
+    .unwrap();
+
+    for (contest_index, contest_id) in [(0, "MIXED"), (1, "SUMMED")].iter() {
+        let tally = &result.tally[*contest_id];
+        assert_eq!(tally.num_votes, spec.num_voters);
+        for candidate in ["Barak Obama", "Santa"].iter() {
+            let expected = result
+                .votes
+                .iter()
+                .filter(|(_, vote)| vote[*contest_index].selections[0].selection == *candidate)
+                .count();
+            let total = tally.totals.get(*candidate).cloned().unwrap_or_default();
+            assert_eq!(total, rust_decimal::Decimal::from(expected));
+        }
+    }
+
+    let mut archive_bytes = Vec::new();
+    let manifest = write_archive(&store, result.election.id, &mut archive_bytes).unwrap();
+    let verification = verify_archive(read_archive(archive_bytes.as_slice()).unwrap());
+    assert!(verification.failures.is_empty());
+    assert_eq!(
+        verification.tally.unwrap()["MIXED"].num_votes,
+        spec.num_voters
+    );
+
+    (store, result, manifest)
+}
+
+// The transactions of a type in a simulated election, and a store holding everything posted before the first of them
+fn simulated_transactions(
+    result: &SimulationResult,
+    tx_type: TransactionType,
+) -> (Vec<SignedTransaction>, MemStore) {
+    let txs = result
+        .transactions
+        .iter()
+        .filter(|tx| tx.transaction_type() == tx_type)
+        .cloned()
+        .collect();
+    let before: Vec<SignedTransaction> = result
+        .transactions
+        .iter()
+        .take_while(|tx| tx.transaction_type() != tx_type)
+        .cloned()
+        .collect();
+
+    (txs, before.into())
+}
+
+#[test]
+fn end_to_end_election_reshare() {
+    // Once voting has ended, trustees 1 and 2 reshare the election key to 4 new trustees with a threshold of 3, who
+    // then mix and decrypt the votes
+    let mut spec = simulation_spec(3, 2);
+    spec.reshare = Some(ReshareSpec {
+        num_trustees: 4,
+        trustees_threshold: 3,
+    });
+    let (store, result, manifest) = run_simulation(&spec);
+    let election = &result.election;
+
+    // The archive replays the reshare before the end of voting, even though it is numbered after the tally
+    assert_eq!(manifest.counts[&TransactionType::ReshareShare], 2);
+    assert_eq!(manifest.counts[&TransactionType::ResharePublicKey], 4);
+
+    // The reshare has taken effect, and the mix chain is as long as the new threshold
+    let current = current_trustees(&store, election).unwrap();
+    assert_eq!(current.generation, 1);
+    assert_eq!(current.threshold, 3);
+    assert_eq!(manifest.counts[&TransactionType::Mix], 3);
+
+    // A dealer's commitment must reshare its own key share. Moving part of one dealer's key share onto another leaves
+    // the reshared key unchanged, but is caught for each dealer.
+    let (share_txs, before_shares) = simulated_transactions(&result, TransactionType::ReshareShare);
+    let shares: Vec<ReshareShareTransaction> = share_txs.into_iter().map(Into::into).collect();
+    shares[0].validate_tx(&before_shares).unwrap();
+
+    let shift = cryptid::curve::CurveElem::generator();
+    let mut shifted = shares.clone();
+    shifted[0].commitment[0] = shifted[0].commitment[0] + shift;
+    shifted[1].commitment[0] = shifted[1].commitment[0] - shift;
+    assert!(matches!(
+        shifted[0].validate_tx(&before_shares),
+        Err(ValidationError::ReshareDealerKeyMismatch(1))
+    ));
+    assert!(matches!(
+        shifted[1].validate_tx(&before_shares),
+        Err(ValidationError::ReshareDealerKeyMismatch(2))
+    ));
+
+    // Only the dealers can reshare
+    let mut not_dealer = shares[0].clone();
+    not_dealer.trustee_index = 3;
+    not_dealer.id = ReshareShareTransaction::build_id(election.id, 1, 3);
+    assert!(matches!(
+        not_dealer.validate_tx(&before_shares),
+        Err(ValidationError::TrusteeNotDealer(3))
+    ));
+
+    // The original trustees hold the key until every new trustee has posted their verification key, which must match
+    // the dealers' commitments
+    let (public_key_txs, before_public_keys) =
+        simulated_transactions(&result, TransactionType::ResharePublicKey);
+    assert_eq!(
+        current_trustees(&before_public_keys, election)
+            .unwrap()
+            .generation,
+        0
+    );
+
+    let mut bad_public_key: ResharePublicKeyTransaction = public_key_txs[0].clone().into();
+    bad_public_key.verification_key = bad_public_key.verification_key + shift;
+    assert!(matches!(
+        bad_public_key.validate_tx(&before_public_keys),
+        Err(ValidationError::VerificationKeyMismatch(1))
+    ));
+
+    // The key can be reshared again until mixing starts, but not once it has
+    let (reshare_txs, _) = simulated_transactions(&result, TransactionType::TrusteeReshare);
+    let mut late_reshare: TrusteeReshareTransaction = reshare_txs[0].clone().into();
+    late_reshare.generation = 2;
+    late_reshare.id = TrusteeReshareTransaction::build_id(election.id, 2);
+    late_reshare.dealers = vec![1, 2, 3];
+
+    let (_, before_mixes) = simulated_transactions(&result, TransactionType::Mix);
+    late_reshare.validate_tx(&before_mixes).unwrap();
+    assert!(matches!(
+        late_reshare.validate_tx(&store),
+        Err(ValidationError::ReshareAfterDecryptionStarted)
+    ));
+
+    // An original trustee can no longer partially decrypt
+    let (partial_txs, _) = simulated_transactions(&result, TransactionType::PartialDecryption);
+    let mut old_partial: PartialDecryptionTransaction = partial_txs[0].clone().into();
+    let old_trustee = election.get_trustee(old_partial.trustee_index).unwrap();
+    old_partial.trustee_public_key = old_trustee.public_key;
+    assert!(matches!(
+        old_partial.validate_tx(&store),
+        Err(ValidationError::TrusteeDoesNotExist(_))
+    ));
+}
+
+#[test]
+fn end_to_end_election_keygen_complaint() {
+    // Trustee 1 sends trustee 3 an invalid share. Trustee 3 only complains once trustees 1 and 2 have posted their public
+    // keys, so trustee 2 posts a new one without trustee 1. Trustees 2 and 3 then mix and decrypt, generating their key
+    // shares from 2 qualified trustees while trustee 3 keeps its index.
+    let mut test_rng = rand::rngs::StdRng::from_seed([0u8; 32]);
+    let mut spec = simulation_spec(3, 2);
+    spec.bad_shares = vec![(1, 3)];
+    let (store, result, manifest) = run_simulation(&spec);
+    let election = &result.election;
+    let (trustee_2, trustee_2_secret) = &result.trustees[1];
+    let (trustee_3, trustee_3_secret) = &result.trustees[2];
+
+    // The archive replays the complaint before the public keys, even though it is numbered after the tally
+    assert_eq!(manifest.counts[&TransactionType::KeyGenComplaint], 1);
+    assert_eq!(manifest.counts[&TransactionType::KeyGenPublicKey], 4);
+    assert_eq!(manifest.counts[&TransactionType::Mix], 2);
+
+    let qualified: Vec<u8> = qualified_trustees(&store, election)
+        .unwrap()
+        .iter()
+        .map(|trustee| trustee.index)
+        .collect();
+    assert_eq!(qualified, vec![2, 3]);
+
+    // A commitment with the wrong number of coefficients, or a small-order x25519 key, is rejected
+    let (commitment_txs, before_commitments) =
+        simulated_transactions(&result, TransactionType::KeyGenCommitment);
+    let commitment_tx: KeyGenCommitmentTransaction = commitment_txs[0].clone().into();
+    commitment_tx.validate_tx(&before_commitments).unwrap();
+
+    let (trustee_1, trustee_1_secret) = &result.trustees[0];
+    let mut short_trustee = trustee_1.clone();
+    short_trustee.threshold -= 1;
+    let mut short_commitment = commitment_tx.clone();
+    short_commitment.commitment = short_trustee.keygen_commitment(trustee_1_secret, election.id);
+    assert!(matches!(
+        short_commitment.validate_tx(&before_commitments),
+        Err(ValidationError::InvalidKeyGenCommitment(1))
+    ));
+
+    let mut small_order_key = commitment_tx;
+    small_order_key.x25519_public_key = x25519_dalek::PublicKey::from([0u8; 32]);
+    assert!(matches!(
+        small_order_key.validate_tx(&before_commitments),
+        Err(ValidationError::InvalidX25519PublicKey(1))
+    ));
+
+    // Trustee 3 finds the bad share, and can't generate its public key from it
+    let commitments: Vec<_> = store
+        .get_multiple(election.id, TransactionType::KeyGenCommitment)
+        .map(|tx| {
+            let tx: KeyGenCommitmentTransaction = tx.unwrap().into();
+            (tx.trustee_index, tx.commitment)
+        })
+        .collect();
+    let x25519_public_keys = trustee_x25519_public_keys(&store, election.id, 0).unwrap();
+    let received: Vec<(u8, EncryptedShare)> = store
+        .get_multiple(election.id, TransactionType::KeyGenShare)
+        .map(|tx| {
+            let tx: KeyGenShareTransaction = tx.unwrap().into();
+            (tx.trustee_index, tx.shares[&3].clone())
+        })
+        .collect();
+    let invalid = trustee_3.invalid_shares(
+        trustee_3_secret,
+        &x25519_public_keys,
+        &commitments,
+        &received,
+        election.id,
+    );
+    assert_eq!(invalid, vec![1]);
+    assert!(matches!(
+        trustee_3.generate_public_key(
+            trustee_3_secret,
+            &x25519_public_keys,
+            &commitments,
+            &received,
+            election.id,
+        ),
+        Err(ValidationError::InvalidKeyGenShare(1))
+    ));
+
+    // The complaint is still accepted after public keys have been posted
+    let (complaint_txs, before_complaints) =
+        simulated_transactions(&result, TransactionType::KeyGenComplaint);
+    assert_eq!(
+        before_complaints
+            .get_multiple(election.id, TransactionType::KeyGenPublicKey)
+            .count(),
+        2
+    );
+    let complaint: KeyGenComplaintTransaction = complaint_txs[0].clone().into();
+    assert_eq!((complaint.trustee_index, complaint.accused_index), (3, 1));
+    complaint.validate_tx(&before_complaints).unwrap();
+
+    // A complaint about a valid share is rejected
+    let proof = trustee_2
+        .shared_secret_proof(
+            &mut test_rng,
+            trustee_2_secret,
+            election.id,
+            &x25519_public_keys[0].1,
+        )
+        .unwrap();
+    let valid_share_complaint =
+        KeyGenComplaintTransaction::new(election.id, 2, trustee_2.public_key, 1, proof);
+    assert!(matches!(
+        valid_share_complaint.validate_tx(&before_complaints),
+        Err(ValidationError::KeyGenShareValid(1))
+    ));
+
+    // A complaint revealing the wrong shared secret is rejected
+    let proof = trustee_3
+        .shared_secret_proof(
+            &mut test_rng,
+            trustee_3_secret,
+            election.id,
+            &x25519_public_keys[1].1,
+        )
+        .unwrap();
+    let wrong_secret_complaint =
+        KeyGenComplaintTransaction::new(election.id, 3, trustee_3.public_key, 1, proof);
+    assert!(matches!(
+        wrong_secret_complaint.validate_tx(&before_complaints),
+        Err(ValidationError::InvalidSharedSecretProof)
+    ));
+
+    // Complaints can't be made once the election key has been posted
+    assert!(matches!(
+        complaint.validate_tx(&store),
+        Err(ValidationError::KeyGenComplaintTooLate)
+    ));
+
+    // The public keys posted before the complaint stay valid, but only the new ones without trustee 1 are used
+    let public_keys: Vec<KeyGenPublicKeyTransaction> = result
+        .transactions
+        .iter()
+        .filter(|tx| tx.transaction_type() == TransactionType::KeyGenPublicKey)
+        .map(|tx| tx.clone().into())
+        .collect();
+    let listed: Vec<(u8, Vec<u8>)> = public_keys
+        .iter()
+        .map(|tx| (tx.trustee_index, tx.disqualified.clone()))
+        .collect();
+    assert_eq!(
+        listed,
+        vec![(1, vec![]), (2, vec![]), (2, vec![1]), (3, vec![1])]
+    );
+    public_keys[0].validate_tx(&store).unwrap();
+
+    let qualified_keys: Vec<u8> = qualified_public_keys(&store, election.id)
+        .unwrap()
+        .iter()
+        .map(|tx| tx.trustee_index)
+        .collect();
+    assert_eq!(qualified_keys, vec![2, 3]);
+    assert!(qualified_public_key(&store, election.id, 1).is_err());
+
+    // A public key can only list disqualified trustees
+    let trustee_2_key = &public_keys[2];
+    trustee_2_key.validate_tx(&store).unwrap();
+    assert!(matches!(
+        trustee_2_key.validate_tx(&before_complaints),
+        Err(ValidationError::InvalidDisqualifiedTrustees(2))
+    ));
+    let mut wrongly_listed = trustee_2_key.clone();
+    wrongly_listed.disqualified = vec![3];
+    assert!(matches!(
+        wrongly_listed.validate_tx(&store),
+        Err(ValidationError::InvalidDisqualifiedTrustees(2))
+    ));
+
+    // The id depends on how many trustees are listed
+    let mut unlisted = trustee_2_key.clone();
+    unlisted.disqualified = vec![];
+    assert!(matches!(
+        unlisted.validate_tx(&store),
+        Err(ValidationError::IdentifierBadComposition)
+    ));
+
+    // A public key proof must match the trustee's own share of the commitments
+    let mut swapped = trustee_2_key.clone();
+    swapped.public_key_proof = public_keys[3].public_key_proof.clone();
+    assert!(matches!(
+        swapped.validate_tx(&store),
+        Err(ValidationError::KeyGenPublicKeyMismatch(2))
+    ));
+
+    // The disqualified trustee can't post a public key without itself
+    let mut disqualified_key = public_keys[0].clone();
+    disqualified_key.disqualified = vec![1];
+    disqualified_key.id = KeyGenPublicKeyTransaction::build_id(election.id, 1, 1);
+    assert!(matches!(
+        disqualified_key.validate_tx(&store),
+        Err(ValidationError::TrusteeDisqualified(1))
+    ));
+
+    // The election key needs a new public key from every qualified trustee
+    let (encryption_key_txs, before_encryption_key) =
+        simulated_transactions(&result, TransactionType::EncryptionKey);
+    let encryption_key: EncryptionKeyTransaction = encryption_key_txs[0].clone().into();
+    encryption_key.validate_tx(&before_encryption_key).unwrap();
+
+    let missing_trustee_3: MemStore = result
+        .transactions
+        .iter()
+        .take_while(|tx| tx.transaction_type() != TransactionType::EncryptionKey)
+        .filter(|tx| tx.id() != public_keys[3].id)
+        .cloned()
+        .collect::<Vec<_>>()
+        .into();
+    assert!(matches!(
+        encryption_key.validate_tx(&missing_trustee_3),
+        Err(ValidationError::MissingKeyGenPublicKeyTransaction(3))
+    ));
+}
@@ -7,7 +7,6 @@ use ed25519_dalek::Verifier;
 use num_enum::IntoPrimitive;
 use num_enum::TryFromPrimitive;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use std::cmp::Ordering;
 use std::convert::AsRef;
 use std::convert::From;
 use std::convert::TryFrom;
@@ -31,6 +30,11 @@ pub enum Transaction {
     Mix(MixTransaction),
     PartialDecryption(PartialDecryptionTransaction),
     Decryption(DecryptionTransaction),
+    Tally(TallyTransaction),
+    TrusteeReshare(TrusteeReshareTransaction),
+    ReshareShare(ReshareShareTransaction),
+    ResharePublicKey(ResharePublicKeyTransaction),
+    KeyGenComplaint(KeyGenComplaintTransaction),
 }
 
 impl Transaction {
@@ -47,6 +51,11 @@ impl Transaction {
             Transaction::Mix(_) => TransactionType::Mix,
             Transaction::PartialDecryption(_) => TransactionType::PartialDecryption,
             Transaction::Decryption(_) => TransactionType::Decryption,
+            Transaction::Tally(_) => TransactionType::Tally,
+            Transaction::TrusteeReshare(_) => TransactionType::TrusteeReshare,
+            Transaction::ReshareShare(_) => TransactionType::ReshareShare,
+            Transaction::ResharePublicKey(_) => TransactionType::ResharePublicKey,
+            Transaction::KeyGenComplaint(_) => TransactionType::KeyGenComplaint,
         }
     }
 
@@ -63,6 +72,11 @@ impl Transaction {
             Transaction::Mix(tx) => tx.id,
             Transaction::PartialDecryption(tx) => tx.id,
             Transaction::Decryption(tx) => tx.id,
+            Transaction::Tally(tx) => tx.id,
+            Transaction::TrusteeReshare(tx) => tx.id,
+            Transaction::ReshareShare(tx) => tx.id,
+            Transaction::ResharePublicKey(tx) => tx.id,
+            Transaction::KeyGenComplaint(tx) => tx.id,
         }
     }
 
@@ -82,6 +96,11 @@ impl Transaction {
             Transaction::Mix(tx) => tx.validate_tx(s),
             Transaction::PartialDecryption(tx) => tx.validate_tx(s),
             Transaction::Decryption(tx) => tx.validate_tx(s),
+            Transaction::Tally(tx) => tx.validate_tx(s),
+            Transaction::TrusteeReshare(tx) => tx.validate_tx(s),
+            Transaction::ReshareShare(tx) => tx.validate_tx(s),
+            Transaction::ResharePublicKey(tx) => tx.validate_tx(s),
+            Transaction::KeyGenComplaint(tx) => tx.validate_tx(s),
         }
     }
 }
@@ -101,6 +120,11 @@ pub enum SignedTransaction {
     Mix(Signed<MixTransaction>),
     PartialDecryption(Signed<PartialDecryptionTransaction>),
     Decryption(Signed<DecryptionTransaction>),
+    Tally(Signed<TallyTransaction>),
+    TrusteeReshare(Signed<TrusteeReshareTransaction>),
+    ReshareShare(Signed<ReshareShareTransaction>),
+    ResharePublicKey(Signed<ResharePublicKeyTransaction>),
+    KeyGenComplaint(Signed<KeyGenComplaintTransaction>),
 }
 
 impl SignedTransaction {
@@ -117,6 +141,11 @@ impl SignedTransaction {
             SignedTransaction::Mix(_) => TransactionType::Mix,
             SignedTransaction::PartialDecryption(_) => TransactionType::PartialDecryption,
             SignedTransaction::Decryption(_) => TransactionType::Decryption,
+            SignedTransaction::Tally(_) => TransactionType::Tally,
+            SignedTransaction::TrusteeReshare(_) => TransactionType::TrusteeReshare,
+            SignedTransaction::ReshareShare(_) => TransactionType::ReshareShare,
+            SignedTransaction::ResharePublicKey(_) => TransactionType::ResharePublicKey,
+            SignedTransaction::KeyGenComplaint(_) => TransactionType::KeyGenComplaint,
         }
     }
 
@@ -143,6 +172,11 @@ impl SignedTransaction {
             SignedTransaction::Mix(signed) => signed.tx.id,
             SignedTransaction::PartialDecryption(signed) => signed.tx.id,
             SignedTransaction::Decryption(signed) => signed.tx.id,
+            SignedTransaction::Tally(signed) => signed.tx.id,
+            SignedTransaction::TrusteeReshare(signed) => signed.tx.id,
+            SignedTransaction::ReshareShare(signed) => signed.tx.id,
+            SignedTransaction::ResharePublicKey(signed) => signed.tx.id,
+            SignedTransaction::KeyGenComplaint(signed) => signed.tx.id,
         }
     }
 
@@ -165,6 +199,58 @@ impl SignedTransaction {
             SignedTransaction::Mix(tx) => tx.validate(s),
             SignedTransaction::PartialDecryption(tx) => tx.validate(s),
             SignedTransaction::Decryption(tx) => tx.validate(s),
+            SignedTransaction::Tally(tx) => tx.validate(s),
+            SignedTransaction::TrusteeReshare(tx) => tx.validate(s),
+            SignedTransaction::ReshareShare(tx) => tx.validate(s),
+            SignedTransaction::ResharePublicKey(tx) => tx.validate(s),
+            SignedTransaction::KeyGenComplaint(tx) => tx.validate(s),
+        }
+    }
+
+    /// Validate the transaction against the store, without verifying the signature
+    ///
+    /// This is used when signatures are verified separately, such as in bulk (see `verify_signatures`).
+    pub fn validate_tx<S: Store>(&self, s: &S) -> Result<(), ValidationError> {
+        if self.id().transaction_type != self.transaction_type() {
+            return Err(ValidationError::MismatchedTransactionType);
+        }
+        match self {
+            SignedTransaction::Election(tx) => tx.validate_tx(s),
+            SignedTransaction::KeyGenCommitment(tx) => tx.validate_tx(s),
+            SignedTransaction::KeyGenShare(tx) => tx.validate_tx(s),
+            SignedTransaction::KeyGenPublicKey(tx) => tx.validate_tx(s),
+            SignedTransaction::EncryptionKey(tx) => tx.validate_tx(s),
+            SignedTransaction::Vote(tx) => tx.validate_tx(s),
+            SignedTransaction::VotingEnd(tx) => tx.validate_tx(s),
+            SignedTransaction::Mix(tx) => tx.validate_tx(s),
+            SignedTransaction::PartialDecryption(tx) => tx.validate_tx(s),
+            SignedTransaction::Decryption(tx) => tx.validate_tx(s),
+            SignedTransaction::Tally(tx) => tx.validate_tx(s),
+            SignedTransaction::TrusteeReshare(tx) => tx.validate_tx(s),
+            SignedTransaction::ReshareShare(tx) => tx.validate_tx(s),
+            SignedTransaction::ResharePublicKey(tx) => tx.validate_tx(s),
+            SignedTransaction::KeyGenComplaint(tx) => tx.validate_tx(s),
+        }
+    }
+
+    /// The public key, signed message and signature, if the transaction has a public key
+    pub(crate) fn signature_parts(&self) -> Option<(PublicKey, Vec<u8>, Signature)> {
+        match self {
+            SignedTransaction::Election(tx) => tx.signature_parts(),
+            SignedTransaction::KeyGenCommitment(tx) => tx.signature_parts(),
+            SignedTransaction::KeyGenShare(tx) => tx.signature_parts(),
+            SignedTransaction::KeyGenPublicKey(tx) => tx.signature_parts(),
+            SignedTransaction::EncryptionKey(tx) => tx.signature_parts(),
+            SignedTransaction::Vote(tx) => tx.signature_parts(),
+            SignedTransaction::VotingEnd(tx) => tx.signature_parts(),
+            SignedTransaction::Mix(tx) => tx.signature_parts(),
+            SignedTransaction::PartialDecryption(tx) => tx.signature_parts(),
+            SignedTransaction::Decryption(tx) => tx.signature_parts(),
+            SignedTransaction::Tally(tx) => tx.signature_parts(),
+            SignedTransaction::TrusteeReshare(tx) => tx.signature_parts(),
+            SignedTransaction::ReshareShare(tx) => tx.signature_parts(),
+            SignedTransaction::ResharePublicKey(tx) => tx.signature_parts(),
+            SignedTransaction::KeyGenComplaint(tx) => tx.signature_parts(),
         }
     }
 
@@ -180,6 +266,11 @@ impl SignedTransaction {
             SignedTransaction::Mix(tx) => tx.verify_signature(),
             SignedTransaction::PartialDecryption(tx) => tx.verify_signature(),
             SignedTransaction::Decryption(tx) => tx.verify_signature(),
+            SignedTransaction::Tally(tx) => tx.verify_signature(),
+            SignedTransaction::TrusteeReshare(tx) => tx.verify_signature(),
+            SignedTransaction::ReshareShare(tx) => tx.verify_signature(),
+            SignedTransaction::ResharePublicKey(tx) => tx.verify_signature(),
+            SignedTransaction::KeyGenComplaint(tx) => tx.verify_signature(),
         }
     }
 
@@ -195,6 +286,11 @@ impl SignedTransaction {
             SignedTransaction::Mix(tx) => tx.public(),
             SignedTransaction::PartialDecryption(tx) => tx.public(),
             SignedTransaction::Decryption(tx) => tx.public(),
+            SignedTransaction::Tally(tx) => tx.public(),
+            SignedTransaction::TrusteeReshare(tx) => tx.public(),
+            SignedTransaction::ReshareShare(tx) => tx.public(),
+            SignedTransaction::ResharePublicKey(tx) => tx.public(),
+            SignedTransaction::KeyGenComplaint(tx) => tx.public(),
         }
     }
 }
@@ -236,6 +332,11 @@ mod sealed {
     impl Sealed for crate::MixTransaction {}
     impl Sealed for crate::PartialDecryptionTransaction {}
     impl Sealed for crate::DecryptionTransaction {}
+    impl Sealed for crate::TallyTransaction {}
+    impl Sealed for crate::TrusteeReshareTransaction {}
+    impl Sealed for crate::ReshareShareTransaction {}
+    impl Sealed for crate::ResharePublicKeyTransaction {}
+    impl Sealed for crate::KeyGenComplaintTransaction {}
 }
 
 /// A generic signed transaction
@@ -279,6 +380,13 @@ impl<T: CryptoBallotTransaction + Serialize> Signed<T> {
         }
     }
 
+    // The public key, signed message and signature, if the transaction has a public key
+    fn signature_parts(&self) -> Option<(PublicKey, Vec<u8>, Signature)> {
+        let public = self.tx.public()?;
+
+        Some((public, self.tx.as_bytes(), self.sig))
+    }
+
     /// Get the inner unsigned transaction
     pub fn inner(&self) -> &T {
         &self.tx
@@ -316,15 +424,19 @@ impl<T: CryptoBallotTransaction + Serialize> Deref for Signed<T> {
 ///
 /// The identifier defines the election, transction-type, and a unique identifier.
 ///
-/// TODO: Entire aligned-struct is 40 bytes, larger than our ideal Copy limit of 32 bytes
-///       We should combine election_id and tx_type and have utility functions
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+/// Every field is byte-aligned, so the identifier is laid out exactly as its 32 byte binary form (see `to_array`),
+/// and orders the same way: by election, then transaction-type, then unique-info.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
 pub struct Identifier {
-    pub election_id: [u8; 15],             // Will align to 16 bytes
-    pub transaction_type: TransactionType, // Will align to 8 bytes
-    pub unique_info: [u8; 16],             // Will align to 16 bytes
+    pub election_id: [u8; 15],             // Bytes 0..15
+    pub transaction_type: TransactionType, // Byte 15
+    pub unique_info: [u8; 16],             // Bytes 16..32
 }
 
+// Identifiers are passed by value everywhere, so make sure they never grow past 32 bytes
+const _: [(); 32] = [(); std::mem::size_of::<Identifier>()];
+
 impl Identifier {
     /// Creat a new Identifier
     pub fn new(
@@ -402,6 +514,19 @@ impl Identifier {
         return Some(Self::new(election_id, transaction_type, Some(unique_info)));
     }
 
+    /// Decode an identifier from its 32 byte binary form
+    pub fn from_array(bytes: [u8; 32]) -> Result<Self, Error> {
+        let transaction_type = TransactionType::try_from_primitive(bytes[15])
+            .map_err(|_| Error::IdentifierBadType(bytes[15]))?;
+
+        // These unwraps are OK - the slices are the correct length
+        Ok(Identifier {
+            election_id: bytes[0..15].try_into().unwrap(),
+            transaction_type,
+            unique_info: bytes[16..32].try_into().unwrap(),
+        })
+    }
+
     pub fn to_array(&self) -> [u8; 32] {
         let mut bytes: [u8; 32] = [0; 32];
         bytes[0..15].clone_from_slice(&self.election_id);
@@ -430,17 +555,11 @@ impl FromStr for Identifier {
             return Err(Error::IdentifierBadLen);
         }
 
-        // These unwraps are OK - we know the length is valid
-        let election_id: [u8; 15] = bytes[0..15].try_into().unwrap();
-        let transaction_type = TransactionType::try_from_primitive(bytes[15]).unwrap();
+        // A 16 byte identifier has no unique-info
+        let mut array = [0; 32];
+        array[..bytes.len()].copy_from_slice(&bytes);
 
-        let unique_info: [u8; 16] = bytes[16..].try_into().unwrap();
-
-        Ok(Identifier {
-            election_id,
-            transaction_type,
-            unique_info,
-        })
+        Identifier::from_array(array)
     }
 }
 
@@ -469,44 +588,6 @@ impl std::fmt::Display for Identifier {
     }
 }
 
-impl PartialOrd for Identifier {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let election_ord = self.election_id.cmp(&other.election_id);
-
-        if let Ordering::Equal = election_ord {
-            let tx_type = self.transaction_type as u8;
-            let other_tx_type = other.transaction_type as u8;
-            let tx_type_ord = tx_type.cmp(&other_tx_type);
-            if let Ordering::Equal = tx_type_ord {
-                Some(self.unique_info.cmp(&other.unique_info))
-            } else {
-                Some(tx_type_ord)
-            }
-        } else {
-            Some(election_ord)
-        }
-    }
-}
-
-impl Ord for Identifier {
-    fn cmp(&self, other: &Self) -> Ordering {
-        let election_ord = self.election_id.cmp(&other.election_id);
-
-        if let Ordering::Equal = election_ord {
-            let tx_type = self.transaction_type as u8;
-            let other_tx_type = other.transaction_type as u8;
-            let tx_type_ord = tx_type.cmp(&other_tx_type);
-            if let Ordering::Equal = tx_type_ord {
-                self.unique_info.cmp(&other.unique_info)
-            } else {
-                tx_type_ord
-            }
-        } else {
-            election_ord
-        }
-    }
-}
-
 impl From<Identifier> for [u8; 32] {
     fn from(item: Identifier) -> Self {
         item.to_array()
@@ -516,7 +597,18 @@ impl From<Identifier> for [u8; 32] {
 /// A transaction type
 // TODO: Maybe make Election = 0 to align with identifiers in merkle-tree
 #[derive(
-    Serialize, Deserialize, TryFromPrimitive, IntoPrimitive, Copy, Debug, Clone, PartialEq, Eq, Hash,
+    Serialize,
+    Deserialize,
+    TryFromPrimitive,
+    IntoPrimitive,
+    Copy,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
 )]
 #[serde(rename_all = "snake_case")]
 #[repr(u8)]
@@ -531,9 +623,20 @@ pub enum TransactionType {
     Mix = 8,
     PartialDecryption = 9,
     Decryption = 10,
+    Tally = 11,
+    TrusteeReshare = 12,
+    ReshareShare = 13,
+    ResharePublicKey = 14,
+    KeyGenComplaint = 15,
 }
 
 impl TransactionType {
+    /// The lowest numbered transaction type, so an election's transactions start at its identifier
+    pub const FIRST: TransactionType = TransactionType::Election;
+
+    /// The highest numbered transaction type, so a range ending at it covers every transaction in an election
+    pub const LAST: TransactionType = TransactionType::KeyGenComplaint;
+
     pub fn hex_string(&self) -> &str {
         match self {
             TransactionType::Election => "01",
@@ -546,6 +649,11 @@ impl TransactionType {
             TransactionType::Mix => "08",
             TransactionType::PartialDecryption => "09",
             TransactionType::Decryption => "0a",
+            TransactionType::Tally => "0b",
+            TransactionType::TrusteeReshare => "0c",
+            TransactionType::ReshareShare => "0d",
+            TransactionType::ResharePublicKey => "0e",
+            TransactionType::KeyGenComplaint => "0f",
         }
     }
 
@@ -561,9 +669,44 @@ impl TransactionType {
             TransactionType::Mix => "mix",
             TransactionType::PartialDecryption => "partial_decryption",
             TransactionType::Decryption => "decryption",
+            TransactionType::Tally => "tally",
+            TransactionType::TrusteeReshare => "trustee_reshare",
+            TransactionType::ReshareShare => "reshare_share",
+            TransactionType::ResharePublicKey => "reshare_public_key",
+            TransactionType::KeyGenComplaint => "key_gen_complaint",
+        }
+    }
+
+    /// The order in which transactions of this type are posted during an election.
+    ///
+    /// This follows id order, except for transaction types numbered after the tally: key generation complaints are
+    /// posted before trustees publish their public keys, and trustee resharing happens before mixing and decryption.
+    /// Reshares are replayed before the end of voting, since they don't depend on when voting ended.
+    pub fn protocol_order(&self) -> u8 {
+        match self {
+            TransactionType::Election => 0,
+            TransactionType::KeyGenCommitment => 1,
+            TransactionType::KeyGenShare => 2,
+            TransactionType::KeyGenComplaint => 3,
+            TransactionType::KeyGenPublicKey => 4,
+            TransactionType::EncryptionKey => 5,
+            TransactionType::Vote => 6,
+            TransactionType::TrusteeReshare => 7,
+            TransactionType::ReshareShare => 8,
+            TransactionType::ResharePublicKey => 9,
+            TransactionType::VotingEnd => 10,
+            TransactionType::Mix => 11,
+            TransactionType::PartialDecryption => 12,
+            TransactionType::Decryption => 13,
+            TransactionType::Tally => 14,
         }
     }
 
+    /// Whether transactions of this type are posted before voting ends.
+    pub fn before_voting_end(&self) -> bool {
+        self.protocol_order() < TransactionType::VotingEnd.protocol_order()
+    }
+
     pub fn from_u8(numeric: u8) -> Option<Self> {
         Self::try_from(numeric).ok()
     }
@@ -670,6 +813,51 @@ impl From<SignedTransaction> for Signed<DecryptionTransaction> {
     }
 }
 
+impl From<SignedTransaction> for Signed<TallyTransaction> {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::Tally(tx) => tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for Signed<TrusteeReshareTransaction> {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::TrusteeReshare(tx) => tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for Signed<ReshareShareTransaction> {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::ReshareShare(tx) => tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for Signed<ResharePublicKeyTransaction> {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::ResharePublicKey(tx) => tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for Signed<KeyGenComplaintTransaction> {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::KeyGenComplaint(tx) => tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
 impl From<SignedTransaction> for ElectionTransaction {
     fn from(tx: SignedTransaction) -> Self {
         match tx {
@@ -760,6 +948,51 @@ impl From<SignedTransaction> for PartialDecryptionTransaction {
     }
 }
 
+impl From<SignedTransaction> for TallyTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::Tally(tx) => tx.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for TrusteeReshareTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::TrusteeReshare(tx) => tx.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for ReshareShareTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::ReshareShare(tx) => tx.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for ResharePublicKeyTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::ResharePublicKey(tx) => tx.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl From<SignedTransaction> for KeyGenComplaintTransaction {
+    fn from(tx: SignedTransaction) -> Self {
+        match tx {
+            SignedTransaction::KeyGenComplaint(tx) => tx.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
 impl From<Signed<ElectionTransaction>> for SignedTransaction {
     fn from(tx: Signed<ElectionTransaction>) -> Self {
         SignedTransaction::Election(tx)
@@ -820,6 +1053,36 @@ impl From<Signed<DecryptionTransaction>> for SignedTransaction {
     }
 }
 
+impl From<Signed<TallyTransaction>> for SignedTransaction {
+    fn from(tx: Signed<TallyTransaction>) -> Self {
+        SignedTransaction::Tally(tx)
+    }
+}
+
+impl From<Signed<TrusteeReshareTransaction>> for SignedTransaction {
+    fn from(tx: Signed<TrusteeReshareTransaction>) -> Self {
+        SignedTransaction::TrusteeReshare(tx)
+    }
+}
+
+impl From<Signed<ReshareShareTransaction>> for SignedTransaction {
+    fn from(tx: Signed<ReshareShareTransaction>) -> Self {
+        SignedTransaction::ReshareShare(tx)
+    }
+}
+
+impl From<Signed<ResharePublicKeyTransaction>> for SignedTransaction {
+    fn from(tx: Signed<ResharePublicKeyTransaction>) -> Self {
+        SignedTransaction::ResharePublicKey(tx)
+    }
+}
+
+impl From<Signed<KeyGenComplaintTransaction>> for SignedTransaction {
+    fn from(tx: Signed<KeyGenComplaintTransaction>) -> Self {
+        SignedTransaction::KeyGenComplaint(tx)
+    }
+}
+
 impl AsRef<ElectionTransaction> for SignedTransaction {
     fn as_ref(&self) -> &ElectionTransaction {
         match self {
@@ -910,6 +1173,51 @@ impl AsRef<DecryptionTransaction> for SignedTransaction {
     }
 }
 
+impl AsRef<TallyTransaction> for SignedTransaction {
+    fn as_ref(&self) -> &TallyTransaction {
+        match self {
+            SignedTransaction::Tally(signed) => &signed.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl AsRef<TrusteeReshareTransaction> for SignedTransaction {
+    fn as_ref(&self) -> &TrusteeReshareTransaction {
+        match self {
+            SignedTransaction::TrusteeReshare(signed) => &signed.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl AsRef<ReshareShareTransaction> for SignedTransaction {
+    fn as_ref(&self) -> &ReshareShareTransaction {
+        match self {
+            SignedTransaction::ReshareShare(signed) => &signed.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl AsRef<ResharePublicKeyTransaction> for SignedTransaction {
+    fn as_ref(&self) -> &ResharePublicKeyTransaction {
+        match self {
+            SignedTransaction::ResharePublicKey(signed) => &signed.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
+impl AsRef<KeyGenComplaintTransaction> for SignedTransaction {
+    fn as_ref(&self) -> &KeyGenComplaintTransaction {
+        match self {
+            SignedTransaction::KeyGenComplaint(signed) => &signed.tx,
+            _ => panic!("wrong transaction type expected"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
 
@@ -928,6 +1236,15 @@ mod test {
         assert!(TransactionType::Mix as u8 == 8);
         assert!(TransactionType::PartialDecryption as u8 == 9);
         assert!(TransactionType::Decryption as u8 == 10);
+        assert!(TransactionType::Tally as u8 == 11);
+        assert!(TransactionType::TrusteeReshare as u8 == 12);
+        assert!(TransactionType::ReshareShare as u8 == 13);
+        assert!(TransactionType::ResharePublicKey as u8 == 14);
+        assert!(TransactionType::KeyGenComplaint as u8 == 15);
+
+        // FIRST and LAST bound every transaction type
+        assert!(TransactionType::from_u8(TransactionType::FIRST as u8 - 1).is_none());
+        assert!(TransactionType::from_u8(TransactionType::LAST as u8 + 1).is_none());
 
         let mut rng = rand::thread_rng();
         let election_id = ElectionTransaction::build_id(rng.gen());
@@ -939,4 +1256,35 @@ mod test {
 
         assert_eq!(election_id, from_string);
     }
+
+    #[test]
+    fn test_identifier_binary_form() {
+        assert_eq!(std::mem::size_of::<Identifier>(), 32);
+
+        let mut rng = rand::thread_rng();
+        let election_id = ElectionTransaction::build_id(rng.gen());
+        let vote_id = Identifier::new(election_id, TransactionType::Vote, Some(rng.gen()));
+        let mix_id = Identifier::new(election_id, TransactionType::Mix, None);
+
+        assert_eq!(Identifier::from_array(vote_id.to_array()).unwrap(), vote_id);
+
+        // Identifiers order the same as their binary and hex forms
+        assert!(election_id < vote_id && vote_id < mix_id);
+        assert!(election_id.to_array() < vote_id.to_array());
+        assert!(vote_id.to_array() < mix_id.to_array());
+        assert!(vote_id.to_string() < mix_id.to_string());
+
+        // Unknown transaction types are rejected rather than panicking
+        let mut bad = vote_id.to_array();
+        bad[15] = 0;
+        assert!(matches!(
+            Identifier::from_array(bad),
+            Err(Error::IdentifierBadType(0))
+        ));
+        assert!(Identifier::from_str(&hex::encode(bad)).is_err());
+
+        // A 16 byte identifier has no unique-info
+        let short = Identifier::from_str(&hex::encode(&mix_id.to_array()[..16])).unwrap();
+        assert_eq!(short, mix_id);
+    }
 }
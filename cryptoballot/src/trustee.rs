@@ -82,7 +82,8 @@ impl Trustee {
     }
 
     pub fn keygen_commitment(&self, sk: &SecretKey, election_id: Identifier) -> KeygenCommitment {
-        self.generator(sk, election_id).get_commitment()
+        self.generator(sk, election_id, self.num_trustees)
+            .get_commitment()
     }
 
     pub fn generate_shares<R: Rng + CryptoRng>(
@@ -93,7 +94,7 @@ impl Trustee {
         election_id: Identifier,
         commitments: &[(u8, KeygenCommitment)],
     ) -> IndexMap<u8, EncryptedShare> {
-        let mut theshold_generator = self.generator(sk, election_id);
+        let mut theshold_generator = self.generator(sk, election_id, self.num_trustees);
 
         for (trustee_index, commitment) in commitments {
             theshold_generator
@@ -118,7 +119,7 @@ impl Trustee {
         shares
     }
 
-    fn decrypt_shares(
+    pub(crate) fn decrypt_shares(
         &self,
         sk: &SecretKey,
         shares: &[(u8, EncryptedShare)],
@@ -145,13 +146,18 @@ impl Trustee {
     }
 
     fn x25519_secret_key(&self, sk: &SecretKey, election_id: Identifier) -> x25519::StaticSecret {
+        x25519::StaticSecret::from(self.x25519_secret_bytes(sk, election_id))
+    }
+
+    // The x25519 secret key, before it is clamped
+    pub(crate) fn x25519_secret_bytes(&self, sk: &SecretKey, election_id: Identifier) -> [u8; 32] {
         // Generate a HKDF, using the election-id as the salt
         let h = Hkdf::<Sha256>::new(Some(&election_id.to_bytes()), sk.as_bytes());
         let mut secret = [0u8; 32];
         h.expand(b"cryptoballot_trustee_x25519_secret_key", &mut secret)
             .unwrap();
 
-        x25519::StaticSecret::from(secret)
+        secret
     }
 
     pub fn x25519_public_key(&self, sk: &SecretKey, election_id: Identifier) -> x25519::PublicKey {
@@ -160,7 +166,7 @@ impl Trustee {
         x25519::PublicKey::from(&secret)
     }
 
-    fn shared_secret(
+    pub(crate) fn shared_secret(
         &self,
         sk: &SecretKey,
         election_id: Identifier,
@@ -169,13 +175,7 @@ impl Trustee {
         let x25519_secret_key = self.x25519_secret_key(sk, election_id);
         let shared_secret = x25519_secret_key.diffie_hellman(sender);
 
-        // Generate a HKDF, using the election-id as the salt
-        let h = Hkdf::<Sha256>::new(Some(&election_id.to_bytes()), shared_secret.as_bytes());
-        let mut shared_bytes = [0u8; 32]; // 256 bits of security
-        h.expand(b"cryptoballot_trustee_shared_secret", &mut shared_bytes)
-            .unwrap();
-
-        shared_bytes
+        share_encryption_key(election_id, shared_secret.as_bytes())
     }
 
     pub fn generate_public_key(
@@ -187,7 +187,7 @@ impl Trustee {
         election_id: Identifier,
     ) -> Result<(ElGamalPublicKey, PubkeyProof), ValidationError> {
         let decryped_shares = self.decrypt_shares(sk, shares, x25519_public_keys, election_id)?;
-        let party = self.generate_party(sk, &commitments, &decryped_shares, election_id);
+        let party = self.generate_party(sk, &commitments, &decryped_shares, election_id)?;
         Ok((party.pubkey(), party.pubkey_proof()))
     }
 
@@ -202,13 +202,18 @@ impl Trustee {
         election_id: Identifier,
     ) -> Result<DecryptShare, ValidationError> {
         let decryped_shares = self.decrypt_shares(sk, shares, x25519_public_keys, election_id)?;
-        let party = self.generate_party(sk, &commitments, &decryped_shares, election_id);
+        let party = self.generate_party(sk, &commitments, &decryped_shares, election_id)?;
 
         Ok(party.decrypt_share(encrypted_vote, rng))
     }
 
     // Generate a cryptid generator derived from the secret-key
-    fn generator(&self, sk: &SecretKey, election_id: Identifier) -> ThresholdGenerator {
+    fn generator(
+        &self,
+        sk: &SecretKey,
+        election_id: Identifier,
+        num_trustees: usize,
+    ) -> ThresholdGenerator {
         // Generate a HKDF, using the election-id as the salt
         let h = Hkdf::<Sha256>::new(Some(&election_id.to_bytes()), sk.as_bytes());
         let mut seed = [0u8; 32]; // 256 bits of security
@@ -221,35 +226,52 @@ impl Trustee {
             &mut rng,
             self.index as usize,
             self.threshold as usize,
-            self.num_trustees,
+            num_trustees,
         )
     }
 
+    // Commitments and shares from trustees disqualified by a KeyGenComplaint are left out by the caller, so the party is
+    // generated from the qualified trustees only. The generator finishes once it has received `n` commitments and shares,
+    // so `n` is the number of qualified trustees. Trustees keep their original indexes, which can be larger than `n` once
+    // a low index has been disqualified (covered by the keygen complaint test).
     fn generate_party(
         &self,
         sk: &SecretKey,
         commitments: &[(u8, KeygenCommitment)],
         shares: &[(u8, Scalar)],
         election_id: Identifier,
-    ) -> ThresholdParty {
-        let mut theshold_generator = self.generator(sk, election_id);
+    ) -> Result<ThresholdParty, ValidationError> {
+        let mut theshold_generator = self.generator(sk, election_id, commitments.len());
 
         for (index, commitment) in commitments {
             theshold_generator
                 .receive_commitment(*index as usize, commitment)
-                .expect("Invalid commitment") // TODO Result
+                .map_err(|_| ValidationError::InvalidKeyGenCommitment(*index))?;
         }
 
         for (index, share) in shares {
             theshold_generator
                 .receive_share(*index as usize, &share)
-                .expect("Invalid share") // TODO Result
+                .map_err(|_| ValidationError::InvalidKeyGenShare(*index))?;
         }
 
-        theshold_generator.finish().unwrap()
+        theshold_generator
+            .finish()
+            .map_err(|_| ValidationError::KeyGenIncomplete)
     }
 }
 
+/// The key used to encrypt shares between two trustees, derived from their x25519 shared secret
+pub(crate) fn share_encryption_key(election_id: Identifier, shared_secret: &[u8; 32]) -> [u8; 32] {
+    // Generate a HKDF, using the election-id as the salt
+    let h = Hkdf::<Sha256>::new(Some(&election_id.to_bytes()), shared_secret);
+    let mut shared_bytes = [0u8; 32]; // 256 bits of security
+    h.expand(b"cryptoballot_trustee_shared_secret", &mut shared_bytes)
+        .unwrap();
+
+    shared_bytes
+}
+
 #[derive(Clone, Debug)]
 pub struct EncryptedShare(Vec<u8>);
 
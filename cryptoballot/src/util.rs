@@ -1,6 +1,7 @@
 use ed25519_dalek::Keypair;
 use ed25519_dalek::PublicKey;
 use ed25519_dalek::SecretKey;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Generate an ed25519 keypair
 pub fn generate_keypair() -> (SecretKey, PublicKey) {
@@ -8,3 +9,18 @@ pub fn generate_keypair() -> (SecretKey, PublicKey) {
     let Keypair { public, secret } = Keypair::generate(&mut csprng);
     (secret, public)
 }
+
+/// The current system time, in seconds since the unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether a value is its type's default, for skipping fields that were added after transactions were first signed.
+///
+/// Skipping them keeps the serialization, and so the signature, of older transactions unchanged.
+pub(crate) fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
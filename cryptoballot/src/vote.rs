@@ -4,7 +4,7 @@ use ed25519_dalek::PublicKey;
 use ed25519_dalek::SecretKey;
 use prost::Message;
 use rand::{CryptoRng, RngCore};
-use std::convert::TryInto;
+use std::collections::HashSet;
 
 /// Transaction 6: Vote
 ///
@@ -14,6 +14,10 @@ use std::convert::TryInto;
 ///
 /// Before a voter can post a VoteTransaction, they must first be authenticated by a quorum of authenticator,
 /// who certify that they can vote this election and ballot.
+///
+/// Until voting ends, a voter may replace their vote by posting a new VoteTransaction signed by the same anonymous key,
+/// with the next `revision`. Only the latest revision of each voter's vote is mixed and tallied (see `latest_votes`).
+/// This lets a voter who was coerced into voting a certain way quietly cast their real vote later.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct VoteTransaction {
     pub id: Identifier,
@@ -29,12 +33,30 @@ pub struct VoteTransaction {
 
     /// A set of authentications, certifying that the anonymous_key provided can vote this election and ballot.
     pub authentication: Vec<Authentication>,
+
+    /// The revision of the voter's vote, starting at 0. Each replacement vote increments the revision by one.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "crate::util::is_default")]
+    pub revision: u16,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct EncryptedVote {
     pub contest_index: u32,
     pub selections: Vec<Ciphertext>,
+
+    /// Proofs that each selection is a valid selection for the contest, one per selection.
+    /// For homomorphic contests, the selections are counters and each proof shows that its counter is in range.
+    ///
+    /// Required for contests whose valid selections can be enumerated (see `Contest::valid_selections`).
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub proofs: Vec<SelectionProof>,
+
+    /// For homomorphic Plurality contests, a proof that no more than `num_winners` candidates were selected.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_proof: Option<SelectionProof>,
 }
 
 impl VoteTransaction {
@@ -46,25 +68,62 @@ impl VoteTransaction {
     ) -> (Self, SecretKey) {
         let (secret_key, public_key) = generate_keypair();
 
-        let vote = VoteTransaction {
-            id: Self::build_id(election_id, &public_key),
+        let vote = Self::with_anonymous_key(election_id, ballot_id, encrypted_votes, public_key);
+
+        (vote, secret_key)
+    }
+
+    /// Create a new vote transaction with an anonymous key generated in advance.
+    ///
+    /// Well-formedness proofs are bound to the anonymous key, so the voter must generate it before encrypting their vote.
+    pub fn with_anonymous_key(
+        election_id: Identifier,
+        ballot_id: String,
+        encrypted_votes: Vec<EncryptedVote>,
+        anonymous_key: PublicKey,
+    ) -> Self {
+        VoteTransaction {
+            id: Self::build_id(election_id, &anonymous_key, 0),
             election: election_id,
             ballot_id: ballot_id,
             encrypted_votes,
-            anonymous_key: public_key,
+            anonymous_key,
             authentication: vec![],
-        };
+            revision: 0,
+        }
+    }
 
-        (vote, secret_key)
+    /// Create a vote transaction that replaces a previous vote.
+    ///
+    /// The replacement keeps the previous vote's ballot, anonymous key and authentications, and must be signed with
+    /// the same anonymous secret key. The encrypted votes must be bound to the same anonymous key.
+    pub fn replace(previous: &VoteTransaction, encrypted_votes: Vec<EncryptedVote>) -> Self {
+        let revision = previous.revision + 1;
+
+        VoteTransaction {
+            id: Self::build_id(previous.election, &previous.anonymous_key, revision),
+            election: previous.election,
+            ballot_id: previous.ballot_id.clone(),
+            encrypted_votes,
+            anonymous_key: previous.anonymous_key,
+            authentication: previous.authentication.clone(),
+            revision,
+        }
     }
 
-    pub fn build_id(election_id: Identifier, public_key: &PublicKey) -> Identifier {
-        let unique_info = public_key.as_bytes();
-        Identifier::new(
-            election_id,
-            TransactionType::Vote,
-            Some(unique_info[0..16].try_into().unwrap()),
-        )
+    // The first 14 bytes of the anonymous key are followed by the next two bytes of the key plus the revision
+    // (wrapping), so that every revision of a voter's vote sorts together, and revision 0 keeps the original
+    // id made of the first 16 bytes of the key. Revisions aren't necessarily in id order, since the suffix can wrap, so
+    // `replay_election` sorts votes by revision before validating them.
+    pub fn build_id(election_id: Identifier, public_key: &PublicKey, revision: u16) -> Identifier {
+        let key = public_key.as_bytes();
+        let suffix = u16::from_be_bytes([key[14], key[15]]).wrapping_add(revision);
+
+        let mut unique_info = [0; 16];
+        unique_info[0..14].copy_from_slice(&key[0..14]);
+        unique_info[14..16].copy_from_slice(&suffix.to_be_bytes());
+
+        Identifier::new(election_id, TransactionType::Vote, Some(unique_info))
     }
 }
 
@@ -92,7 +151,7 @@ impl CryptoBallotTransaction for VoteTransaction {
     /// Validate the vote transaction
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         // Check the ID
-        if Self::build_id(self.election, &self.anonymous_key) != self.id {
+        if Self::build_id(self.election, &self.anonymous_key, self.revision) != self.id {
             return Err(ValidationError::IdentifierBadComposition);
         }
 
@@ -105,25 +164,76 @@ impl CryptoBallotTransaction for VoteTransaction {
         let start_collision =
             Identifier::start(self.election, TransactionType::Vote, Some(unique_info_mask));
         let end_collision =
-            Identifier::start(self.election, TransactionType::Vote, Some(unique_info_mask));
-        if store.range(start_collision, end_collision).len() > 0 {
+            Identifier::end(self.election, TransactionType::Vote, Some(unique_info_mask));
+        let previous_votes: Vec<SignedTransaction> = store
+            .range(start_collision, end_collision)
+            .collect::<Result<_, _>>()?;
+
+        // A replacement vote must directly follow the voter's latest vote
+        let previous_votes: Vec<&VoteTransaction> = previous_votes
+            .iter()
+            .map(AsRef::<VoteTransaction>::as_ref)
+            .collect();
+        if previous_votes
+            .iter()
+            .any(|previous| previous.anonymous_key != self.anonymous_key)
+        {
             return Err(ValidationError::VoteAnonymousKeyCollision);
         }
+        match previous_votes
+            .iter()
+            .max_by_key(|previous| previous.revision)
+        {
+            None if self.revision == 0 => {}
+            None => return Err(ValidationError::ReplacedVoteDoesNotExist),
+            Some(previous) => {
+                if self.revision == 0 {
+                    return Err(ValidationError::VoteAnonymousKeyCollision);
+                }
+                if previous.revision.checked_add(1) != Some(self.revision) {
+                    return Err(ValidationError::VoteRevisionOutOfOrder(self.revision));
+                }
+                if previous.ballot_id != self.ballot_id {
+                    return Err(ValidationError::ReplacementVoteWrongBallot);
+                }
+            }
+        }
 
         // Validate that there is a EncryptionKeyTransaction
         let enc_key_tx = Identifier::new(self.election, TransactionType::EncryptionKey, None);
-        if store.get_transaction(enc_key_tx).is_none() {
-            return Err(ValidationError::EncryptionKeyTransactionDoesNotExist);
-        }
+        let enc_key_tx: EncryptionKeyTransaction = match store.get_transaction(enc_key_tx)? {
+            Some(tx) => tx.into(),
+            None => return Err(ValidationError::EncryptionKeyTransactionDoesNotExist),
+        };
 
         // Validate that there isn't a VotingEnd Transactipn
-        let enc_key_tx = Identifier::new(self.election, TransactionType::VotingEnd, None);
-        if store.get_transaction(enc_key_tx).is_some() {
+        let voting_end_tx = Identifier::new(self.election, TransactionType::VotingEnd, None);
+        if store.get_transaction(voting_end_tx)?.is_some() {
             return Err(ValidationError::VotingHasEnded);
         }
 
-        // TODO: minimum authentication needed to be defined in election
+        // Validate that voting is open
+        let now = store.current_time()?;
+        if let Some(voting_start) = election.voting_start {
+            if now < voting_start {
+                return Err(ValidationError::VotingNotStarted);
+            }
+        }
+        if let Some(voting_end) = election.voting_end {
+            if now >= voting_end {
+                return Err(ValidationError::VotingHasEnded);
+            }
+        }
+
+        // Validate authentications, each must come from a distinct authenticator
+        let mut authenticators = HashSet::with_capacity(self.authentication.len());
         for authn in self.authentication.iter() {
+            if !authenticators.insert(authn.authenticator) {
+                return Err(ValidationError::DuplicateAuthentication(
+                    authn.authenticator,
+                ));
+            }
+
             let authenticator = election
                 .get_authenticator(authn.authenticator)
                 .ok_or(ValidationError::AuthDoesNotExist)?;
@@ -138,23 +248,112 @@ impl CryptoBallotTransaction for VoteTransaction {
                 .map_err(|_| ValidationError::AuthFailed)?;
         }
 
+        // Validate that enough authenticators have authenticated this voter
+        let required_authentications = election.authenticators_threshold as usize;
+        if authenticators.len() < required_authentications {
+            return Err(ValidationError::NotEnoughAuthentications(
+                required_authentications,
+                authenticators.len(),
+            ));
+        }
+
         let ballot = match election.get_ballot(&self.ballot_id) {
             Some(ballot) => ballot,
             None => return Err(ValidationError::BallotDoesNotExist),
         };
 
-        // Verify that the voter has only voted in contests for which they are authorized
+        // Verify that the voter has only voted in contests for which they are authorized, and only once in each. The
+        // selection proofs don't tie a vote to a single entry, so a repeated entry would otherwise be counted twice.
+        let mut contests = HashSet::with_capacity(self.encrypted_votes.len());
         for encrypted_vote in &self.encrypted_votes {
             if !ballot.contests.contains(&encrypted_vote.contest_index) {
                 return Err(ValidationError::VotedInWrongContest);
             }
+            if !contests.insert(encrypted_vote.contest_index) {
+                return Err(ValidationError::DuplicateContestVote(
+                    encrypted_vote.contest_index,
+                ));
+            }
+        }
+
+        // Verify that every selection is well-formed
+        for encrypted_vote in &self.encrypted_votes {
+            let contest = election
+                .get_contest(encrypted_vote.contest_index)
+                .ok_or(ValidationError::VotedInWrongContest)?;
+
+            verify_vote_proofs(
+                &enc_key_tx.encryption_key,
+                self.election,
+                &self.anonymous_key,
+                contest,
+                encrypted_vote,
+            )?;
         }
 
         Ok(())
     }
 }
 
+/// Iterate over the votes that count in an election: the latest revision of each voter's vote, in ascending vote-id order.
+///
+/// Votes are read from the store lazily.
+pub fn latest_votes<'a, S: Store>(
+    store: &'a S,
+    election_id: Identifier,
+) -> impl Iterator<Item = Result<VoteTransaction, StoreError>> + 'a {
+    let mut votes = store
+        .get_multiple(election_id, TransactionType::Vote)
+        .peekable();
+
+    std::iter::from_fn(move || {
+        let mut latest: VoteTransaction = match votes.next()? {
+            Ok(vote) => vote.into(),
+            Err(err) => return Some(Err(err)),
+        };
+
+        // Every revision of a voter's vote sorts together, though not necessarily in revision order
+        loop {
+            let replaced = match votes.peek() {
+                Some(Ok(next)) => {
+                    let next: &VoteTransaction = next.as_ref();
+                    next.anonymous_key == latest.anonymous_key
+                }
+                _ => false,
+            };
+            if !replaced {
+                break;
+            }
+            if let Some(Ok(next)) = votes.next() {
+                let next: VoteTransaction = next.into();
+                if next.revision > latest.revision {
+                    latest = next;
+                }
+            }
+        }
+
+        Some(Ok(latest))
+    })
+}
+
+/// Check that a vote has not been replaced by a later revision
+pub fn vote_is_latest<S: Store>(store: &S, vote: &VoteTransaction) -> Result<bool, StoreError> {
+    match vote.revision.checked_add(1) {
+        Some(next) => {
+            let next_id = VoteTransaction::build_id(vote.election, &vote.anonymous_key, next);
+            Ok(store.get_transaction(next_id)?.is_none())
+        }
+        None => Ok(true),
+    }
+}
+
 /// Encrypt a vote with the public key provided by the encryption_key transaction (EncryptionKeyTransaction.encryption_key)
+///
+/// The encryption randomness is discarded. Use `BenalohBallot` to allow the voter to audit the encryption.
+///
+/// No well-formedness proofs are produced, so this should only be used for contests that allow write-ins, or Score contests
+/// without a `max_score`.
+/// Use `encrypt_vote_with_proofs` to encrypt a vote for any contest.
 pub fn encrypt_vote<R: CryptoRng + RngCore>(
     encryption_key: &cryptid::elgamal::PublicKey,
     vote: Vec<Selection>,
@@ -2,23 +2,48 @@ use crate::*;
 use ed25519_dalek::PublicKey;
 
 /// Transaction 7: VotingEnd
+///
+/// Marks the end of voting. It is normally posted by the election authority, but if the election has a `voting_end`
+/// time, any trustee may post it once that time has passed, with `voting_end` as its timestamp.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct VotingEndTransaction {
     pub id: Identifier,
     pub election: Identifier,
+
+    /// The public key of the election authority, or of the trustee that ended voting at the scheduled `voting_end`
     #[serde(with = "EdPublicKeyHex")]
     pub authority_public_key: PublicKey,
+
+    /// The time voting ended, in seconds since the unix epoch
+    /// The first mix slot opens to other trustees `timeout_secs` after this time.
+    /// Zero for transactions that predate timestamps.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "crate::util::is_default")]
+    pub timestamp: u64,
 }
 
 impl VotingEndTransaction {
     /// Create a new DecryptionTransaction with the decrypted vote
-    pub fn new(election: Identifier, authority_public_key: PublicKey) -> Self {
+    pub fn new(election: Identifier, authority_public_key: PublicKey, timestamp: u64) -> Self {
         VotingEndTransaction {
             id: Identifier::new(election, TransactionType::VotingEnd, None),
             election: election,
             authority_public_key,
+            timestamp,
         }
     }
+
+    /// Create a VotingEnd transaction for an election whose scheduled `voting_end` has passed, to be signed by a trustee.
+    ///
+    /// Returns None if the election has no scheduled end.
+    pub fn scheduled(
+        election: &ElectionTransaction,
+        trustee_public_key: PublicKey,
+    ) -> Option<Self> {
+        let voting_end = election.voting_end?;
+
+        Some(Self::new(election.id, trustee_public_key, voting_end))
+    }
 }
 
 impl CryptoBallotTransaction for VotingEndTransaction {
@@ -45,13 +70,25 @@ impl CryptoBallotTransaction for VotingEndTransaction {
     /// Validate the transaction
     ///
     /// The validation does the following:
-    ///  - Validates that this transaction has been signed by a valid election authority
+    ///  - Validates that this transaction has been signed by a valid election authority,
+    ///    or by a trustee at the election's scheduled `voting_end`
+    ///  - Validates that the timestamp is not in the future
     fn validate_tx<S: Store>(&self, store: &S) -> Result<(), ValidationError> {
         let election = store.get_election(self.election)?;
 
-        // Validate the the election authority public key is the same
+        // Validate the the election authority public key is the same, or that a trustee is ending voting on schedule.
+        // Trustees replaced by a later reshare are accepted too, as archives replay reshares before the end of voting.
         if self.authority_public_key != election.authority_public {
-            return Err(ValidationError::AuthorityPublicKeyMismatch);
+            if !has_held_key(store, &election, &self.authority_public_key)? {
+                return Err(ValidationError::AuthorityPublicKeyMismatch);
+            }
+            if election.voting_end != Some(self.timestamp) {
+                return Err(ValidationError::VotingEndBeforeDeadline);
+            }
+        }
+
+        if self.timestamp > store.current_time()? {
+            return Err(ValidationError::TimestampInFuture(self.timestamp));
         }
 
         Ok(())
@@ -0,0 +1,347 @@
+use crate::*;
+use cryptid::curve::CurveElem;
+use cryptid::elgamal::{Ciphertext, PublicKey as EncryptionPublicKey};
+use curve25519_dalek::scalar::Scalar;
+use ed25519_dalek::PublicKey;
+use prost::Message;
+use rand::{CryptoRng, RngCore};
+use sha2::{Digest, Sha512};
+use std::convert::TryFrom;
+
+/// A non-interactive zero-knowledge proof that an encrypted selection is one of the valid selections for its contest.
+///
+/// This is a disjunctive Chaum-Pedersen proof: for every valid selection `M` it proves that `log_G(c1) == log_Y(c2 - M)`,
+/// where at most one of these statements can be true and the verifier can't tell which one. The proof is bound to the
+/// election-id, the voter's anonymous-key, the contest and the position of the selection, so it can't be replayed in another vote.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SelectionProof {
+    /// One branch per valid selection, in the order given by `Contest::valid_selections`
+    pub branches: Vec<ProofBranch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProofBranch {
+    #[serde(with = "ScalarHex")]
+    pub challenge: Scalar,
+
+    #[serde(with = "ScalarHex")]
+    pub response: Scalar,
+}
+
+/// Encrypt a vote for a single contest, attaching a proof that each selection is well-formed.
+///
+/// If the contest's valid selections can't be enumerated (see `Contest::valid_selections`), no proofs are attached.
+/// Votes for homomorphic contests are encrypted as counters instead (see `encrypt_homomorphic_vote`).
+pub fn encrypt_vote_with_proofs<R: CryptoRng + RngCore>(
+    encryption_key: &EncryptionPublicKey,
+    election_id: Identifier,
+    anonymous_key: &PublicKey,
+    contest: &Contest,
+    vote: Vec<Selection>,
+    rng: &mut R,
+) -> Result<EncryptedVote, Error> {
+    if contest.homomorphic {
+        return encrypt_homomorphic_vote(
+            encryption_key,
+            election_id,
+            anonymous_key,
+            contest,
+            vote,
+            rng,
+        );
+    }
+
+    let valid_selections = match contest.valid_selections() {
+        Some(valid_selections) => valid_selections,
+        None => {
+            return Ok(EncryptedVote {
+                contest_index: contest.index,
+                selections: encrypt_vote(encryption_key, vote, rng)?,
+                proofs: vec![],
+                limit_proof: None,
+            })
+        }
+    };
+
+    let messages = valid_selections
+        .iter()
+        .map(encode_selection)
+        .collect::<Result<Vec<CurveElem>, Error>>()?;
+
+    let context = ProofContext {
+        election_id,
+        anonymous_key: *anonymous_key,
+        contest_index: contest.index,
+    };
+
+    let mut selections = Vec::with_capacity(vote.len());
+    let mut proofs = Vec::with_capacity(vote.len());
+    for (position, selection) in vote.iter().enumerate() {
+        let real_branch = valid_selections
+            .iter()
+            .position(|valid| valid == selection)
+            .ok_or_else(|| Error::InvalidSelection(selection.selection.clone(), contest.index))?;
+
+        let randomness = Scalar::random(rng);
+        let ciphertext = encrypt_point(encryption_key, &messages[real_branch], &randomness);
+        let proof = prove_membership(
+            encryption_key,
+            &context,
+            position,
+            &ciphertext,
+            &randomness,
+            &messages,
+            real_branch,
+            rng,
+        )?;
+
+        selections.push(ciphertext);
+        proofs.push(proof);
+    }
+
+    Ok(EncryptedVote {
+        contest_index: contest.index,
+        selections,
+        proofs,
+        limit_proof: None,
+    })
+}
+
+/// Verify the well-formedness proofs attached to an encrypted vote.
+///
+/// Contests whose valid selections can't be enumerated (see `Contest::valid_selections`) are not checked.
+pub fn verify_vote_proofs(
+    encryption_key: &EncryptionPublicKey,
+    election_id: Identifier,
+    anonymous_key: &PublicKey,
+    contest: &Contest,
+    encrypted_vote: &EncryptedVote,
+) -> Result<(), ValidationError> {
+    if contest.homomorphic {
+        return verify_homomorphic_vote(
+            encryption_key,
+            election_id,
+            anonymous_key,
+            contest,
+            encrypted_vote,
+        );
+    }
+
+    let valid_selections = match contest.valid_selections() {
+        Some(valid_selections) => valid_selections,
+        None => return Ok(()),
+    };
+
+    let contest_index = contest.index;
+    if encrypted_vote.selections.len() > contest.candidates.len() {
+        return Err(ValidationError::TooManySelections(contest_index));
+    }
+    if encrypted_vote.proofs.len() != encrypted_vote.selections.len() {
+        return Err(ValidationError::SelectionProofMissing(contest_index));
+    }
+
+    let messages = valid_selections
+        .iter()
+        .map(encode_selection)
+        .collect::<Result<Vec<CurveElem>, Error>>()
+        .map_err(|_| ValidationError::SelectionProofFailed(contest_index))?;
+
+    let context = ProofContext {
+        election_id,
+        anonymous_key: *anonymous_key,
+        contest_index,
+    };
+
+    for (position, (ciphertext, proof)) in encrypted_vote
+        .selections
+        .iter()
+        .zip(encrypted_vote.proofs.iter())
+        .enumerate()
+    {
+        if !verify_membership(
+            encryption_key,
+            &context,
+            position,
+            ciphertext,
+            &messages,
+            proof,
+        ) {
+            return Err(ValidationError::SelectionProofFailed(contest_index));
+        }
+    }
+
+    Ok(())
+}
+
+// Everything a proof is bound to, other than the position of the ciphertext in the vote
+pub(crate) struct ProofContext {
+    pub(crate) election_id: Identifier,
+    pub(crate) anonymous_key: PublicKey,
+    pub(crate) contest_index: u32,
+}
+
+// Prove that the ciphertext is an encryption of `messages[real_branch]`, without revealing which message it encrypts.
+// The real branch is proven honestly, and every other branch is simulated.
+pub(crate) fn prove_membership<R: CryptoRng + RngCore>(
+    encryption_key: &EncryptionPublicKey,
+    context: &ProofContext,
+    position: usize,
+    ciphertext: &Ciphertext,
+    randomness: &Scalar,
+    messages: &[CurveElem],
+    real_branch: usize,
+    rng: &mut R,
+) -> Result<SelectionProof, Error> {
+    let mut challenges = Vec::with_capacity(messages.len());
+    let mut responses = Vec::with_capacity(messages.len());
+    let mut commitments = Vec::with_capacity(messages.len());
+    let nonce = Scalar::random(rng);
+    for (i, message) in messages.iter().enumerate() {
+        if i == real_branch {
+            challenges.push(Scalar::zero());
+            responses.push(Scalar::zero());
+            commitments.push((
+                CurveElem::generator().scaled(&to_cryptid(&nonce)),
+                encryption_key.y.scaled(&to_cryptid(&nonce)),
+            ));
+        } else {
+            let challenge = Scalar::random(rng);
+            let response = Scalar::random(rng);
+            commitments.push(branch_commitments(
+                encryption_key,
+                ciphertext,
+                message,
+                &challenge,
+                &response,
+            ));
+            challenges.push(challenge);
+            responses.push(response);
+        }
+    }
+
+    // The real branch takes whatever challenge is left over
+    let challenge = proof_challenge(context, position, ciphertext, &commitments)?;
+    let simulated: Scalar = challenges.iter().sum();
+    challenges[real_branch] = challenge - simulated;
+    responses[real_branch] = nonce + challenges[real_branch] * randomness;
+
+    Ok(SelectionProof {
+        branches: challenges
+            .into_iter()
+            .zip(responses)
+            .map(|(challenge, response)| ProofBranch {
+                challenge,
+                response,
+            })
+            .collect(),
+    })
+}
+
+// Verify that the ciphertext is an encryption of one of the messages
+pub(crate) fn verify_membership(
+    encryption_key: &EncryptionPublicKey,
+    context: &ProofContext,
+    position: usize,
+    ciphertext: &Ciphertext,
+    messages: &[CurveElem],
+    proof: &SelectionProof,
+) -> bool {
+    if proof.branches.len() != messages.len() {
+        return false;
+    }
+
+    let commitments: Vec<(CurveElem, CurveElem)> = proof
+        .branches
+        .iter()
+        .zip(messages.iter())
+        .map(|(branch, message)| {
+            branch_commitments(
+                encryption_key,
+                ciphertext,
+                message,
+                &branch.challenge,
+                &branch.response,
+            )
+        })
+        .collect();
+
+    let challenge = match proof_challenge(context, position, ciphertext, &commitments) {
+        Ok(challenge) => challenge,
+        Err(_) => return false,
+    };
+
+    let challenges: Scalar = proof.branches.iter().map(|branch| branch.challenge).sum();
+    challenges == challenge
+}
+
+// Encrypt a curve point: (c1, c2) = (r⋅G, M + r⋅Y)
+pub(crate) fn encrypt_point(
+    encryption_key: &EncryptionPublicKey,
+    message: &CurveElem,
+    randomness: &Scalar,
+) -> Ciphertext {
+    let randomness = to_cryptid(randomness);
+    Ciphertext {
+        c1: CurveElem::generator().scaled(&randomness),
+        c2: *message + encryption_key.y.scaled(&randomness),
+    }
+}
+
+// The curve point that cryptid encodes the given bytes as.
+// Encrypting under the public-key G gives (r⋅G, M + r⋅G), so the message point is c2 - c1.
+pub(crate) fn encode_bytes(bytes: &[u8]) -> CurveElem {
+    let key = EncryptionPublicKey::new(CurveElem::generator());
+    let ciphertext = key.encrypt(&mut rand::rngs::OsRng {}, bytes);
+
+    ciphertext.c2 - ciphertext.c1
+}
+
+pub(crate) fn to_cryptid(scalar: &Scalar) -> cryptid::Scalar {
+    // A dalek scalar is always canonical, so this can't fail
+    cryptid::Scalar::try_from(scalar.to_bytes().to_vec()).expect("cryptoballot: invalid scalar")
+}
+
+// Recompute the commitments (a, b) for a branch from its challenge e and response z:
+// a = z⋅G - e⋅c1 and b = z⋅Y - e⋅(c2 - M)
+fn branch_commitments(
+    encryption_key: &EncryptionPublicKey,
+    ciphertext: &Ciphertext,
+    message: &CurveElem,
+    challenge: &Scalar,
+    response: &Scalar,
+) -> (CurveElem, CurveElem) {
+    let challenge = to_cryptid(challenge);
+    let response = to_cryptid(response);
+
+    let a = CurveElem::generator().scaled(&response) - ciphertext.c1.scaled(&challenge);
+    let b = encryption_key.y.scaled(&response) - (ciphertext.c2 - *message).scaled(&challenge);
+
+    (a, b)
+}
+
+// Fiat-Shamir challenge, bound to the election, voter, contest and the position of the ciphertext
+fn proof_challenge(
+    context: &ProofContext,
+    position: usize,
+    ciphertext: &Ciphertext,
+    commitments: &[(CurveElem, CurveElem)],
+) -> Result<Scalar, Error> {
+    let mut hasher = Sha512::new();
+    hasher.update(b"cryptoballot selection proof");
+    hasher.update(context.election_id.to_string().as_bytes());
+    hasher.update(context.anonymous_key.as_bytes());
+    hasher.update(&context.contest_index.to_be_bytes());
+    hasher.update(&(position as u64).to_be_bytes());
+    hasher.update(&serde_cbor::to_vec(&(&ciphertext.c1, &ciphertext.c2))?);
+    hasher.update(&serde_cbor::to_vec(commitments)?);
+
+    Ok(Scalar::from_hash(hasher))
+}
+
+// The curve point that cryptid encodes a selection as
+fn encode_selection(selection: &Selection) -> Result<CurveElem, Error> {
+    let mut buf = Vec::with_capacity(selection.encoded_len());
+    selection.encode(&mut buf)?;
+
+    Ok(encode_bytes(&buf))
+}
@@ -0,0 +1,33 @@
+use cryptoballot::*;
+use std::io::Write;
+
+pub fn command_archive(matches: &clap::ArgMatches, uri: &str) {
+    let output = crate::expand(matches.value_of("OUTPUT").unwrap());
+
+    let transactions = crate::command_e2e::election_transactions(matches, uri);
+    let election_id = transactions[0].id();
+    let store = MemStore::from(transactions);
+
+    let file = match std::fs::File::create(&output) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("cryptoballot archive: cannot create {}: {}", output, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut writer = std::io::BufWriter::new(file);
+    let manifest = match write_archive(&store, election_id, &mut writer) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            eprintln!("cryptoballot archive: cannot write {}: {}", output, e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = writer.flush() {
+        eprintln!("cryptoballot archive: cannot write {}: {}", output, e);
+        std::process::exit(1);
+    }
+
+    println!("{}", serde_json::to_string_pretty(&manifest).unwrap());
+}
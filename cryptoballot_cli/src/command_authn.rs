@@ -19,16 +19,20 @@ pub fn command_authn_generate(matches: &clap::ArgMatches) {
         .unwrap()
         .parse()
         .expect("Invalid keysize");
+    let scheme = matches.value_of("scheme").unwrap();
 
     // TODO Check --quite
-    if keysize < 2048 {
+    if scheme == "rsa" && keysize < 2048 {
         eprintln!("cryptoballot: WARNING: Using insecure keysize for authn")
     }
 
     // For now just use the nil ballot id
     // TODO: Change this when we have ballot and contest system in place
     let ballot_ids = vec!["TEST".to_string()];
-    let (authn, secrets) = Authenticator::new(keysize, &ballot_ids).unwrap();
+    let (authn, secrets) = match scheme {
+        "schnorr" => Authenticator::new_schnorr(&ballot_ids),
+        _ => Authenticator::new(keysize, &ballot_ids).unwrap(),
+    };
 
     let mut file = File::create(&secret_location).unwrap_or_else(|e| {
         eprintln!(
@@ -1,40 +1,35 @@
 use cryptoballot::*;
 use std::str;
-use tallystick::plurality::DefaultPluralityTally;
-
-pub fn command_e2e(matches: &clap::ArgMatches, uri: &str) {
-    let election_id = crate::expand(matches.value_of("ELECTION-ID").unwrap());
-
-    if election_id.len() < 15 {
-        eprintln!("cryptoballot e2e: invalid election-id");
-        std::process::exit(1);
-    }
-    let prefix = &election_id[0..15];
 
+pub fn command_e2e(matches: &clap::ArgMatches, uri: &str) -> Result<(), Error> {
     let mut store = MemStore::default();
 
-    let transactions = crate::rest::get_transactions_by_prefix(uri, &prefix).unwrap();
-
-    if transactions.len() == 0 {
-        eprint!("No Transactions present");
-        std::process::exit(1)
-    }
+    // An archive is verified offline, without contacting the server
+    let (election_id, failures) = match matches.value_of("archive") {
+        Some(path) => {
+            let archive = read_archive_file(&crate::expand(path));
+            let election_id = archive.manifest.election_id;
+            let failures = replay_archive(archive, &mut store, |store, tx| store.set(tx));
+            (election_id, failures)
+        }
+        None => {
+            let transactions = election_transactions(matches, uri);
+            let election_id = transactions[0].id();
 
-    let first_transaction = &transactions[0];
-    if first_transaction.transaction_type() != TransactionType::Election {
-        eprint!("Frist transaction must be an election transaction");
-        std::process::exit(1)
-    }
-    let election_id = first_transaction.id();
-
-    for tx in transactions {
-        match tx.validate(&store) {
-            Ok(()) => store.set(tx),
-            Err(e) => {
-                eprint!("Failed to validate transaction {}: {}", tx.id(), e);
-                std::process::exit(1)
-            }
+            // Replay on the election's own clock, since voting has usually ended by the time it is verified
+            let failures = replay_election(transactions, &mut store, |store, tx| store.set(tx));
+            (election_id, failures)
+        }
+    };
+
+    if !failures.is_empty() {
+        for failure in failures {
+            eprintln!(
+                "Failed to validate transaction {}: {}",
+                failure.id, failure.error
+            );
         }
+        std::process::exit(1)
     }
 
     println!("> Election verified OK");
@@ -43,7 +38,7 @@ pub fn command_e2e(matches: &clap::ArgMatches, uri: &str) {
         println!("Votes:");
         let votes = store.get_multiple(election_id, TransactionType::Decryption);
         for vote in votes {
-            let vote: DecryptionTransaction = vote.into();
+            let vote: DecryptionTransaction = vote.unwrap().into();
             let vote = vote.decrypted_vote;
 
             for selection in vote {
@@ -54,40 +49,106 @@ pub fn command_e2e(matches: &clap::ArgMatches, uri: &str) {
         }
     }
 
-    if matches.is_present("print-tally") {
-        println!("Tally:");
-
-        // TODO: Use a real tally / ballot / contest system
-        let mut tally = DefaultPluralityTally::new(1);
-
-        let votes = store.get_multiple(election_id, TransactionType::Decryption);
-        for vote in votes {
-            let vote: DecryptionTransaction = vote.into();
-            let vote = vote.decrypted_vote;
-            tally.add(vote[0].selection.clone());
+    if matches.is_present("print-tally") || matches.is_present("print-results") {
+        let election = store.get_election(election_id).unwrap();
+        let tally = tally_election(&store, &election)?;
+
+        if matches.is_present("print-tally") {
+            println!("Tally:");
+            for (contest_id, result) in tally.iter() {
+                println!("  Contest {}:", contest_id);
+                for (candidate, num_votes) in result.totals.iter() {
+                    println!("    {} got {} votes", candidate, num_votes);
+                }
+            }
         }
 
-        for (candidate, num_votes) in tally.totals().iter() {
-            println!("  {} got {} votes", candidate, num_votes);
+        if matches.is_present("print-results") {
+            println!("Results:");
+            for (contest_id, result) in tally.iter() {
+                let winners = result.winners.clone().into_unranked();
+                println!("  Contest {}: {}", contest_id, winners.join(", "));
+            }
         }
     }
 
-    if matches.is_present("print-results") {
-        println!("Results:");
+    Ok(())
+}
 
-        // TODO: Use a real tally / ballot / contest system
-        let mut tally = DefaultPluralityTally::new(1);
+// Get every transaction in an election, from an SQLite database or the server, starting with the election transaction
+pub fn election_transactions(matches: &clap::ArgMatches, uri: &str) -> Vec<SignedTransaction> {
+    let election_id = crate::expand(matches.value_of("ELECTION-ID").unwrap());
 
-        let votes = store.get_multiple(election_id, TransactionType::Decryption);
-        for vote in votes {
-            let vote: DecryptionTransaction = vote.into();
-            let vote = vote.decrypted_vote;
+    if election_id.len() < 15 {
+        eprintln!("cryptoballot: invalid election-id");
+        std::process::exit(1);
+    }
+    let prefix = &election_id[0..15];
 
-            // For now, assume it's a string
-            tally.add(vote[0].selection.clone());
+    let transactions = match matches.value_of("db") {
+        Some(db) => transactions_from_db(&crate::expand(db), &election_id),
+        None => crate::rest::get_transactions_by_prefix(uri, &prefix).unwrap(),
+    };
+
+    if transactions.len() == 0 {
+        eprintln!("No Transactions present");
+        std::process::exit(1)
+    }
+
+    let first_transaction = &transactions[0];
+    if first_transaction.transaction_type() != TransactionType::Election {
+        eprintln!("Frist transaction must be an election transaction");
+        std::process::exit(1)
+    }
+
+    transactions
+}
+
+// Read an election archive from a file, checking it against its manifest
+fn read_archive_file(path: &str) -> ElectionArchive {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("cryptoballot e2e: cannot open {}: {}", path, e);
+            std::process::exit(1);
         }
+    };
 
-        let winners = tally.winners().into_unranked();
-        println!("  The winner is {}", winners[0]);
+    match read_archive(std::io::BufReader::new(file)) {
+        Ok(archive) => archive,
+        Err(e) => {
+            eprintln!("cryptoballot e2e: invalid archive {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// Read every transaction in an election from an SQLite database, in id order
+fn transactions_from_db(path: &str, election_id: &str) -> Vec<SignedTransaction> {
+    let election_id =
+        match Identifier::new_from_str_id(election_id, TransactionType::Election, None) {
+            Some(election_id) => election_id,
+            None => {
+                eprintln!("cryptoballot e2e: invalid election-id");
+                std::process::exit(1);
+            }
+        };
+
+    let db = match SqliteStore::open(path) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("cryptoballot e2e: cannot open {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let start = Identifier::start(election_id, TransactionType::FIRST, None);
+    let end = Identifier::end(election_id, TransactionType::LAST, None);
+    match db.range(start, end).collect() {
+        Ok(transactions) => transactions,
+        Err(e) => {
+            eprintln!("cryptoballot e2e: cannot read {}: {}", path, e);
+            std::process::exit(1);
+        }
     }
 }
@@ -46,6 +46,8 @@ pub fn command_election_generate(uri: &str, secret_key: &SecretKey, post: bool)
         write_in: true,
         num_winners: 1,
         candidates: vec![],
+        homomorphic: false,
+        max_score: None,
         properties: IndexMap::new(),
     };
 
@@ -61,7 +63,7 @@ pub fn command_election_generate(uri: &str, secret_key: &SecretKey, post: bool)
     };
     election.trustees = vec![trustee];
     election.mix_config = Some(MixConfig {
-        timeout_secs: 0,
+        timeout_secs: 600,
         batch_size: None,
     });
 
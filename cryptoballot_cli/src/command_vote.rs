@@ -30,12 +30,6 @@ pub fn command_vote_generate(
     let election_id = crate::expand(matches.value_of("ELECTION-ID").unwrap());
     let secret_vote = crate::expand(matches.value_of("VOTE").unwrap());
 
-    let selection = Selection {
-        write_in: true,
-        score: 0,
-        selection: secret_vote,
-    };
-
     // Get the encryption-key
     let enc_id = cryptoballot::Identifier::new_from_str_id(
         &election_id,
@@ -51,35 +45,63 @@ pub fn command_vote_generate(
     let encryption_key_tx = crate::rest::get_transaction(uri, enc_id)
         .expect("Unable to get encryption_key transaction");
     let encryption_key_tx: EncryptionKeyTransaction = encryption_key_tx.into();
+    let election_id = encryption_key_tx.election;
+
+    // TODO: Replace with real error
+    let election_tx =
+        crate::rest::get_transaction(uri, election_id).expect("Unable to get election transaction");
+    let election_tx: ElectionTransaction = election_tx.into();
+    let contest = election_tx
+        .contests
+        .iter()
+        .find(|contest| contest.index == 0)
+        .expect("Election has no contest 0");
+
+    // Anything that isn't a candidate is a write-in
+    let selection = Selection {
+        write_in: !contest
+            .candidates
+            .iter()
+            .any(|candidate| candidate.id == secret_vote),
+        score: 0,
+        selection: secret_vote,
+    };
 
-    // Encrypt the secret vote
+    // Encrypt the secret vote, proving that it is well-formed
     // TODO: Real error not expect
-    let encrypted_selections =
-        cryptoballot::encrypt_vote(&encryption_key_tx.encryption_key, vec![selection], &mut rng)
-            .expect("Error encrypting vote");
+    let encrypted_vote = cryptoballot::encrypt_vote_with_proofs(
+        &encryption_key_tx.encryption_key,
+        election_id,
+        &public_key,
+        contest,
+        vec![selection],
+        &mut rng,
+    )
+    .expect("Error encrypting vote");
 
     // Generate an empty vote transaction
-    let election_id = encryption_key_tx.election;
-    let vote = VoteTransaction {
-        id: VoteTransaction::build_id(election_id, &public_key),
-        election: election_id,
-        ballot_id: "BALLOT1".to_string(),
-        encrypted_votes: vec![EncryptedVote {
-            contest_index: 0,
-            selections: encrypted_selections,
-        }],
-        anonymous_key: public_key,
-        authentication: vec![],
-    };
+    let vote = VoteTransaction::with_anonymous_key(
+        election_id,
+        "BALLOT1".to_string(),
+        vec![encrypted_vote],
+        public_key,
+    );
 
     // TODO: Normally we would do blind authentication here, but this is just for testing for now so skip
 
+    // Keep a receipt so the vote can be tracked through to the tally
+    let receipt = vote.receipt();
+
     // Sign and seal the vote transaction
     let vote: SignedTransaction = Signed::sign(&secret_key, vote).unwrap().into();
 
     let tx_json = serde_json::to_string_pretty(&vote).unwrap();
     println!("{}", tx_json);
 
+    // The receipt goes to stderr, so the transaction can still be piped
+    eprintln!("Tracking code: {}", receipt.tracking_code);
+    eprintln!("{}", serde_json::to_string_pretty(&receipt).unwrap());
+
     // Post it or print it
     if post {
         // TODO: post_transaction should return a result with an Err(string) if there's an error
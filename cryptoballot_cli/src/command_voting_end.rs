@@ -1,3 +1,4 @@
+use cryptoballot::unix_time;
 use cryptoballot::Signed;
 use cryptoballot::SignedTransaction;
 use cryptoballot::TransactionType;
@@ -39,7 +40,7 @@ pub fn command_voting_end_generate(
             });
 
     // Create a voting-end transaction
-    let voting_end_tx = VotingEndTransaction::new(election_id, public_key);
+    let voting_end_tx = VotingEndTransaction::new(election_id, public_key, unix_time());
 
     //  Turn it into a signed transaction
     let voting_end_tx = Signed::sign(&secret_key, voting_end_tx).unwrap();
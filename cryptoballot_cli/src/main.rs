@@ -3,6 +3,7 @@ use clap::{App, Arg, SubCommand};
 use cryptoballot::*;
 use ed25519_dalek::SecretKey;
 
+mod command_archive;
 mod command_authn;
 mod command_e2e;
 mod command_election;
@@ -93,9 +94,24 @@ fn main() {
                 .arg(
                     Arg::with_name("ELECTION-ID")
                         .index(1)
-                        .required(true) 
+                        .required_unless("archive")
                         .help("Election ID"),
                 )
+                .arg(
+                    Arg::with_name("db")
+                        .long("db")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Read the election from an SQLite database instead of the server"),
+                )
+                .arg(
+                    Arg::with_name("archive")
+                        .long("archive")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .conflicts_with("db")
+                        .help("Verify an election archive offline, instead of reading from the server"),
+                )
                 .arg(
                     Arg::with_name("print-votes")
                         .long("print-votes")
@@ -112,6 +128,30 @@ fn main() {
                         .help("Print the election results"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("archive")
+                .about("Write an election to an archive that can be verified offline")
+                .setting(AppSettings::ArgRequiredElseHelp)
+                .arg(
+                    Arg::with_name("ELECTION-ID")
+                        .index(1)
+                        .required(true)
+                        .help("Election ID"),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .index(2)
+                        .required(true)
+                        .help("Archive file to write"),
+                )
+                .arg(
+                    Arg::with_name("db")
+                        .long("db")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Read the election from an SQLite database instead of the server"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("trustee")
                 .about("Trustee related commands")
@@ -148,6 +188,14 @@ fn main() {
                                 .help("Length of RSA key, anything less than 2048 is insecure")
                                 .takes_value(true)
                                 .default_value("4096"),
+                        )
+                        .arg(
+                            Arg::with_name("scheme")
+                                .long("scheme")
+                                .help("Blind-signature scheme to use")
+                                .takes_value(true)
+                                .possible_values(&["rsa", "schnorr"])
+                                .default_value("rsa"),
                         ),
                 ),
         )
@@ -271,7 +319,14 @@ fn main() {
         std::process::exit(0);
     }
     if let Some(matches) = matches.subcommand_matches("e2e") {
-        command_e2e::command_e2e(matches, &uri);
+        if let Err(e) = command_e2e::command_e2e(matches, &uri) {
+            eprintln!("cryptoballot e2e: {}", e);
+            std::process::exit(1);
+        }
+        std::process::exit(0);
+    }
+    if let Some(matches) = matches.subcommand_matches("archive") {
+        command_archive::command_archive(matches, &uri);
         std::process::exit(0);
     }
     if let Some(matches) = matches.subcommand_matches("trustee") {
@@ -350,31 +405,29 @@ fn command_get_transaction(matches: &clap::ArgMatches, uri: &str) {
 fn command_tally(matches: &clap::ArgMatches, uri: &str) {
     // Unwraps OK - required args
     let election_id = matches.value_of("election-id").unwrap();
-    let election_id = election_id.parse().unwrap();
-
-    // TODO: remove these unwraps, use try_into();
-    let election = rest::get_transaction(uri, election_id).unwrap();
-    let _election: ElectionTransaction = election.into();
+    let election_id: Identifier = election_id.parse().unwrap();
 
-    //let vote_txs =
-    //    rest::get_multiple_transactions(election.id(), Some(TransactionType::Decryption), uri)
-    //        .unwrap();
-
-    // TODO: Use a real tally / ballot / contest system
-    //let mut tally = DefaultPluralityTally::new(1);
-
-    //for vote in vote_txs {
-    // TODO: use try_into();
-    //    let vote: Signed<DecryptionTransaction> = vote.into();
-
-    //    let selection = std::str::from_utf8(&vote.decrypted_vote)
-    //        .unwrap()
-    //        .to_owned();
-    //    tally.add(selection);
-    //}
+    let tally_id = TallyTransaction::build_id(election_id);
+    let tally = match rest::get_transaction(uri, tally_id) {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!(
+                "cryptoballot tally: unable to fetch tally transaction: {}",
+                e
+            );
+            std::process::exit(1);
+        }
+    };
+    let tally: TallyTransaction = tally.into();
 
-    //let winners = tally.winners().into_unranked();
-    //println!("The winner is {}", winners[0]);
+    for (contest_id, result) in tally.tally.iter() {
+        println!("Contest {}:", contest_id);
+        for (candidate, num_votes) in result.totals.iter() {
+            println!("  {} got {} votes", candidate, num_votes);
+        }
+        let winners = result.winners.clone().into_unranked();
+        println!("  Winners: {}", winners.join(", "));
+    }
 }
 
 // Utility Functions
@@ -3,25 +3,30 @@
 #[macro_use]
 extern crate serde_derive; // Required for Protobuf.
 
-use cryptoballot::{Identifier, SignedTransaction};
-use exonum::runtime::{AnyTx, CallInfo};
+use cryptoballot::{Identifier, SignedTransaction, StoreError, TransactionIter};
+use exonum::runtime::{AnyTx, BlockchainData, CallInfo};
 use exonum::{
     crypto::PublicKey,
     crypto::SecretKey,
     merkledb::{
-        access::{Access, FromAccess},
-        MapIndex,
+        access::{Access, AsReadonly, FromAccess, RawAccess},
+        Entry, MapIndex,
     },
 };
 use exonum_derive::ExecutionFail;
 use exonum_derive::{BinaryValue, FromAccess, ObjectHash};
 use exonum_explorer::api::TransactionHex;
 use exonum_proto::ProtobufConvert;
+use exonum_time::TimeSchema;
 
 pub mod proto;
 
 pub const CRYPTOBALLOT_SERVICE_ID: u32 = 5013;
 
+/// The exonum-time oracle instance that provides the time agreed upon by the validators
+pub const TIME_SERVICE_ID: u32 = 5014;
+pub const TIME_SERVICE_NAME: &str = "exonum-time";
+
 /// Cryptoballot Transaction
 #[derive(Clone, Debug, Serialize, Deserialize, ProtobufConvert, BinaryValue, ObjectHash)]
 #[protobuf_convert(source = "proto::Transaction")]
@@ -73,8 +78,11 @@ impl std::convert::TryInto<SignedTransaction> for Transaction {
 /// Schema of the key-value storage used by the demo cryptocurrency service.
 #[derive(Debug, FromAccess)]
 pub struct TransactionSchema<T: Access> {
-    /// Correspondence of tx ids to the transaction payload
-    pub transactions: MapIndex<T::Base, String, Transaction>,
+    /// Correspondence of binary tx ids (see `Identifier::to_bytes`) to the transaction payload
+    pub transactions: MapIndex<T::Base, Vec<u8>, Transaction>,
+
+    /// The time reported by the time oracle when the latest transaction was executed, in seconds since the unix epoch
+    pub block_time: Entry<T::Base, u64>,
 }
 
 impl<T: Access> TransactionSchema<T> {
@@ -84,34 +92,49 @@ impl<T: Access> TransactionSchema<T> {
     }
 }
 
+/// The time agreed upon by the validators, in seconds since the unix epoch, as reported by the exonum-time oracle
+///
+/// Returns None if the oracle is not running, or has not reported a time yet.
+pub fn consensus_time<T: RawAccess + AsReadonly>(data: &BlockchainData<T>) -> Option<u64> {
+    let time_data = data.for_service(TIME_SERVICE_NAME)?;
+    let time = TimeSchema::new(time_data).time.get()?;
+
+    Some(time.timestamp() as u64)
+}
+
 impl<T: Access> cryptoballot::Store for TransactionSchema<T> {
-    fn get_transaction(&self, id: Identifier) -> Option<SignedTransaction> {
-        let key = id.to_string();
-        let encoded_tx = self.transactions.get(&key);
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        let encoded_tx = self.transactions.get(&id.to_bytes());
 
         match encoded_tx {
-            Some(encoded_tx) => SignedTransaction::from_bytes(&encoded_tx.data).ok(),
-            None => None,
+            Some(encoded_tx) => SignedTransaction::from_bytes(&encoded_tx.data)
+                .map(Some)
+                .map_err(|_| StoreError::Corrupt(id.to_string())),
+            None => Ok(None),
         }
     }
 
-    fn range(&self, start: Identifier, end_inclusive: Identifier) -> Vec<SignedTransaction> {
-        let mut results = Vec::new();
-
-        let start = start.to_string();
-        let end = end_inclusive.to_string();
+    fn range(&self, start: Identifier, end_inclusive: Identifier) -> TransactionIter<'_> {
+        let start = start.to_bytes();
+        let end = end_inclusive.to_bytes();
 
-        for (k, v) in self.transactions.iter_from(&start) {
+        let transactions = self
+            .transactions
+            .iter_from(&start)
             // If we're lexographically larger than end, we've gone one past the end
-            if k > end {
-                break;
-            }
+            .take_while(move |(k, _)| *k <= end)
+            .map(|(k, v)| {
+                SignedTransaction::from_bytes(&v.data)
+                    .map_err(|_| StoreError::Corrupt(hex::encode(k)))
+            });
 
-            if let Ok(decoded) = SignedTransaction::from_bytes(&v.data) {
-                results.push(decoded)
-            }
-        }
-        results
+        Box::new(transactions)
+    }
+
+    // Use the time agreed upon by the validators, so that every validator agrees on voting windows and mix timeouts.
+    // Validators' system clocks differ, so time-dependent transactions are rejected until the time oracle reports a time.
+    fn current_time(&self) -> Result<u64, StoreError> {
+        self.block_time.get().ok_or(StoreError::TimeUnavailable)
     }
 }
 
@@ -137,9 +160,12 @@ pub enum Error {
 use exonum::runtime::ExecutionContext;
 
 pub fn verify_and_store(context: ExecutionContext<'_>, tx: Transaction) -> Result<(), Error> {
+    let block_time = consensus_time(&context.data());
     let mut schema = TransactionSchema::new(context.service_data());
-    if schema.transactions.get(&tx.id).is_some() {
-        return Err(Error::TransactionAlreadyExists);
+
+    // Record the consensus time, so time-dependent transactions are validated against it
+    if let Some(block_time) = block_time {
+        schema.block_time.set(block_time);
     }
 
     println!("Creating tx: {:?}", tx);
@@ -151,6 +177,11 @@ pub fn verify_and_store(context: ExecutionContext<'_>, tx: Transaction) -> Resul
         }
     };
 
+    let key = unpacked_tx.id().to_bytes();
+    if schema.transactions.get(&key).is_some() {
+        return Err(Error::TransactionAlreadyExists);
+    }
+
     if let Some(_pkey) = unpacked_tx.public() {
         // TODO: Check that exonum public-key matches inner public-key if it exists
         //let author = context
@@ -172,6 +203,6 @@ pub fn verify_and_store(context: ExecutionContext<'_>, tx: Transaction) -> Resul
     // TODO: Election Authority public key for election tx
 
     // All checks pass, store the transaction
-    schema.transactions.put(&tx.id.clone(), tx);
+    schema.transactions.put(&key, tx);
     Ok(())
 }
@@ -0,0 +1,44 @@
+//! Reads the block time from the state written by the sawtooth BlockInfo transaction family.
+//!
+//! The BlockInfo protobuf messages aren't part of the sawtooth SDK, so the two fields we need are decoded directly.
+//! Transactions must list the BlockInfo namespace (`00b10c`) in their inputs for the block time to be readable.
+
+use protobuf::wire_format::WireType;
+use protobuf::{CodedInputStream, ProtobufResult};
+use sawtooth_sdk::processor::handler::TransactionContext;
+
+const BLOCK_INFO_NAMESPACE: &str = "00b10c";
+
+// BlockInfoConfig.latest_block and BlockInfo.timestamp
+const LATEST_BLOCK_FIELD: u32 = 1;
+const TIMESTAMP_FIELD: u32 = 5;
+
+/// The timestamp of the latest block, in seconds since the unix epoch
+///
+/// Returns None if the BlockInfo transaction family is not running, or its state isn't readable.
+pub fn block_time(context: &dyn TransactionContext) -> Option<u64> {
+    let config_address = format!("{}01{}", BLOCK_INFO_NAMESPACE, "0".repeat(62));
+    let config = context.get_state_entry(&config_address).ok()??;
+    let latest_block = read_uint64_field(&config, LATEST_BLOCK_FIELD).ok()??;
+
+    let block_address = format!("{}00{:062x}", BLOCK_INFO_NAMESPACE, latest_block);
+    let block_info = context.get_state_entry(&block_address).ok()??;
+
+    read_uint64_field(&block_info, TIMESTAMP_FIELD).ok()?
+}
+
+// Read a single uint64 field out of an encoded protobuf message
+fn read_uint64_field(bytes: &[u8], field_number: u32) -> ProtobufResult<Option<u64>> {
+    let mut input = CodedInputStream::from_bytes(bytes);
+    let mut value = None;
+    while !input.eof()? {
+        let (field, wire_type) = input.read_tag_unpack()?;
+        if field == field_number && wire_type == WireType::WireTypeVarint {
+            value = Some(input.read_uint64()?);
+        } else {
+            input.skip_field(wire_type)?;
+        }
+    }
+
+    Ok(value)
+}
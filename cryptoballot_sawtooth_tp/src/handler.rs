@@ -83,9 +83,12 @@ impl TransactionHandler for CbTransactionHandler {
                 &e
             );
 
-            // TODO: This doesn't account for state network errors (should be InternalError)
-            // TODO: turn get_transaction into a Result and bubble error to here and check
-            ApplyError::InvalidTransaction(err)
+            // State errors aren't the transaction's fault, so the validator should retry rather than reject it
+            match e {
+                TPError::ValidationError(ValidationError::Store(StoreError::Backend(_)))
+                | TPError::ContextError(_) => ApplyError::InternalError(err),
+                _ => ApplyError::InvalidTransaction(err),
+            }
         })?;
 
         // Store the transaction
@@ -107,7 +110,7 @@ fn validate_transaction(transaction: &SignedTransaction, state: &CbState) -> Res
     } else {
         // All transaction except elections are immutable
         // TODO: Allow resubmisson to pass if they are the same??
-        if state.get_transaction(transaction.id()).is_some() {
+        if state.get(transaction.id())?.is_some() {
             return Err(TPError::AlreadyExists(transaction.id().to_string()));
         }
     }
@@ -162,12 +165,24 @@ impl<'a> CbState<'a> {
 }
 
 impl<'a> Store for CbState<'a> {
-    // TODO: Perhaps change this from Option<T> to Result<T>
-    fn get_transaction(&self, id: Identifier) -> Option<SignedTransaction> {
-        self.get(id).ok().flatten()
+    fn get_transaction(&self, id: Identifier) -> Result<Option<SignedTransaction>, StoreError> {
+        self.get(id).map_err(|e| match e {
+            TPError::CannotParseTransaction(id) => StoreError::Corrupt(id),
+            e => StoreError::Backend(e.to_string()),
+        })
+    }
+
+    // Use the block time recorded by the BlockInfo transaction family, so that every validator agrees on
+    // voting windows and mix timeouts. Validators' system clocks differ, so time-dependent transactions are
+    // rejected if the block time isn't available.
+    fn current_time(&self) -> Result<u64, StoreError> {
+        crate::block_info::block_time(&*self.context).ok_or(StoreError::TimeUnavailable)
     }
 
-    fn range(&self, _start: Identifier, _end_exclusive: Identifier) -> Vec<SignedTransaction> {
-        todo!()
+    // TODO: Sawtooth state can only be listed by address prefix from a client, not from within a transaction processor
+    fn range(&self, _start: Identifier, _end_inclusive: Identifier) -> TransactionIter<'_> {
+        Box::new(std::iter::once(Err(StoreError::Backend(
+            "range queries are not supported by sawtooth state".to_string(),
+        ))))
     }
 }
@@ -1,5 +1,6 @@
 extern crate sawtooth_sdk;
 
+mod block_info;
 mod error;
 mod handler;
 
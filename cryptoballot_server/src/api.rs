@@ -1,6 +1,6 @@
 use exonum_rust_runtime::api::{self, ServiceApiBuilder, ServiceApiState};
 
-use cryptoballot::SignedTransaction;
+use cryptoballot::{Identifier, SignedTransaction};
 use cryptoballot_exonum::TransactionSchema;
 
 /// Public service API description.
@@ -24,10 +24,15 @@ impl CryptoballotApi {
     pub async fn get_tx(state: ServiceApiState, query: TxQuery) -> api::Result<SignedTransaction> {
         use std::convert::TryInto;
 
+        let id: Identifier = query
+            .id
+            .parse()
+            .map_err(|_| api::Error::bad_request().title("Invalid transaction id"))?;
+
         let schema = TransactionSchema::new(state.service_data());
         let exonum_tx = schema
             .transactions
-            .get(&query.id)
+            .get(&id.to_bytes())
             .ok_or_else(|| api::Error::not_found().title("Transaction not found"))?;
 
         Ok(exonum_tx
@@ -47,9 +52,11 @@ impl CryptoballotApi {
         let mut txs = Vec::new();
 
         if let Some(prefix) = query.prefix {
-            let start = format!("{:0<64}", prefix);
+            let prefix = prefix.to_lowercase();
+            let start = hex::decode(format!("{:0<64}", prefix))
+                .map_err(|_| api::Error::bad_request().title("Invalid transaction id prefix"))?;
             for (k, exonum_tx) in schema.transactions.iter_from(&start) {
-                if !k.starts_with(&prefix) {
+                if !hex::encode(&k).starts_with(&prefix) {
                     break;
                 }
                 txs.push(exonum_tx.try_into().map_err(|_| {
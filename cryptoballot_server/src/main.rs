@@ -8,8 +8,10 @@ pub mod api;
 pub mod service;
 pub mod tasks;
 
+use cryptoballot_exonum::{TIME_SERVICE_ID, TIME_SERVICE_NAME};
 use ed25519_dalek::{PublicKey, SecretKey};
 use exonum_cli::{NodeBuilder, Spec};
+use exonum_time::TimeServiceFactory;
 use std::sync::{Arc, RwLock};
 
 lazy_static! {
@@ -32,8 +34,13 @@ pub fn public_key() -> PublicKey {
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     exonum::helpers::init_logger().unwrap();
-    let builder =
-        NodeBuilder::new().with(Spec::new(service::CryptoballotService).with_default_instance());
+    let builder = NodeBuilder::new()
+        .with(Spec::new(TimeServiceFactory::default()).with_instance(
+            TIME_SERVICE_ID,
+            TIME_SERVICE_NAME,
+            (),
+        ))
+        .with(Spec::new(service::CryptoballotService).with_default_instance());
 
     if let Some(node) = builder.execute_command()? {
         // Store the secret-key so we can access it from other contexts
@@ -70,6 +70,39 @@ impl Service for CryptoballotService {
             }
         }
 
+        // Check if any elections have reached their scheduled voting_end
+        // Voting is only ended on schedule once the validators agree on the time
+        if let Some(now) = cryptoballot_exonum::consensus_time(&ctx.data()) {
+            let schema = TransactionSchema::new(ctx.service_data());
+            match crate::tasks::process_pending_voting_ends(&schema, now) {
+                Ok(voting_end_txs) => {
+                    let broadcaster = ctx.generic_broadcaster().blocking();
+                    for voting_end_tx in voting_end_txs {
+                        println!("Broadcasting scheduled voting end {}", voting_end_tx.id());
+                        let exonum_tx: Transaction = voting_end_tx.into();
+                        broadcaster.submit_tx((), exonum_tx).ok();
+                    }
+                }
+                Err(err) => eprintln!("Error processing scheduled voting ends: {}", err),
+            }
+        }
+
+        // Check if any mix slots have opened up to us because another trustee timed out
+        {
+            let schema = TransactionSchema::new(ctx.service_data());
+            match crate::tasks::process_pending_mixes(&schema) {
+                Ok(mix_txs) => {
+                    let broadcaster = ctx.generic_broadcaster().blocking();
+                    for mix_tx in mix_txs {
+                        println!("Broadcasting timed-out mix {}", mix_tx.id());
+                        let exonum_tx: Transaction = mix_tx.into();
+                        broadcaster.submit_tx((), exonum_tx).ok();
+                    }
+                }
+                Err(err) => eprintln!("Error processing pending mixes: {}", err),
+            }
+        }
+
         // TODO: Check instance status and do something if we're frozen or stopped etc.
         //       Also do nothing if we're just an auditor and not a full peer.
 
@@ -99,7 +132,7 @@ impl Service for CryptoballotService {
                                 continue;
                             }
                         };
-                        if let Some(tx) = schema.get_transaction(id) {
+                        if let Ok(Some(tx)) = schema.get_transaction(id) {
                             let tx_json = serde_json::to_string_pretty(&tx).unwrap();
                             println!("{}", tx_json);
 
@@ -109,7 +142,7 @@ impl Service for CryptoballotService {
                             // TODO: Vote decryptions in batches
                             // let mut stored_dependent_txs = DEPENDENT_TXS.lock().unwrap();
                             for dependent_tx in dependent_txs {
-                                if schema.get_transaction(dependent_tx.id()).is_none() {
+                                if let Ok(None) = schema.get_transaction(dependent_tx.id()) {
                                     println!(
                                         "Broadcasting dependent {} {}",
                                         dependent_tx.transaction_type(),
@@ -1,10 +1,9 @@
-use cryptid::elgamal::Ciphertext;
 use cryptid::threshold::KeygenCommitment;
 use cryptoballot::*;
 use ed25519_dalek::PublicKey;
 use rand::rngs::StdRng;
 use rand::SeedableRng;
-use std::collections::HashMap;
+use std::sync::Mutex;
 use x25519_dalek as x25519;
 
 pub fn generate_transactions<S: Store>(
@@ -22,6 +21,10 @@ pub fn generate_transactions<S: Store>(
             process_keygen_public_key(store, incoming_tx.clone().into())
         }
 
+        TransactionType::KeyGenComplaint => {
+            process_keygen_complaint(store, incoming_tx.clone().into())
+        }
+
         TransactionType::VotingEnd => process_voting_end(store, incoming_tx.clone().into()),
 
         TransactionType::Mix => process_mix(store, incoming_tx.clone().into()),
@@ -30,6 +33,8 @@ pub fn generate_transactions<S: Store>(
             process_partial_decryption(store, incoming_tx.clone().into())
         }
 
+        TransactionType::Decryption => process_decryption(store, incoming_tx.clone().into()),
+
         _ => Ok(vec![]),
     }
 }
@@ -74,9 +79,8 @@ fn process_keygen_commitment<S: Store>(
         // Check that we have enough commitment transactions already
         let commit_txs: Vec<KeyGenCommitmentTransaction> = store
             .get_multiple(election_tx.id, TransactionType::KeyGenCommitment)
-            .into_iter()
-            .map(|tx| tx.into())
-            .collect();
+            .map(|tx| tx.map(|tx| tx.into()))
+            .collect::<Result<_, _>>()?;
 
         if commit_txs.len() == election_tx.trustees.len() {
             let commitments: Vec<(u8, KeygenCommitment)> = commit_txs
@@ -118,73 +122,146 @@ fn process_keygen_share<S: Store>(
     share_tx: KeyGenShareTransaction,
 ) -> Result<Vec<SignedTransaction>, Error> {
     let public_key = crate::public_key();
-    let secret_key = crate::secret_key();
 
     // Get the election_tx
     let election_tx = store.get_election(share_tx.election)?.tx;
 
     if let Some(trustee) = trustee_from_election(&election_tx, &public_key) {
         // Check that we have enough keygen_tx transactions already
-        let share_txs: Vec<KeyGenShareTransaction> = store
+        let num_share_txs = store
             .get_multiple(election_tx.id, TransactionType::KeyGenShare)
-            .into_iter()
-            .map(|tx| tx.into())
-            .collect();
+            .count();
 
-        if share_txs.len() != election_tx.trustees.len() {
+        if num_share_txs != election_tx.trustees.len() {
             return Ok(vec![]);
         }
 
-        // Get all commitments
-        let commitments: Vec<(u8, KeygenCommitment)> = store
-            .get_multiple(election_tx.id, TransactionType::KeyGenCommitment)
-            .into_iter()
-            .map(|tx| tx.into())
-            .map(|tx: KeyGenCommitmentTransaction| (tx.trustee_index, tx.commitment))
-            .collect();
+        return generate_keygen_public_key(store, &election_tx, &trustee);
+    }
 
-        // Get all x25519 public keys
-        let x25519_public_keys: Vec<(u8, x25519::PublicKey)> = store
-            .get_multiple(election_tx.id, TransactionType::KeyGenCommitment)
-            .into_iter()
-            .map(|tx| tx.into())
-            .map(|tx: KeyGenCommitmentTransaction| (tx.trustee_index, tx.x25519_public_key))
-            .collect();
-
-        let shares: Vec<(u8, EncryptedShare)> = share_txs
-            .into_iter()
-            .map(|tx| {
-                (
-                    tx.trustee_index,
-                    tx.shares.get(&trustee.index).unwrap().clone(),
-                )
-            })
-            .collect();
-
-        let (public_key, public_key_proof) = trustee.generate_public_key(
-            &secret_key,
-            &x25519_public_keys,
-            &commitments,
-            &shares,
-            election_tx.id,
-        )?;
+    Ok(vec![])
+}
 
-        let pk_tx = KeyGenPublicKeyTransaction::new(
-            election_tx.id,
-            trustee.index,
-            trustee.public_key,
-            public_key,
-            public_key_proof,
-        );
-        let secret_key = crate::secret_key();
-        let pk_tx = Signed::sign(&secret_key, pk_tx)?;
-        return Ok(vec![pk_tx.into()]);
+// On keygen_complaint transaction, post a new public key without the disqualified trustee (if we are a trustee)
+fn process_keygen_complaint<S: Store>(
+    store: &S,
+    complaint_tx: KeyGenComplaintTransaction,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let public_key = crate::public_key();
+
+    // Get the election_tx
+    let election_tx = store.get_election(complaint_tx.election)?.tx;
+
+    if let Some(trustee) = trustee_from_election(&election_tx, &public_key) {
+        // Complaints can come in before all the shares have
+        let num_share_txs = store
+            .get_multiple(election_tx.id, TransactionType::KeyGenShare)
+            .count();
+        if num_share_txs != election_tx.trustees.len() {
+            return Ok(vec![]);
+        }
+
+        return generate_keygen_public_key(store, &election_tx, &trustee);
     }
 
     Ok(vec![])
 }
 
-// On keygen_public_key transaction, check if we have ALL trustee public_keys, and if so, generate a encryption_key (if we are election authority)
+// Generate our keygen_public_key transaction from the trustees not disqualified by a complaint, or complain about the
+// trustees that sent us an invalid share instead
+fn generate_keygen_public_key<S: Store>(
+    store: &S,
+    election_tx: &ElectionTransaction,
+    trustee: &Trustee,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let public_key = crate::public_key();
+    let secret_key = crate::secret_key();
+
+    // Complaints are too late once the election key has been posted
+    if store
+        .get_transaction(EncryptionKeyTransaction::build_id(election_tx.id))?
+        .is_some()
+    {
+        return Ok(vec![]);
+    }
+
+    let mut disqualified: Vec<u8> = disqualified_trustees(store, election_tx.id)?
+        .into_iter()
+        .collect();
+    disqualified.sort_unstable();
+    if disqualified.contains(&trustee.index) {
+        return Ok(vec![]);
+    }
+
+    // Several complaints can disqualify the same trustee, but we only need one public key without them
+    let pk_tx_id = KeyGenPublicKeyTransaction::build_id(
+        election_tx.id,
+        trustee.index,
+        disqualified.len() as u8,
+    );
+    if store.get_transaction(pk_tx_id)?.is_some() {
+        return Ok(vec![]);
+    }
+
+    let (x25519_public_keys, commitments, shares) =
+        trustee_key_material(store, election_tx, trustee)?;
+
+    let invalid_shares = trustee.invalid_shares(
+        &secret_key,
+        &x25519_public_keys,
+        &commitments,
+        &shares,
+        election_tx.id,
+    );
+    if !invalid_shares.is_empty() {
+        let mut rng: StdRng = SeedableRng::from_entropy();
+        let mut complaint_txs = Vec::with_capacity(invalid_shares.len());
+        for accused in invalid_shares {
+            let accused_public_key = x25519_public_keys
+                .iter()
+                .find(|(index, _)| *index == accused)
+                .map(|(_, public_key)| public_key)
+                .ok_or(ValidationError::TrusteeDoesNotExist(accused))?;
+            let shared_secret = trustee.shared_secret_proof(
+                &mut rng,
+                &secret_key,
+                election_tx.id,
+                accused_public_key,
+            )?;
+            let complaint_tx = KeyGenComplaintTransaction::new(
+                election_tx.id,
+                trustee.index,
+                public_key,
+                accused,
+                shared_secret,
+            );
+            let complaint_tx = Signed::sign(&secret_key, complaint_tx)?;
+            complaint_txs.push(complaint_tx.into());
+        }
+        return Ok(complaint_txs);
+    }
+
+    let (public_key, public_key_proof) = trustee.generate_public_key(
+        &secret_key,
+        &x25519_public_keys,
+        &commitments,
+        &shares,
+        election_tx.id,
+    )?;
+
+    let pk_tx = KeyGenPublicKeyTransaction::new(
+        election_tx.id,
+        trustee.index,
+        trustee.public_key,
+        public_key,
+        public_key_proof,
+        disqualified,
+    );
+    let pk_tx = Signed::sign(&secret_key, pk_tx)?;
+    Ok(vec![pk_tx.into()])
+}
+
+// On keygen_public_key transaction, check if we have ALL qualified trustee public_keys, and if so, generate a encryption_key (if we are election authority)
 fn process_keygen_public_key<S: Store>(
     store: &S,
     pk_tx: KeyGenPublicKeyTransaction,
@@ -196,19 +273,16 @@ fn process_keygen_public_key<S: Store>(
     let election_tx = store.get_election(pk_tx.election)?.tx;
 
     if election_tx.authority_public == public_key {
-        // Get all public key transactions
-        let pk_txs: Vec<KeyGenPublicKeyTransaction> = store
-            .get_multiple(election_tx.id, TransactionType::KeyGenPublicKey)
-            .into_iter()
-            .map(|tx| tx.into())
-            .collect();
-
-        if election_tx.trustees.len() == pk_txs.len() {
-            // Generate an encryption_key transaction
+        // Get the public key transactions made without every disqualified trustee
+        let qualified = qualified_trustees(store, &election_tx)?;
+        let pk_txs = qualified_public_keys(store, election_tx.id)?;
+
+        if !pk_txs.is_empty() && qualified.len() == pk_txs.len() {
+            // Generate an encryption_key transaction, from a public key that is not stale
             let encryption_key_tx = EncryptionKeyTransaction::new(
                 election_tx.id,
                 election_tx.authority_public.clone(),
-                pk_tx.public_key,
+                pk_txs[0].public_key,
             );
             let encryption_key_tx = Signed::sign(&secret_key, encryption_key_tx)?;
             return Ok(vec![encryption_key_tx.into()]);
@@ -218,96 +292,134 @@ fn process_keygen_public_key<S: Store>(
     Ok(vec![])
 }
 
+/// Post a VotingEnd transaction for any election we are a trustee of, whose scheduled `voting_end` has passed.
+///
+/// Scheduled voting ends are read from the elections in the store, so none are missed if the server restarts.
+/// This should be called on every block, with the time agreed upon by consensus.
+pub fn process_pending_voting_ends<S: Store>(
+    store: &S,
+    now: u64,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let public_key = crate::public_key();
+    let secret_key = crate::secret_key();
+    let mut voting_end_txs = Vec::new();
+    for election_tx in stored_elections(store)? {
+        match election_tx.voting_end {
+            Some(voting_end) if now >= voting_end => {}
+            _ => continue,
+        }
+        if trustee_from_election(&election_tx, &public_key).is_none() {
+            continue;
+        }
+
+        // The election authority, or another trustee, may have already ended voting
+        let voting_end_id = Identifier::new(election_tx.id, TransactionType::VotingEnd, None);
+        if store.get_transaction(voting_end_id)?.is_some() {
+            continue;
+        }
+
+        if let Some(voting_end_tx) = VotingEndTransaction::scheduled(&election_tx, public_key) {
+            let voting_end_tx = Signed::sign(&secret_key, voting_end_tx)?;
+            voting_end_txs.push(voting_end_tx.into());
+        }
+    }
+
+    Ok(voting_end_txs)
+}
+
+// Every election in the store. Rather than reading every transaction, the scan reads the first transaction of each
+// election and then skips straight to the next election-id.
+fn stored_elections<S: Store>(store: &S) -> Result<Vec<ElectionTransaction>, Error> {
+    let end = Identifier {
+        election_id: [255; 15],
+        transaction_type: TransactionType::KeyGenComplaint,
+        unique_info: [255; 16],
+    };
+
+    let mut elections = Vec::new();
+    let mut next = Some([0; 15]);
+    while let Some(election_id) = next {
+        let start = Identifier {
+            election_id,
+            transaction_type: TransactionType::Election,
+            unique_info: [0; 16],
+        };
+        let first = match store.range(start, end).next() {
+            Some(tx) => tx?,
+            None => break,
+        };
+
+        next = next_election_id(first.id().election_id);
+        if let SignedTransaction::Election(election_tx) = first {
+            elections.push(election_tx.tx);
+        }
+    }
+
+    Ok(elections)
+}
+
+// The election-id that directly follows the given one, or None if it is the last possible id
+fn next_election_id(mut election_id: [u8; 15]) -> Option<[u8; 15]> {
+    for byte in election_id.iter_mut().rev() {
+        match byte.checked_add(1) {
+            Some(incremented) => {
+                *byte = incremented;
+                return Some(election_id);
+            }
+            None => *byte = 0,
+        }
+    }
+
+    None
+}
+
 // On voting_end transaction, produce either a mix or start decrypting votes (if there is no mix config)
 fn process_voting_end<S: Store>(
     store: &S,
     voting_end_tx: VotingEndTransaction,
 ) -> Result<Vec<SignedTransaction>, Error> {
     let public_key = crate::public_key();
-    let secret_key = crate::secret_key();
 
     // Get the election_tx
     let election_tx = store.get_election(voting_end_tx.election)?.tx;
 
     if let Some(trustee) = trustee_from_election(&election_tx, &public_key) {
-        // If there's a mix config, produce a mix transaction
-        if let Some(_mix_config) = &election_tx.mix_config {
-            if trustee.index == 1 {
-                // create the mix if we're the first trustee
-                // TODO: Handle timeout of the first trustee and we're the second (and so on)
-                // TODO: Also handle the situation where WE previously timed out, but we're back online again
-                //       In this situation, we go to the "back of the line" to wait our turn again
-
-                // Get the EncryptionKey Transaction
-                let encryption_key_tx = EncryptionKeyTransaction::build_id(election_tx.id);
-                let encryption_key_tx: EncryptionKeyTransaction =
-                    store.get_transaction(encryption_key_tx).unwrap().into();
-
-                // Get all vote transactions
-                let vote_txs: Vec<VoteTransaction> = store
-                    .get_multiple(election_tx.id, TransactionType::Vote)
-                    .into_iter()
-                    .map(|tx| tx.into())
-                    .collect();
-
-                // Produce contest -> votes map
-                let contest_votes: HashMap<u32, Vec<(Identifier, Vec<Ciphertext>)>> = {
-                    let mut contest_votes = HashMap::with_capacity(election_tx.contests.len());
-                    for vote_tx in vote_txs {
-                        for encrypted_vote in vote_tx.encrypted_votes {
-                            contest_votes
-                                .entry(encrypted_vote.contest_index)
-                                .or_insert(vec![])
-                                .push((vote_tx.id, encrypted_vote.selections));
-                        }
-                    }
-                    contest_votes
-                };
+        // Homomorphic contests are never mixed or decrypted vote-by-vote, only their totals are decrypted
+        let mut txs = produce_homomorphic_partials(store, &election_tx, &trustee, &voting_end_tx)?;
 
-                let mut mix_txs = Vec::with_capacity(contest_votes.len());
-                let mut rng = rand::thread_rng();
-                for (contest, votes) in contest_votes {
-                    let vote_ids = votes.iter().map(|(id, _)| *id).collect();
-                    let ciphertexts = votes
-                        .into_iter()
-                        .map(|(_, ciphertext)| ciphertext)
-                        .collect();
-
-                    // TODO: This could be expensive, so don't do it on the consensus thread
-                    let (mixed, proof) = mix(
-                        &mut rng,
-                        ciphertexts,
-                        &encryption_key_tx.encryption_key,
-                        trustee.index,
-                        0,
-                        contest,
-                        0,
-                    )?;
-
-                    let mix_tx = MixTransaction::new(
-                        election_tx.id,
-                        None,
-                        &trustee,
-                        0,
-                        contest,
-                        0,
-                        vote_ids,
-                        mixed,
-                        proof,
-                    );
-
-                    let mix_tx = Signed::sign(&secret_key, mix_tx)?;
-                    mix_txs.push(mix_tx.into());
+        // If there's a mix config, produce a mix transaction for every batch in every other contest
+        if let Some(mix_config) = &election_tx.mix_config {
+            for contest in &election_tx.contests {
+                if contest.homomorphic {
+                    continue;
                 }
 
-                return Ok(mix_txs);
+                let batches = mix_config.batch_votes(store, election_tx.id, contest.index)?;
+
+                for batch in 0..batches.len() {
+                    let pending = PendingMix {
+                        election_id: election_tx.id,
+                        contest_index: contest.index,
+                        batch: batch as u32,
+                        mix_index: 0,
+                        prev_mix_id: None,
+                        opens_at: voting_end_tx.timestamp,
+                    };
+                    txs.extend(schedule_mix(store, &election_tx, &trustee, pending, &[])?);
+                }
             }
-        } else {
+        } else if let Some(contest) = election_tx.contests.first() {
             // If there's no mix config, produce partial decryptions for every vote
-            for contest in &election_tx.contests {
-                return produce_partials(store, &election_tx, &trustee, contest.index, None);
-            }
+            txs.extend(produce_partials(
+                store,
+                &election_tx,
+                &trustee,
+                contest.index,
+                None,
+            )?);
         }
+
+        return Ok(txs);
     }
 
     Ok(vec![])
@@ -319,7 +431,6 @@ fn process_mix<S: Store>(
     mix_tx: MixTransaction,
 ) -> Result<Vec<SignedTransaction>, Error> {
     let public_key = crate::public_key();
-    let secret_key = crate::secret_key();
 
     // Get the election_tx
     let election_tx = store.get_election(mix_tx.election_id)?.tx;
@@ -338,52 +449,174 @@ fn process_mix<S: Store>(
                 );
             }
 
-            // TODO: Handle timeout of an intermediary trustee, and go before it would normally be our turn
-            // TODO: Also handle the situation where WE previously timed out, but we're back online again
-            //       In this situation, we go to the "back of the line" to wait our turn again
-
-            if trustee.index == mix_tx.mix_index + 2 {
-                // Get the EncryptionKey Transaction
-                let encryption_key_tx = EncryptionKeyTransaction::build_id(election_tx.id);
-                let encryption_key_tx: EncryptionKeyTransaction =
-                    store.get_transaction(encryption_key_tx).unwrap().into();
-
-                let vote_ids = mix_tx.vote_ids;
-                let ciphertexts = mix_tx.mixed_ciphertexts;
-
-                // TODO: This could be expensive, so don't do it on the consensus thread
-                let mut rng = rand::thread_rng();
-                let (mixed, proof) = mix(
-                    &mut rng,
-                    ciphertexts,
-                    &encryption_key_tx.encryption_key,
-                    trustee.index,
-                    mix_tx.mix_index + 1,
-                    mix_tx.contest_index,
-                    0,
-                )?;
+            let mut already_mixed = mix_tx.previous_mixers(store)?;
+            already_mixed.push(mix_tx.trustee_index);
 
-                let new_mix_tx = MixTransaction::new(
-                    election_tx.id,
-                    None,
-                    &trustee,
-                    mix_tx.mix_index + 1,
-                    mix_tx.contest_index,
-                    0,
-                    vote_ids,
-                    mixed,
-                    proof,
-                );
-
-                let new_mix_tx = Signed::sign(&secret_key, new_mix_tx)?;
-                return Ok(vec![new_mix_tx.into()]);
-            }
+            let pending = PendingMix {
+                election_id: election_tx.id,
+                contest_index: mix_tx.contest_index,
+                batch: mix_tx.batch,
+                mix_index: mix_tx.mix_index + 1,
+                prev_mix_id: Some(mix_tx.id),
+                opens_at: mix_tx.timestamp,
+            };
+            return schedule_mix(store, &election_tx, &trustee, pending, &already_mixed);
         }
     }
 
     Ok(vec![])
 }
 
+/// A mix slot that this node may fill once the trustees ahead of it have timed out
+#[derive(Clone, Debug)]
+struct PendingMix {
+    election_id: Identifier,
+    contest_index: u32,
+    batch: u32,
+    mix_index: u8,
+    prev_mix_id: Option<Identifier>,
+    opens_at: u64,
+}
+
+lazy_static! {
+    static ref PENDING_MIXES: Mutex<Vec<PendingMix>> = Mutex::new(Vec::new());
+}
+
+// Work out where we are in line for a mix slot. If it's our turn, produce the mix now,
+// otherwise wait for the trustees ahead of us to time out. `pending.opens_at` is the reference
+// time of the slot (the timestamp of the previous mix or voting_end), and is updated to our turn.
+fn schedule_mix<S: Store>(
+    store: &S,
+    election_tx: &ElectionTransaction,
+    trustee: &Trustee,
+    mut pending: PendingMix,
+    already_mixed: &[u8],
+) -> Result<Vec<SignedTransaction>, Error> {
+    let mix_config = match &election_tx.mix_config {
+        Some(mix_config) => mix_config,
+        None => return Ok(vec![]),
+    };
+
+    let slot_order = mix_config.slot_order(&election_tx.trustees, pending.mix_index, already_mixed);
+    let position = match slot_order.iter().position(|index| *index == trustee.index) {
+        Some(position) => position,
+        // We've already mixed earlier in this chain
+        None => return Ok(vec![]),
+    };
+    pending.opens_at = mix_config.slot_opens_at(pending.opens_at, position);
+
+    if store.current_time()? >= pending.opens_at {
+        return produce_mix(store, election_tx, trustee, &pending);
+    }
+
+    PENDING_MIXES.lock().unwrap().push(pending);
+    Ok(vec![])
+}
+
+/// Produce any mixes whose slot has opened up to us because the trustees ahead of us timed out.
+///
+/// This should be called on every block.
+pub fn process_pending_mixes<S: Store>(store: &S) -> Result<Vec<SignedTransaction>, Error> {
+    let now = store.current_time()?;
+    let ready: Vec<PendingMix> = {
+        let mut pending_mixes = PENDING_MIXES.lock().unwrap();
+        let (ready, waiting) = pending_mixes
+            .drain(..)
+            .partition(|pending| now >= pending.opens_at);
+        *pending_mixes = waiting;
+        ready
+    };
+
+    let public_key = crate::public_key();
+    let mut mix_txs = Vec::new();
+    for pending in ready {
+        let election_tx = store.get_election(pending.election_id)?.tx;
+        if let Some(trustee) = trustee_from_election(&election_tx, &public_key) {
+            mix_txs.extend(produce_mix(store, &election_tx, &trustee, &pending)?);
+        }
+    }
+
+    Ok(mix_txs)
+}
+
+// Produce a mix for the slot, unless another trustee has already filled it (for example because we were offline
+// and timed out, in which case we wait for our turn in a later slot instead)
+fn produce_mix<S: Store>(
+    store: &S,
+    election_tx: &ElectionTransaction,
+    trustee: &Trustee,
+    pending: &PendingMix,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let secret_key = crate::secret_key();
+
+    if MixTransaction::get_mix_in_slot(
+        store,
+        pending.election_id,
+        pending.contest_index,
+        pending.batch,
+        pending.mix_index,
+    )?
+    .is_some()
+    {
+        return Ok(vec![]);
+    }
+
+    let (vote_ids, ciphertexts) = match pending.prev_mix_id {
+        Some(prev_mix_id) => {
+            let prev_mix = store.get_mix(prev_mix_id)?.tx;
+            (prev_mix.vote_ids, prev_mix.mixed_ciphertexts)
+        }
+        None => {
+            let mix_config = match &election_tx.mix_config {
+                Some(mix_config) => mix_config,
+                None => return Ok(vec![]),
+            };
+            let mut batches =
+                mix_config.batch_votes(store, election_tx.id, pending.contest_index)?;
+            if pending.batch as usize >= batches.len() {
+                return Ok(vec![]);
+            }
+            batches
+                .swap_remove(pending.batch as usize)
+                .into_iter()
+                .unzip()
+        }
+    };
+
+    // Get the EncryptionKey Transaction
+    let encryption_key_tx = EncryptionKeyTransaction::build_id(election_tx.id);
+    let encryption_key_tx: EncryptionKeyTransaction =
+        store.get_transaction(encryption_key_tx)?.unwrap().into();
+
+    // TODO: This could be expensive, so don't do it on the consensus thread
+    let mut rng = rand::thread_rng();
+    let (mixed, proof) = mix(
+        &mut rng,
+        ciphertexts,
+        &encryption_key_tx.encryption_key,
+        trustee.index,
+        pending.mix_index,
+        pending.contest_index,
+        pending.batch,
+    )?;
+
+    let mix_tx = MixTransaction::new(
+        election_tx.id,
+        pending.prev_mix_id,
+        trustee,
+        pending.mix_index,
+        store.current_time()?,
+        pending.contest_index,
+        pending.batch,
+        vote_ids,
+        mixed,
+        proof,
+    );
+
+    let mix_tx = Signed::sign(&secret_key, mix_tx)?;
+    Ok(vec![mix_tx.into()])
+}
+
 // On PartialDecrytion transaction, check if we have enough partials for a full decryption transaction
 // TODO: This needs to be batched, likely goes in a different function, also snouldn't happen on the consensus thread
 fn process_partial_decryption<S: Store>(
@@ -411,49 +644,65 @@ fn process_partial_decryption<S: Store>(
         // TODO: Need some way of partitioning the work between trustee nodes,
         //       while at the same time allowing them to pick up eachother's slack
         //       Alternatively, just do it all with no coordination and let consensus sort it out
-        let partial_txs = store.range(start, end);
+        let partial_txs: Vec<PartialDecryptionTransaction> = store
+            .range(start, end)
+            .map(|tx| tx.map(|tx| tx.into()))
+            .collect::<Result<_, _>>()?;
 
         if partial_txs.len() >= election_tx.trustees_threshold as usize {
-            let partial_txs: Vec<PartialDecryptionTransaction> =
-                partial_txs.into_iter().map(|tx| tx.into()).collect();
-
-            // Get upstream encrypted selections
-            let ciphertexts = match partial_tx.upstream_id.transaction_type {
-                TransactionType::Vote => {
-                    let vote = store.get_vote(partial_tx.upstream_id)?.tx;
-                    let mut ciphertexts = None;
-                    for encrypted_vote in vote.encrypted_votes {
-                        if encrypted_vote.contest_index == partial_tx.contest_index {
-                            ciphertexts = Some(encrypted_vote.selections);
-                            break;
+            // Get public key transactions
+            let pubkeys = qualified_public_keys(store, election_tx.id)?;
+
+            // Fully decrypt the vote, or the totals of a homomorphic contest
+            let decrypted = if partial_tx.upstream_id.transaction_type == TransactionType::VotingEnd
+            {
+                let contest = election_tx
+                    .get_contest(partial_tx.contest_index)
+                    .ok_or(Error::CannotFindContet(partial_tx.contest_index))?;
+                let (totals, num_votes) = homomorphic_aggregate(store, election_tx.id, contest)?;
+
+                decrypt_totals(
+                    contest,
+                    &totals,
+                    num_votes,
+                    election_tx.trustees_threshold,
+                    &election_tx.get_full_trustees(),
+                    &pubkeys,
+                    &partial_txs,
+                )?
+            } else {
+                // Get upstream encrypted selections
+                let ciphertexts = match partial_tx.upstream_id.transaction_type {
+                    TransactionType::Vote => {
+                        let vote = store.get_vote(partial_tx.upstream_id)?.tx;
+                        let mut ciphertexts = None;
+                        for encrypted_vote in vote.encrypted_votes {
+                            if encrypted_vote.contest_index == partial_tx.contest_index {
+                                ciphertexts = Some(encrypted_vote.selections);
+                                break;
+                            }
+                        }
+                        match ciphertexts {
+                            Some(ct) => ct,
+                            None => return Err(Error::CannotFindContet(partial_tx.contest_index)),
                         }
                     }
-                    match ciphertexts {
-                        Some(ct) => ct,
-                        None => return Err(Error::CannotFindContet(partial_tx.contest_index)),
+                    TransactionType::Mix => {
+                        let mut mix = store.get_mix(partial_tx.upstream_id)?.tx;
+                        mix.mixed_ciphertexts
+                            .swap_remove(partial_tx.upstream_index as usize)
                     }
-                }
-                TransactionType::Mix => {
-                    let mut mix = store.get_mix(partial_tx.upstream_id)?.tx;
-                    mix.mixed_ciphertexts
-                        .swap_remove(partial_tx.upstream_index as usize)
-                }
-                _ => return Err(Error::UnexpectedTransactionType),
-            };
+                    _ => return Err(Error::UnexpectedTransactionType),
+                };
 
-            // Get public key transactions
-            let pubkeys = store.get_multiple(election_tx.id, TransactionType::KeyGenPublicKey);
-            let pubkeys: Vec<KeyGenPublicKeyTransaction> =
-                pubkeys.into_iter().map(|tx| tx.into()).collect();
-
-            // Fully decrypt the vote
-            let decrypted = decrypt_vote(
-                &ciphertexts,
-                election_tx.trustees_threshold,
-                &election_tx.get_full_trustees(),
-                &pubkeys,
-                &partial_txs,
-            )?;
+                decrypt_vote(
+                    &ciphertexts,
+                    election_tx.trustees_threshold,
+                    &election_tx.get_full_trustees(),
+                    &pubkeys,
+                    &partial_txs,
+                )?
+            };
 
             let trustee_indexs = partial_txs.iter().map(|tx| tx.trustee_index).collect();
 
@@ -475,6 +724,40 @@ fn process_partial_decryption<S: Store>(
     Ok(vec![])
 }
 
+// On Decryption transaction, check if all votes are decrypted, and if so, produce a tally (if we are election authority)
+fn process_decryption<S: Store>(
+    store: &S,
+    decryption_tx: DecryptionTransaction,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let public_key = crate::public_key();
+    let secret_key = crate::secret_key();
+
+    // Get the election_tx
+    let election_tx = store.get_election(decryption_tx.election_id)?.tx;
+
+    if election_tx.authority_public == public_key {
+        // Only produce a single tally
+        if store
+            .get_tally(TallyTransaction::build_id(election_tx.id))
+            .is_ok()
+        {
+            return Ok(vec![]);
+        }
+
+        // If not all votes are decrypted yet, tally_election will fail, so just wait
+        let tally = match tally_election(store, &election_tx) {
+            Ok(tally) => tally,
+            Err(_) => return Ok(vec![]),
+        };
+
+        let tally_tx = TallyTransaction::new(election_tx.id, tally);
+        let tally_tx = Signed::sign(&secret_key, tally_tx)?;
+        return Ok(vec![tally_tx.into()]);
+    }
+
+    Ok(vec![])
+}
+
 // TODO: Switch to batching
 fn produce_partials<S: Store>(
     store: &S,
@@ -489,38 +772,8 @@ fn produce_partials<S: Store>(
     // If there's no mix config, produce partial decryptions for every vote
     let mut rng = rand::thread_rng();
 
-    let share_txs: Vec<KeyGenShareTransaction> = store
-        .get_multiple(election_tx.id, TransactionType::KeyGenShare)
-        .into_iter()
-        .map(|tx| tx.into())
-        .collect();
-
-    let commit_txs: Vec<KeyGenCommitmentTransaction> = store
-        .get_multiple(election_tx.id, TransactionType::KeyGenCommitment)
-        .into_iter()
-        .map(|tx| tx.into())
-        .collect();
-
-    let commitments: Vec<(u8, KeygenCommitment)> = commit_txs
-        .iter()
-        .map(|tx| (tx.trustee_index, tx.commitment.clone()))
-        .collect();
-
-    let x25519_public_keys: Vec<(u8, x25519::PublicKey)> = commit_txs
-        .into_iter()
-        .map(|tx| (tx.trustee_index, tx.x25519_public_key))
-        .collect();
-
-    // Get all Shares shared with this trustee
-    let shares: Vec<(u8, EncryptedShare)> = share_txs
-        .into_iter()
-        .map(|tx| {
-            (
-                tx.trustee_index,
-                tx.shares.get(&trustee.index).unwrap().clone(),
-            )
-        })
-        .collect();
+    let (x25519_public_keys, commitments, shares) =
+        trustee_key_material(store, election_tx, trustee)?;
 
     // Produce partial decryptions
     let mut parial_txs = Vec::new();
@@ -557,12 +810,17 @@ fn produce_partials<S: Store>(
             }
         }
         None => {
-            let vote_txs = store.get_multiple(election_tx.id, TransactionType::Vote);
-
-            for vote_tx in vote_txs {
-                let vote_tx: VoteTransaction = vote_tx.into();
+            // Only the latest revision of each voter's vote is decrypted
+            for vote_tx in latest_votes(store, election_tx.id) {
+                for encrypted_vote in vote_tx?.encrypted_votes {
+                    // Votes in homomorphic contests are never decrypted individually
+                    let homomorphic = election_tx
+                        .get_contest(encrypted_vote.contest_index)
+                        .map_or(false, |contest| contest.homomorphic);
+                    if homomorphic {
+                        continue;
+                    }
 
-                for encrypted_vote in vote_tx.encrypted_votes {
                     let mut decrypt_shares = Vec::with_capacity(encrypted_vote.selections.len());
                     for ciphertext in encrypted_vote.selections {
                         let partial_decrypt = trustee.partial_decrypt(
@@ -595,6 +853,115 @@ fn produce_partials<S: Store>(
     return Ok(parial_txs);
 }
 
+// Produce a single partial decryption of the totals of each homomorphic contest
+fn produce_homomorphic_partials<S: Store>(
+    store: &S,
+    election_tx: &ElectionTransaction,
+    trustee: &Trustee,
+    voting_end_tx: &VotingEndTransaction,
+) -> Result<Vec<SignedTransaction>, Error> {
+    let public_key = crate::public_key();
+    let secret_key = crate::secret_key();
+    let mut rng = rand::thread_rng();
+
+    let homomorphic_contests: Vec<&Contest> = election_tx
+        .contests
+        .iter()
+        .filter(|contest| contest.homomorphic)
+        .collect();
+    if homomorphic_contests.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let (x25519_public_keys, commitments, shares) =
+        trustee_key_material(store, election_tx, trustee)?;
+
+    let mut parial_txs = Vec::with_capacity(homomorphic_contests.len());
+    for contest in homomorphic_contests {
+        let (totals, _num_votes) = homomorphic_aggregate(store, election_tx.id, contest)?;
+
+        let mut decrypt_shares = Vec::with_capacity(totals.len());
+        for ciphertext in totals {
+            let partial_decrypt = trustee.partial_decrypt(
+                &mut rng,
+                &secret_key,
+                &x25519_public_keys,
+                &commitments,
+                &shares,
+                &ciphertext,
+                election_tx.id,
+            )?;
+            decrypt_shares.push(partial_decrypt);
+        }
+
+        let partial_decrypt_tx = PartialDecryptionTransaction::new(
+            election_tx.id,
+            voting_end_tx.id,
+            0,
+            trustee.index,
+            contest.index,
+            public_key,
+            decrypt_shares,
+        );
+
+        let partial_decrypt_tx = Signed::sign(&secret_key, partial_decrypt_tx)?;
+        parial_txs.push(partial_decrypt_tx.into());
+    }
+
+    Ok(parial_txs)
+}
+
+// Gather the keygen material this trustee needs to partially decrypt, leaving out trustees disqualified by a complaint
+fn trustee_key_material<S: Store>(
+    store: &S,
+    election_tx: &ElectionTransaction,
+    trustee: &Trustee,
+) -> Result<
+    (
+        Vec<(u8, x25519::PublicKey)>,
+        Vec<(u8, KeygenCommitment)>,
+        Vec<(u8, EncryptedShare)>,
+    ),
+    Error,
+> {
+    let disqualified = disqualified_trustees(store, election_tx.id)?;
+
+    let share_txs: Vec<KeyGenShareTransaction> = store
+        .get_multiple(election_tx.id, TransactionType::KeyGenShare)
+        .map(|tx| tx.map(|tx| tx.into()))
+        .collect::<Result<_, _>>()?;
+
+    let commit_txs: Vec<KeyGenCommitmentTransaction> = store
+        .get_multiple(election_tx.id, TransactionType::KeyGenCommitment)
+        .map(|tx| tx.map(|tx| tx.into()))
+        .collect::<Result<_, _>>()?;
+
+    let commitments: Vec<(u8, KeygenCommitment)> = commit_txs
+        .iter()
+        .filter(|tx| !disqualified.contains(&tx.trustee_index))
+        .map(|tx| (tx.trustee_index, tx.commitment.clone()))
+        .collect();
+
+    let x25519_public_keys: Vec<(u8, x25519::PublicKey)> = commit_txs
+        .into_iter()
+        .map(|tx| (tx.trustee_index, tx.x25519_public_key))
+        .collect();
+
+    // Get all Shares shared with this trustee
+    let shares: Vec<(u8, EncryptedShare)> = share_txs
+        .into_iter()
+        .filter(|tx| !disqualified.contains(&tx.trustee_index))
+        .map(|tx| {
+            (
+                tx.trustee_index,
+                tx.shares.get(&trustee.index).unwrap().clone(),
+            )
+        })
+        .collect();
+
+    Ok((x25519_public_keys, commitments, shares))
+}
+
 fn trustee_from_election(
     election_tx: &ElectionTransaction,
     public_key: &PublicKey,